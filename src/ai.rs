@@ -0,0 +1,1394 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+
+use crate::board::{Board, GameCell, Outcome, Player, Position, LINES};
+use crate::notakto::NotaktoPosition;
+use crate::numerical::{self, NumericalPosition};
+use crate::quantum::QuantumPosition;
+use crate::qubic::QubicPosition;
+use crate::ultimate::{self, SubBoardState, UltimatePosition};
+use crate::wild::WildPosition;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy, // Random moves
+    Medium, // Wins, blocks and prefers good cells
+    Perfect, // Full negamax search, limited one on big boards
+}
+
+impl Difficulty {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "perfect" => Some(Difficulty::Perfect),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Perfect => "perfect",
+        }
+    }
+}
+
+// Returns a cell for the player whose turn it is.
+// The position must not be finished.
+pub fn choose_move(position: &Position, difficulty: Difficulty) -> usize {
+    let moves = position.legal_moves();
+    assert!(!moves.is_empty(), "no legal moves");
+
+    match difficulty {
+        Difficulty::Easy => random_move(&moves),
+        Difficulty::Medium if position.rules().misere => misere_heuristic_move(position, &moves),
+        Difficulty::Medium => heuristic_move(position, &moves),
+        Difficulty::Perfect => perfect_move(position, &moves),
+    }
+}
+
+fn random_move(moves: &[usize]) -> usize {
+    *moves.choose(&mut rand::thread_rng()).unwrap()
+}
+
+fn heuristic_move(position: &Position, moves: &[usize]) -> usize {
+    let board = position.board();
+    let lines = Lines::new(position.rules().lines(), position.rules().cells());
+    let player = position.turn();
+
+    // Winning move or blocking of the winning move of the opponent
+    for player in [player, player.other()] {
+        if let Some(&cell) = moves.iter().find(|&&cell| board.is_winning_move(cell, player)) {
+            return cell;
+        }
+    }
+
+    // Cells in the most lines which are still open are the best ones,
+    // like the center and then corners of the classic board
+    let moves = nearby_moves(position, moves);
+
+    lines.best_cell(board, player, &moves)
+}
+
+// Any move which does not complete a line, the least useful cells by the `cell_score` first
+fn misere_heuristic_move(position: &Position, moves: &[usize]) -> usize {
+    let board = position.board();
+    let player = position.turn();
+    let lines = Lines::new(position.rules().lines(), position.rules().cells());
+
+    let safe_moves: Vec<usize> = moves.iter()
+        .copied()
+        .filter(|&cell| !board.is_winning_move(cell, player))
+        .collect();
+
+    if safe_moves.is_empty() {
+        return random_move(moves);
+    }
+
+    let best_score = safe_moves.iter().map(|&cell| lines.cell_score(board, player, cell)).min().unwrap();
+
+    let best_moves: Vec<usize> = safe_moves.into_iter()
+        .filter(|&cell| lines.cell_score(board, player, cell) == best_score)
+        .collect();
+
+    random_move(&best_moves)
+}
+
+// All lines of the board and lines through every cell
+struct Lines {
+    lines: Vec<Vec<usize>>,
+    cell_lines: Vec<Vec<usize>>, // Indexes in the `lines`
+}
+
+impl Lines {
+    fn new(lines: Vec<Vec<usize>>, cells: usize) -> Self {
+        let mut cell_lines = vec![Vec::new(); cells];
+
+        for (id, line) in lines.iter().enumerate() {
+            for &cell in line {
+                cell_lines[cell].push(id);
+            }
+        }
+
+        Self { lines, cell_lines }
+    }
+
+    // How useful the empty cell is for the player, for attack and defence
+    fn cell_score(&self, board: &Board, player: Player, cell: usize) -> i32 {
+        self.cell_lines[cell].iter()
+            .map(|&id| {
+                let (owned, blocked) = count_marks(board, player, &self.lines[id]);
+
+                match (owned, blocked) {
+                    (_, 0) => (owned + 1) * (owned + 1),
+                    (0, _) => blocked * blocked,
+                    _ => 0, // Nobody can win by this line
+                }
+            })
+            .sum()
+    }
+
+    // One of the moves with the best `cell_score`
+    fn best_cell(&self, board: &Board, player: Player, moves: &[usize]) -> usize {
+        let scores: Vec<i32> = moves.iter().map(|&cell| self.cell_score(board, player, cell)).collect();
+        let best_score = scores.iter().copied().max().unwrap();
+
+        let best_moves: Vec<usize> = moves.iter()
+            .zip(scores)
+            .filter(|&(_, score)| score == best_score)
+            .map(|(&cell, _)| cell)
+            .collect();
+
+        random_move(&best_moves)
+    }
+
+    // The empty cell completes a line of the player
+    fn is_winning_move(&self, board: &Board, player: Player, cell: usize) -> bool {
+        self.cell_lines[cell].iter()
+            .any(|&id| self.lines[id].iter().all(|&val| val == cell || board.get(val) == player.cell()))
+    }
+
+    // Lines where only one player has marks, longer ones are much more valuable
+    fn evaluate(&self, board: &Board, player: Player) -> i32 {
+        self.lines.iter()
+            .map(|line| match count_marks(board, player, line) {
+                (0, 0) => 0,
+                (owned, 0) => 4i32.pow(owned as u32),
+                (0, blocked) => -(4i32.pow(blocked as u32)),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+// On big boards only cells near other marks are useful
+fn nearby_moves(position: &Position, moves: &[usize]) -> Vec<usize> {
+    let rules = position.rules();
+
+    if rules.cells() <= FULL_SEARCH_CELLS || position.moves().is_empty() {
+        return moves.to_vec();
+    }
+
+    let is_nearby = |cell: usize| position.moves().iter().any(|&val| {
+        let dx = (cell % rules.width).abs_diff(val % rules.width);
+        let dy = (cell / rules.width).abs_diff(val / rules.width);
+
+        dx <= NEARBY_DISTANCE && dy <= NEARBY_DISTANCE
+    });
+
+    moves.iter().copied().filter(|&cell| is_nearby(cell)).collect()
+}
+
+// Marks of the player and of the opponent
+fn count_marks(board: &Board, player: Player, line: &[usize]) -> (i32, i32) {
+    let owned = line.iter().filter(|&&val| board.get(val) == player.cell()).count() as i32;
+    let blocked = line.iter().filter(|&&val| board.get(val) == player.other().cell()).count() as i32;
+
+    (owned, blocked)
+}
+
+// Boards with more empty cells are searched only to this depth
+const SEARCH_DEPTH: usize = 4;
+// and only the best moves by the `cell_score` are searched
+const SEARCH_WIDTH: usize = 8;
+// Positions with less empty cells are searched completely
+const FULL_SEARCH_CELLS: usize = 10;
+// Cells which are farther from all marks are not considered on big boards
+const NEARBY_DISTANCE: usize = 2;
+
+const WIN_SCORE: i32 = 1_000_000;
+
+// Positions of any mode for the `negamax`
+trait Search {
+    fn outcome(&self) -> Outcome;
+    fn move_count(&self) -> usize;
+    fn turn(&self) -> Player;
+
+    // Moves worth searching, the best ones first
+    fn candidate_moves(&mut self) -> Vec<usize>;
+
+    fn apply(&mut self, cell: usize);
+    fn undo(&mut self);
+
+    // Score for the player whose turn it is, used at the depth limit
+    fn evaluate(&self) -> i32;
+}
+
+// One of the best moves found by the search to the depth
+fn search_move(search: &mut impl Search, depth: usize) -> usize {
+    let mut best_score = i32::MIN;
+    let mut best_moves = Vec::new();
+
+    for cell in search.candidate_moves() {
+        search.apply(cell);
+        let score = -negamax(search, depth - 1, -i32::MAX, i32::MAX);
+        search.undo();
+
+        if score > best_score {
+            best_score = score;
+            best_moves.clear();
+        }
+
+        if score == best_score {
+            best_moves.push(cell);
+        }
+    }
+
+    // Equal moves are chosen randomly, so games are not always the same
+    random_move(&best_moves)
+}
+
+// Score of the position for the player whose turn it is.
+// Faster wins and slower losses have bigger absolute values.
+// Positions at the depth limit are scored by `Search::evaluate`.
+fn negamax(search: &mut impl Search, depth: usize, mut alpha: i32, beta: i32) -> i32 {
+    // Usually the last move has won, but in Order & Chaos even a move of Chaos can complete the line of Order
+    match search.outcome() {
+        Outcome::Ongoing => (),
+        Outcome::Draw => return 0,
+        outcome if outcome.winner() == Some(search.turn()) => return WIN_SCORE - search.move_count() as i32,
+        _ => return search.move_count() as i32 - WIN_SCORE,
+    }
+
+    if depth == 0 {
+        return search.evaluate();
+    }
+
+    let mut best_score = -i32::MAX;
+
+    for cell in search.candidate_moves() {
+        search.apply(cell);
+        let score = -negamax(search, depth - 1, -beta, -alpha);
+        search.undo();
+
+        best_score = best_score.max(score);
+        alpha = alpha.max(score);
+
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    best_score
+}
+
+struct BoardSearch {
+    position: Position,
+    lines: Lines,
+    is_full_search: bool,
+}
+
+impl Search for BoardSearch {
+    fn outcome(&self) -> Outcome {
+        self.position.outcome()
+    }
+
+    fn move_count(&self) -> usize {
+        self.position.moves().len()
+    }
+
+    fn turn(&self) -> Player {
+        self.position.turn()
+    }
+
+    // All legal moves or only the best ones for not complete searches
+    fn candidate_moves(&mut self) -> Vec<usize> {
+        let mut moves = self.position.legal_moves();
+
+        if !self.is_full_search {
+            let board = self.position.board();
+            let player = self.position.turn();
+
+            // Useful cells are the worst ones in misère games
+            let sign = if self.position.rules().misere { 1 } else { -1 };
+
+            if !self.position.rules().misere {
+                moves = nearby_moves(&self.position, &moves);
+            }
+
+            moves.sort_by_cached_key(|&cell| sign * self.lines.cell_score(board, player, cell));
+            moves.truncate(SEARCH_WIDTH);
+        }
+
+        moves
+    }
+
+    fn apply(&mut self, cell: usize) {
+        self.position.apply_move(cell).unwrap();
+    }
+
+    fn undo(&mut self) {
+        self.position.undo_move();
+    }
+
+    fn evaluate(&self) -> i32 {
+        let score = self.lines.evaluate(self.position.board(), self.position.turn());
+
+        if self.position.rules().misere {
+            -score
+        }
+        else {
+            score
+        }
+    }
+}
+
+fn perfect_move(position: &Position, moves: &[usize]) -> usize {
+    let is_full_search = moves.len() <= FULL_SEARCH_CELLS;
+    let depth = if is_full_search { moves.len() } else { SEARCH_DEPTH };
+
+    let mut search = BoardSearch {
+        position: position.clone(),
+        lines: Lines::new(position.rules().lines(), position.rules().cells()),
+        is_full_search,
+    };
+
+    search_move(&mut search, depth)
+}
+
+// Moves of the ultimate tic-tac-toe are searched only to this depth
+const ULTIMATE_SEARCH_DEPTH: usize = 6;
+// and only the best moves by the `ultimate_move_score` are searched
+const ULTIMATE_SEARCH_WIDTH: usize = 9;
+
+// Sub-boards are much more valuable than cells in the evaluation
+const SUB_BOARD_WEIGHT: i32 = 20;
+
+// Returns a cell of the ultimate tic-tac-toe for the player whose turn it is.
+// The position must not be finished.
+pub fn choose_ultimate_move(position: &UltimatePosition, difficulty: Difficulty) -> usize {
+    let moves = position.legal_moves();
+    assert!(!moves.is_empty(), "no legal moves");
+
+    match difficulty {
+        Difficulty::Easy => random_move(&moves),
+        Difficulty::Medium => ultimate_heuristic_move(position, &moves),
+        Difficulty::Perfect => search_move(&mut position.clone(), ULTIMATE_SEARCH_DEPTH),
+    }
+}
+
+fn ultimate_heuristic_move(position: &UltimatePosition, moves: &[usize]) -> usize {
+    let mut position = position.clone();
+
+    let scores: Vec<i32> = moves.iter().map(|&cell| ultimate_move_score(&mut position, cell)).collect();
+    let best_score = scores.iter().copied().max().unwrap();
+
+    let best_moves: Vec<usize> = moves.iter()
+        .zip(scores)
+        .filter(|&(_, score)| score == best_score)
+        .map(|(&cell, _)| cell)
+        .collect();
+
+    random_move(&best_moves)
+}
+
+// How good the move is without a search, for the player whose turn it is.
+// Winning and blocking of sub-boards is good, sending the opponent
+// to a sub-board which the opponent can win or to any sub-board is bad.
+fn ultimate_move_score(position: &mut UltimatePosition, cell: usize) -> i32 {
+    let player = position.turn();
+    let is_blocking = wins_sub_board(position, cell, player.other());
+
+    let outcome = position.apply_move(cell).unwrap();
+    let sub_board = position.sub_board(ultimate::sub_board_of(cell));
+    let active = position.active_sub_boards();
+
+    let is_dangerous = active.iter().any(|&val| {
+        ultimate::sub_board_cells(val).into_iter()
+            .any(|cell| position.board().is_empty(cell) && wins_sub_board(position, cell, player.other()))
+    });
+
+    position.undo_move();
+
+    if let Outcome::Win { .. } = outcome {
+        return WIN_SCORE;
+    }
+
+    let mut score = 0;
+
+    if sub_board == SubBoardState::Won(player) {
+        score += 100;
+    }
+
+    if is_blocking {
+        score += 50;
+    }
+
+    if is_dangerous {
+        score -= 80;
+    }
+
+    if active.len() > 1 {
+        score -= 20;
+    }
+
+    score
+}
+
+// The empty cell completes a line of the player in its sub-board
+fn wins_sub_board(position: &UltimatePosition, cell: usize, player: Player) -> bool {
+    let cells = ultimate::sub_board_cells(ultimate::sub_board_of(cell));
+    let index = ultimate::cell_in_sub_board(cell);
+
+    LINES.iter()
+        .filter(|line| line.contains(&index))
+        .any(|line| line.iter().all(|&i| i == index || position.board().get(cells[i]) == player.cell()))
+}
+
+impl Search for UltimatePosition {
+    fn outcome(&self) -> Outcome {
+        UltimatePosition::outcome(self)
+    }
+
+    fn move_count(&self) -> usize {
+        self.moves().len()
+    }
+
+    fn turn(&self) -> Player {
+        UltimatePosition::turn(self)
+    }
+
+    fn candidate_moves(&mut self) -> Vec<usize> {
+        let mut moves = self.legal_moves();
+
+        moves.sort_by_cached_key(|&cell| -ultimate_move_score(self, cell));
+        moves.truncate(ULTIMATE_SEARCH_WIDTH);
+        moves
+    }
+
+    fn apply(&mut self, cell: usize) {
+        self.apply_move(cell).unwrap();
+    }
+
+    fn undo(&mut self) {
+        self.undo_move();
+    }
+
+    fn evaluate(&self) -> i32 {
+        ultimate_evaluate(self)
+    }
+}
+
+// Lines of won sub-boards and lines of cells in open sub-boards
+// where only one player has marks, for the player whose turn it is
+fn ultimate_evaluate(position: &UltimatePosition) -> i32 {
+    let player = position.turn();
+
+    // Like the `Lines::evaluate`, by marks or sub-boards of the player and of the opponent
+    let score = |owned: u32, blocked: u32| match (owned, blocked) {
+        (0, 0) => 0,
+        (owned, 0) => 4i32.pow(owned),
+        (0, blocked) => -(4i32.pow(blocked)),
+        _ => 0,
+    };
+
+    let sub_board_score: i32 = LINES.iter()
+        .map(|line| {
+            let states = line.map(|i| position.sub_board(i));
+
+            if states.contains(&SubBoardState::Full) {
+                return 0;
+            }
+
+            let owned = states.iter().filter(|&&val| val == SubBoardState::Won(player)).count() as u32;
+            let blocked = states.iter().filter(|&&val| val == SubBoardState::Won(player.other())).count() as u32;
+
+            score(owned, blocked)
+        })
+        .sum();
+
+    let cell_score: i32 = (0..9)
+        .filter(|&val| position.sub_board(val) == SubBoardState::Open)
+        .flat_map(|val| {
+            let cells = ultimate::sub_board_cells(val);
+
+            LINES.iter().map(move |line| {
+                let owned = line.iter().filter(|&&i| position.board().get(cells[i]) == player.cell()).count() as u32;
+                let blocked = line.iter().filter(|&&i| position.board().get(cells[i]) == player.other().cell()).count() as u32;
+
+                score(owned, blocked)
+            })
+        })
+        .sum();
+
+    sub_board_score * SUB_BOARD_WEIGHT + cell_score
+}
+
+// The cube has no far cells, but forced moves make the search narrow
+const QUBIC_SEARCH_DEPTH: usize = 6;
+
+// Returns a cell of the 3D tic-tac-toe for the player whose turn it is.
+// The position must not be finished.
+pub fn choose_qubic_move(position: &QubicPosition, difficulty: Difficulty) -> usize {
+    let moves = position.legal_moves();
+    assert!(!moves.is_empty(), "no legal moves");
+
+    let lines = position.lines().iter().map(|line| line.to_vec()).collect();
+    let lines = Lines::new(lines, position.board().cells().len());
+
+    match difficulty {
+        Difficulty::Easy => random_move(&moves),
+        Difficulty::Medium => qubic_heuristic_move(position, &lines, &moves),
+        Difficulty::Perfect => search_move(&mut QubicSearch { position: position.clone(), lines }, QUBIC_SEARCH_DEPTH),
+    }
+}
+
+fn qubic_heuristic_move(position: &QubicPosition, lines: &Lines, moves: &[usize]) -> usize {
+    let board = position.board();
+    let player = position.turn();
+
+    // Winning move or blocking of the winning move of the opponent
+    for player in [player, player.other()] {
+        if let Some(&cell) = moves.iter().find(|&&cell| lines.is_winning_move(board, player, cell)) {
+            return cell;
+        }
+    }
+
+    lines.best_cell(board, player, moves)
+}
+
+struct QubicSearch {
+    position: QubicPosition,
+    lines: Lines,
+}
+
+impl Search for QubicSearch {
+    fn outcome(&self) -> Outcome {
+        self.position.outcome()
+    }
+
+    fn move_count(&self) -> usize {
+        self.position.moves().len()
+    }
+
+    fn turn(&self) -> Player {
+        self.position.turn()
+    }
+
+    fn candidate_moves(&mut self) -> Vec<usize> {
+        let board = self.position.board();
+        let player = self.position.turn();
+        let mut moves = self.position.legal_moves();
+
+        // A win ends the game and a threat of the opponent has to be blocked,
+        // so forced sequences are searched deeper
+        for player in [player, player.other()] {
+            let forced: Vec<usize> = moves.iter()
+                .copied()
+                .filter(|&cell| self.lines.is_winning_move(board, player, cell))
+                .collect();
+
+            if !forced.is_empty() {
+                return forced;
+            }
+        }
+
+        moves.sort_by_cached_key(|&cell| -self.lines.cell_score(board, player, cell));
+        moves.truncate(SEARCH_WIDTH);
+        moves
+    }
+
+    fn apply(&mut self, cell: usize) {
+        self.position.apply_move(cell).unwrap();
+    }
+
+    fn undo(&mut self) {
+        self.position.undo_move();
+    }
+
+    fn evaluate(&self) -> i32 {
+        self.lines.evaluate(self.position.board(), self.position.turn())
+    }
+}
+
+// Boards of Notakto are bit masks of cells with X, the same for every symmetric board
+const DEAD_BOARD: u16 = 1 << 9;
+
+// Returns a cell of Notakto for the player whose turn it is.
+// The position must not be finished.
+pub fn choose_notakto_move(position: &NotaktoPosition, difficulty: Difficulty) -> usize {
+    let moves = position.legal_moves();
+    assert!(!moves.is_empty(), "no legal moves");
+
+    match difficulty {
+        Difficulty::Easy => random_move(&moves),
+        Difficulty::Medium => notakto_safe_move(position, &moves),
+        Difficulty::Perfect => notakto_perfect_move(position, &moves),
+    }
+}
+
+// Moves which do not kill a board, or at least not the last one
+fn notakto_safe_move(position: &NotaktoPosition, moves: &[usize]) -> usize {
+    let mut position = position.clone();
+
+    let mut danger = |cell: usize| {
+        let outcome = position.apply_move(cell).unwrap();
+        let is_killing = position.is_dead(position.board_of(cell));
+        position.undo_move();
+
+        match (outcome, is_killing) {
+            (Outcome::Loss { .. }, _) => 2,
+            (_, true) => 1,
+            _ => 0,
+        }
+    };
+
+    let dangers: Vec<i32> = moves.iter().map(|&cell| danger(cell)).collect();
+    let least_danger = dangers.iter().copied().min().unwrap();
+
+    let best_moves: Vec<usize> = moves.iter()
+        .zip(dangers)
+        .filter(|&(_, danger)| danger == least_danger)
+        .map(|(&cell, _)| cell)
+        .collect();
+
+    random_move(&best_moves)
+}
+
+// Positions are small enough for the complete search,
+// when there is no winning move the game is made longer
+fn notakto_perfect_move(position: &NotaktoPosition, moves: &[usize]) -> usize {
+    let boards: Vec<u16> = (0..position.boards())
+        .map(|board| {
+            if position.is_dead(board) {
+                return DEAD_BOARD;
+            }
+
+            (0..9)
+                .filter(|&i| position.board().get(position.cell(board, i)) == GameCell::First)
+                .fold(0, |mask, i| mask | 1 << i)
+        })
+        .collect();
+
+    let mut memo = HashMap::new();
+
+    let winning_moves: Vec<usize> = moves.iter()
+        .copied()
+        .filter(|&cell| {
+            let mut next = boards.clone();
+            let board = position.board_of(cell);
+            let index = (0..9).find(|&i| position.cell(board, i) == cell).unwrap();
+
+            next[board] = place_notakto_mark(next[board], index);
+            !is_notakto_win(next, &mut memo)
+        })
+        .collect();
+
+    if winning_moves.is_empty() {
+        return notakto_safe_move(position, moves);
+    }
+
+    random_move(&winning_moves)
+}
+
+// Whether the player whose turn it is wins
+fn is_notakto_win(mut boards: Vec<u16>, memo: &mut HashMap<Vec<u16>, bool>) -> bool {
+    // The opponent has killed the last board
+    if boards.iter().all(|&val| val == DEAD_BOARD) {
+        return true;
+    }
+
+    for val in boards.iter_mut() {
+        *val = canonical_notakto_board(*val);
+    }
+
+    boards.sort_unstable();
+
+    if let Some(&result) = memo.get(&boards) {
+        return result;
+    }
+
+    let result = (0..boards.len())
+        .filter(|&board| boards[board] != DEAD_BOARD)
+        .any(|board| {
+            (0..9)
+                .filter(|&i| boards[board] & 1 << i == 0)
+                .any(|i| {
+                    let mut next = boards.clone();
+                    next[board] = place_notakto_mark(next[board], i);
+
+                    !is_notakto_win(next, memo)
+                })
+        });
+
+    memo.insert(boards, result);
+    result
+}
+
+fn place_notakto_mark(board: u16, index: usize) -> u16 {
+    let board = board | 1 << index;
+
+    if LINES.iter().any(|line| line.iter().all(|&i| board & 1 << i != 0)) {
+        DEAD_BOARD
+    }
+    else {
+        board
+    }
+}
+
+// The smallest mask among rotations and reflections of the board
+fn canonical_notakto_board(board: u16) -> u16 {
+    if board == DEAD_BOARD {
+        return board;
+    }
+
+    let transform = |board: u16, map: fn(usize, usize) -> (usize, usize)| {
+        (0..9)
+            .filter(|&i| board & 1 << i != 0)
+            .fold(0, |mask, i| {
+                let (row, column) = map(i / 3, i % 3);
+                mask | 1 << (row * 3 + column)
+            })
+    };
+
+    let rotate: fn(usize, usize) -> (usize, usize) = |row, column| (column, 2 - row);
+    let mirror: fn(usize, usize) -> (usize, usize) = |row, column| (row, 2 - column);
+
+    let mut result = board;
+    let mut val = board;
+
+    for _ in 0..4 {
+        val = transform(val, rotate);
+        result = result.min(val).min(transform(val, mirror));
+    }
+
+    result
+}
+
+// Wild positions with more empty cells are searched only to this depth
+const WILD_SEARCH_DEPTH: usize = 4;
+
+// Returns a cell and a mark of wild tic-tac-toe or Order & Chaos
+// for the player whose turn it is. The position must not be finished.
+pub fn choose_wild_move(position: &WildPosition, difficulty: Difficulty) -> (usize, Player) {
+    let cells = position.legal_moves();
+    assert!(!cells.is_empty(), "no legal moves");
+
+    let is_full_search = difficulty == Difficulty::Perfect && cells.len() <= FULL_SEARCH_CELLS;
+
+    let mut search = WildSearch {
+        position: position.clone(),
+        lines: Lines::new(position.rules().lines(), position.rules().cells()),
+        is_full_search,
+    };
+
+    let val = match difficulty {
+        Difficulty::Easy => return (random_move(&cells), *[Player::First, Player::Second].choose(&mut rand::thread_rng()).unwrap()),
+        Difficulty::Medium => wild_heuristic_move(&mut search),
+        Difficulty::Perfect if is_full_search => search_move(&mut search, cells.len()),
+        Difficulty::Perfect => search_move(&mut search, WILD_SEARCH_DEPTH),
+    };
+
+    decode_wild_move(val)
+}
+
+// Moves of the wild search are cells with marks, `2 * cell` is X and `2 * cell + 1` is O
+fn encode_wild_move(cell: usize, symbol: Player) -> usize {
+    2 * cell + symbol.index()
+}
+
+fn decode_wild_move(val: usize) -> (usize, Player) {
+    match val % 2 {
+        0 => (val / 2, Player::First),
+        _ => (val / 2, Player::Second),
+    }
+}
+
+// One of the candidate moves with the best `WildSearch::move_score`
+fn wild_heuristic_move(search: &mut WildSearch) -> usize {
+    let moves = search.candidate_moves();
+    let scores: Vec<i32> = moves.iter().map(|&val| search.move_score(decode_wild_move(val))).collect();
+    let best_score = scores.iter().copied().max().unwrap();
+
+    let best_moves: Vec<usize> = moves.iter()
+        .zip(scores)
+        .filter(|&(_, score)| score == best_score)
+        .map(|(&val, _)| val)
+        .collect();
+
+    random_move(&best_moves)
+}
+
+struct WildSearch {
+    position: WildPosition,
+    lines: Lines,
+    is_full_search: bool,
+}
+
+impl WildSearch {
+    fn is_order(&self) -> bool {
+        self.position.is_order_and_chaos() && self.position.turn() == Player::First
+    }
+
+    fn is_chaos(&self) -> bool {
+        self.position.is_order_and_chaos() && self.position.turn() == Player::Second
+    }
+
+    fn completes_line(&self, (cell, symbol): (usize, Player)) -> bool {
+        self.lines.is_winning_move(self.position.board(), symbol, cell)
+    }
+
+    // The move lets the next player complete a line. Only lines through
+    // the cell are changed and other lines would have been completed already.
+    fn makes_threat(&self, (cell, symbol): (usize, Player)) -> bool {
+        let win_length = self.position.rules().win_length as i32;
+
+        self.lines.cell_lines[cell].iter()
+            .any(|&id| count_marks(self.position.board(), symbol, &self.lines.lines[id]) == (win_length - 2, 0))
+    }
+
+    // Order extends lines of the same marks, Chaos and players of
+    // wild tic-tac-toe prefer to spoil lines of the other marks
+    fn move_score(&self, (cell, symbol): (usize, Player)) -> i32 {
+        let board = self.position.board();
+
+        self.lines.cell_lines[cell].iter()
+            .map(|&id| {
+                let (same, other) = count_marks(board, symbol, &self.lines.lines[id]);
+
+                match (same, other) {
+                    (_, 0) if self.is_order() => (same + 1) * (same + 1),
+                    (_, 0) => -(same + 1),
+                    (0, _) if !self.is_order() => other * other,
+                    _ => 0,
+                }
+            })
+            .sum()
+    }
+}
+
+impl Search for WildSearch {
+    fn outcome(&self) -> Outcome {
+        self.position.outcome()
+    }
+
+    fn move_count(&self) -> usize {
+        self.position.moves().len()
+    }
+
+    fn turn(&self) -> Player {
+        self.position.turn()
+    }
+
+    fn candidate_moves(&mut self) -> Vec<usize> {
+        let all_moves: Vec<(usize, Player)> = self.position.legal_moves()
+            .into_iter()
+            .flat_map(|cell| [(cell, Player::First), (cell, Player::Second)])
+            .collect();
+
+        let completing: Vec<(usize, Player)> = all_moves.iter()
+            .copied()
+            .filter(|&val| self.completes_line(val))
+            .collect();
+
+        let mut moves = if self.is_chaos() && !completing.is_empty() {
+            // Every line of Order has to be blocked by the other mark
+            completing.iter().map(|&(cell, symbol)| (cell, symbol.other())).collect()
+        }
+        else if !completing.is_empty() {
+            completing
+        }
+        else if self.is_order() {
+            all_moves
+        }
+        else {
+            let safe_moves: Vec<(usize, Player)> = all_moves.iter()
+                .copied()
+                .filter(|&val| !self.makes_threat(val))
+                .collect();
+
+            // Every move loses, so any of them is enough
+            if safe_moves.is_empty() {
+                all_moves[..1].to_vec()
+            }
+            else {
+                safe_moves
+            }
+        };
+
+        moves.sort_by_cached_key(|&val| -self.move_score(val));
+
+        if !self.is_full_search {
+            moves.truncate(SEARCH_WIDTH);
+        }
+
+        moves.into_iter().map(|(cell, symbol)| encode_wild_move(cell, symbol)).collect()
+    }
+
+    fn apply(&mut self, val: usize) {
+        let (cell, symbol) = decode_wild_move(val);
+        self.position.apply_move(cell, symbol).unwrap();
+    }
+
+    fn undo(&mut self) {
+        self.position.undo_move();
+    }
+
+    // Only Order & Chaos is evaluated, wild positions are decided by threats
+    fn evaluate(&self) -> i32 {
+        if !self.position.is_order_and_chaos() {
+            return 0;
+        }
+
+        let board = self.position.board();
+
+        // Lines where Order still can make five same marks
+        let score: i32 = self.lines.lines.iter()
+            .map(|line| match count_marks(board, Player::First, line) {
+                (owned, 0) => 4i32.pow(owned as u32),
+                (0, blocked) => 4i32.pow(blocked as u32),
+                _ => 0,
+            })
+            .sum();
+
+        match self.position.turn() {
+            Player::First => score,
+            _ => -score,
+        }
+    }
+}
+
+// Returns a cell and a number of numerical tic-tac-toe for the player
+// whose turn it is. The position must not be finished.
+pub fn choose_numerical_move(position: &NumericalPosition, difficulty: Difficulty) -> (usize, u8) {
+    let cells = position.legal_moves();
+    assert!(!cells.is_empty(), "no legal moves");
+
+    let mut position = position.clone();
+
+    let val = match difficulty {
+        Difficulty::Easy => {
+            let numbers = position.remaining_numbers(position.turn());
+            return (random_move(&cells), *numbers.choose(&mut rand::thread_rng()).unwrap());
+        }
+        Difficulty::Medium => random_move(&position.candidate_moves()),
+        Difficulty::Perfect => search_numerical_move(&mut position),
+    };
+
+    decode_numerical_move(val)
+}
+
+// Kind of the score in the memo, searches with narrow windows find only bounds of scores
+#[derive(Clone, Copy)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+// Numbers in cells, zero for empty ones. They also tell the remaining numbers and the turn.
+type NumericalKey = [u8; 9];
+
+// Like `search_move` to the end of the game, transpositions of moves are searched only once
+fn search_numerical_move(position: &mut NumericalPosition) -> usize {
+    let mut memo = HashMap::new();
+    let mut best_score = i32::MIN;
+    let mut best_moves = Vec::new();
+
+    for val in position.candidate_moves() {
+        position.apply(val);
+        let score = -numerical_negamax(position, -i32::MAX, i32::MAX, &mut memo);
+        position.undo();
+
+        if score > best_score {
+            best_score = score;
+            best_moves.clear();
+        }
+
+        if score == best_score {
+            best_moves.push(val);
+        }
+    }
+
+    random_move(&best_moves)
+}
+
+// The `negamax` with the memo. Scores depend on the count of moves, which is the same for all keys of a position.
+fn numerical_negamax(position: &mut NumericalPosition, mut alpha: i32, mut beta: i32, memo: &mut HashMap<NumericalKey, (i32, Bound)>) -> i32 {
+    match position.outcome() {
+        Outcome::Ongoing => (),
+        Outcome::Draw => return 0,
+        outcome if outcome.winner() == Some(position.turn()) => return WIN_SCORE - position.moves().len() as i32,
+        _ => return position.moves().len() as i32 - WIN_SCORE,
+    }
+
+    let key = canonical_numerical_key(std::array::from_fn(|cell| position.number(cell).unwrap_or(0)));
+    let original_alpha = alpha;
+
+    if let Some(&(score, bound)) = memo.get(&key) {
+        match bound {
+            Bound::Exact => return score,
+            Bound::Lower => alpha = alpha.max(score),
+            Bound::Upper => beta = beta.min(score),
+        }
+
+        if alpha >= beta {
+            return score;
+        }
+    }
+
+    let mut best_score = -i32::MAX;
+
+    for val in position.candidate_moves() {
+        position.apply(val);
+        let score = -numerical_negamax(position, -beta, -alpha, memo);
+        position.undo();
+
+        best_score = best_score.max(score);
+        alpha = alpha.max(score);
+
+        if alpha >= beta {
+            break;
+        }
+    }
+
+    let bound = if best_score <= original_alpha {
+        Bound::Upper
+    }
+    else if best_score >= beta {
+        Bound::Lower
+    }
+    else {
+        Bound::Exact
+    };
+
+    memo.insert(key, (best_score, bound));
+    best_score
+}
+
+// The smallest key of the 8 symmetric boards, they have the same scores
+fn canonical_numerical_key(key: NumericalKey) -> NumericalKey {
+    let transform = |key: NumericalKey, map: fn(usize, usize) -> (usize, usize)| {
+        let mut result = [0; 9];
+
+        for (i, &number) in key.iter().enumerate() {
+            let (row, column) = map(i / 3, i % 3);
+            result[row * 3 + column] = number;
+        }
+
+        result
+    };
+
+    let rotate: fn(usize, usize) -> (usize, usize) = |row, column| (column, 2 - row);
+    let mirror: fn(usize, usize) -> (usize, usize) = |row, column| (row, 2 - column);
+
+    let mut result = key;
+    let mut val = key;
+
+    for _ in 0..4 {
+        val = transform(val, rotate);
+        result = result.min(val).min(transform(val, mirror));
+    }
+
+    result
+}
+
+// Moves of the numerical search are `10 * cell + number`
+fn encode_numerical_move(cell: usize, number: u8) -> usize {
+    10 * cell + number as usize
+}
+
+fn decode_numerical_move(val: usize) -> (usize, u8) {
+    (val / 10, (val % 10) as u8)
+}
+
+// The player whose turn it is can complete a line by one of the remaining numbers
+fn has_numerical_threat(position: &NumericalPosition) -> bool {
+    let numbers = position.remaining_numbers(position.turn());
+
+    LINES.iter().any(|line| {
+        let empty: Vec<usize> = line.iter().copied().filter(|&cell| position.number(cell).is_none()).collect();
+        let sum: u8 = line.iter().filter_map(|&cell| position.number(cell)).sum();
+
+        empty.len() == 1 && sum < numerical::MAGIC_SUM && numbers.contains(&(numerical::MAGIC_SUM - sum))
+    })
+}
+
+impl Search for NumericalPosition {
+    fn outcome(&self) -> Outcome {
+        NumericalPosition::outcome(self)
+    }
+
+    fn move_count(&self) -> usize {
+        self.moves().len()
+    }
+
+    fn turn(&self) -> Player {
+        NumericalPosition::turn(self)
+    }
+
+    // Winning moves, or moves which do not let the opponent complete a line
+    fn candidate_moves(&mut self) -> Vec<usize> {
+        let numbers = self.remaining_numbers(NumericalPosition::turn(self));
+
+        let all_moves: Vec<usize> = self.legal_moves()
+            .into_iter()
+            .flat_map(|cell| numbers.iter().map(move |&number| encode_numerical_move(cell, number)))
+            .collect();
+
+        let mut winning_moves = Vec::new();
+        let mut safe_moves = Vec::new();
+
+        for &val in &all_moves {
+            let (cell, number) = decode_numerical_move(val);
+
+            match self.apply_move(cell, number).unwrap() {
+                Outcome::Win { .. } => winning_moves.push(val),
+                _ if !has_numerical_threat(self) => safe_moves.push(val),
+                _ => (),
+            }
+
+            self.undo_move();
+        }
+
+        if !winning_moves.is_empty() {
+            winning_moves
+        }
+        else if !safe_moves.is_empty() {
+            safe_moves
+        }
+        else {
+            // Every move loses, so any of them is enough
+            all_moves[..1].to_vec()
+        }
+    }
+
+    fn apply(&mut self, val: usize) {
+        let (cell, number) = decode_numerical_move(val);
+        self.apply_move(cell, number).unwrap();
+    }
+
+    fn undo(&mut self) {
+        self.undo_move();
+    }
+
+    // Positions are always searched completely
+    fn evaluate(&self) -> i32 {
+        0
+    }
+}
+
+// Quantum positions are searched only to this depth
+const QUANTUM_SEARCH_DEPTH: usize = 4;
+const QUANTUM_MEDIUM_DEPTH: usize = 2;
+
+// Returns two cells of the spooky mark for the player whose turn it is,
+// the same cell twice for the last free cell, or the cell for the measurement
+// if the cycle has to be collapsed. The position must not be finished.
+pub fn choose_quantum_move(position: &QuantumPosition, difficulty: Difficulty) -> [usize; 2] {
+    let mut search = QuantumSearch { positions: vec![position.clone()] };
+    let moves = search.candidate_moves();
+    assert!(!moves.is_empty(), "no legal moves");
+
+    let val = match difficulty {
+        Difficulty::Easy => random_move(&moves),
+        Difficulty::Medium => search_move(&mut search, QUANTUM_MEDIUM_DEPTH),
+        Difficulty::Perfect => search_move(&mut search, QUANTUM_SEARCH_DEPTH),
+    };
+
+    // The move after the measurement is searched again
+    match decode_quantum_move(val) {
+        (Some(cell), _) => [cell, cell],
+        (None, cells) => cells,
+    }
+}
+
+// The player who measures the cycle makes the next move too, so the search
+// joins them: `100 * (measurement + 1) + 9 * cell + other`, without the
+// measurement part when there is no cycle
+fn encode_quantum_move(measurement: Option<usize>, [cell, other]: [usize; 2]) -> usize {
+    measurement.map_or(0, |val| 100 * (val + 1)) + 9 * cell + other
+}
+
+fn decode_quantum_move(val: usize) -> (Option<usize>, [usize; 2]) {
+    let measurement = (val >= 100).then(|| val / 100 - 1);
+    let val = val % 100;
+
+    (measurement, [val / 9, val % 9])
+}
+
+// Measurements change many cells at once, so positions are copied instead of undoing moves
+struct QuantumSearch {
+    positions: Vec<QuantumPosition>,
+}
+
+impl QuantumSearch {
+    fn position(&self) -> &QuantumPosition {
+        self.positions.last().unwrap()
+    }
+}
+
+impl Search for QuantumSearch {
+    fn outcome(&self) -> Outcome {
+        self.position().outcome()
+    }
+
+    fn move_count(&self) -> usize {
+        self.position().actions().len()
+    }
+
+    // The measurement which finishes the game does not pass the turn, so it is counted by moves of the search
+    fn turn(&self) -> Player {
+        match self.positions.len() % 2 {
+            1 => self.positions[0].turn(),
+            _ => self.positions[0].turn().other(),
+        }
+    }
+
+    fn candidate_moves(&mut self) -> Vec<usize> {
+        let position = self.position();
+
+        let Some(mark) = position.cycle() else {
+            return position.legal_moves().into_iter().map(|cells| encode_quantum_move(None, cells)).collect();
+        };
+
+        let mut moves = Vec::new();
+
+        for &cell in &position.marks()[mark] {
+            let mut next = position.clone();
+
+            // The measurement can finish the game
+            if next.collapse(cell).unwrap() != Outcome::Ongoing {
+                moves.push(encode_quantum_move(Some(cell), [0, 0]));
+                continue;
+            }
+
+            moves.extend(next.legal_moves().into_iter().map(|cells| encode_quantum_move(Some(cell), cells)));
+        }
+
+        moves
+    }
+
+    fn apply(&mut self, val: usize) {
+        let mut position = self.position().clone();
+        let (measurement, [cell, other]) = decode_quantum_move(val);
+
+        if let Some(measurement) = measurement {
+            position.collapse(measurement).unwrap();
+        }
+
+        if position.outcome() == Outcome::Ongoing {
+            position.apply_move(cell, other).unwrap();
+        }
+
+        self.positions.push(position);
+    }
+
+    fn undo(&mut self) {
+        self.positions.pop();
+    }
+
+    // Lines of classical marks where only one player has marks
+    fn evaluate(&self) -> i32 {
+        let position = self.position();
+        let player = position.turn();
+
+        LINES.iter()
+            .map(|line| match count_marks(position.board(), player, line) {
+                (0, 0) => 0,
+                (owned, 0) => 4i32.pow(owned as u32),
+                (0, blocked) => -(4i32.pow(blocked as u32)),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Rules;
+
+    fn play(rules: Rules, moves: &[usize]) -> Position {
+        let mut position = Position::new(rules);
+
+        for &cell in moves {
+            position.apply_move(cell).unwrap();
+        }
+
+        position
+    }
+
+    // Every move of the opponent is tried, the bot answers by the search
+    fn assert_never_loses(position: &mut Position, bot: Player) {
+        if position.outcome() != Outcome::Ongoing {
+            assert_ne!(position.outcome().winner(), Some(bot.other()), "lost after {:?}", position.moves());
+            return;
+        }
+
+        let moves = if position.turn() == bot {
+            vec![choose_move(position, Difficulty::Perfect)]
+        }
+        else {
+            position.legal_moves()
+        };
+
+        for cell in moves {
+            position.apply_move(cell).unwrap();
+            assert_never_loses(position, bot);
+            position.undo_move();
+        }
+    }
+
+    #[test]
+    fn perfect_never_loses_classic() {
+        for bot in [Player::First, Player::Second] {
+            assert_never_loses(&mut Position::default(), bot);
+        }
+    }
+
+    // Easy plays random moves, so only the other difficulties are checked
+    #[test]
+    fn takes_immediate_wins() {
+        // X wins by 2, O would win by 5
+        let classic = play(Rules::default(), &[0, 3, 1, 4]);
+        // Four of X in the row 1, both ends are open
+        let gomoku = play(Rules::gomoku(false), &[16, 46, 17, 47, 18, 48, 19, 64]);
+
+        for difficulty in [Difficulty::Medium, Difficulty::Perfect] {
+            assert_eq!(choose_move(&classic, difficulty), 2);
+            assert!([15, 20].contains(&choose_move(&gomoku, difficulty)));
+        }
+    }
+
+    #[test]
+    fn blocks_immediate_threats() {
+        // O has to block the top row
+        let classic = play(Rules::default(), &[0, 4, 2]);
+        // Four of O in the row 3, only the right end is open
+        let gomoku = play(Rules::gomoku(false), &[45, 46, 0, 47, 224, 48, 200, 49]);
+
+        for difficulty in [Difficulty::Medium, Difficulty::Perfect] {
+            assert_eq!(choose_move(&classic, difficulty), 1);
+            assert_eq!(choose_move(&gomoku, difficulty), 50);
+        }
+    }
+
+    // The first player wins Notakto on one board, so the exact search never loses it
+    #[test]
+    fn perfect_never_loses_notakto() {
+        fn check(position: &mut NotaktoPosition) {
+            if position.legal_moves().is_empty() {
+                return;
+            }
+
+            let moves = match position.turn() {
+                Player::First => vec![choose_notakto_move(position, Difficulty::Perfect)],
+                _ => position.legal_moves(),
+            };
+
+            for cell in moves {
+                let outcome = position.apply_move(cell).unwrap();
+                assert_ne!(outcome.winner(), Some(Player::Second), "lost after {:?}", position.moves());
+
+                check(position);
+                position.undo_move();
+            }
+        }
+
+        check(&mut NotaktoPosition::new(1));
+    }
+}
@@ -0,0 +1,626 @@
+// Pure tic-tac-toe rules without any Discord specific things,
+// so they can be reused by bots, replays and other frontends.
+
+// Cell indexes of the classic board, other boards are numbered
+// the same way, row by row:
+// 0 1 2
+// 3 4 5
+// 6 7 8
+pub const LINES: [[usize; 3]; 8] = [
+    // Horizontal
+    [0, 1, 2],
+    [3, 4, 5],
+    [6, 7, 8],
+
+    // Vertical
+    [0, 3, 6],
+    [1, 4, 7],
+    [2, 5, 8],
+
+    // Diagonal
+    [0, 4, 8], // Left to right
+    [2, 4, 6], // Right to left
+];
+
+pub const CELL_NAMES: [&str; 9] = [
+    "top left", "top", "top right",
+    "left", "center", "right",
+    "bottom left", "bottom", "bottom right",
+];
+
+pub const LINE_NAMES: [&str; 8] = [
+    "top row", "middle row", "bottom row",
+    "left column", "middle column", "right column",
+    "main diagonal", "anti-diagonal",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum GameCell {
+    #[default]
+    None,
+    First,
+    Second,
+    Third,
+    Fourth,
+}
+
+impl GameCell {
+    pub fn player(self) -> Option<Player> {
+        match self {
+            GameCell::None => None,
+            GameCell::First => Some(Player::First),
+            GameCell::Second => Some(Player::Second),
+            GameCell::Third => Some(Player::Third),
+            GameCell::Fourth => Some(Player::Fourth),
+        }
+    }
+}
+
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Player {
+    First, // X
+    Second, // O
+    Third, // Δ, only in games of three or four players
+    Fourth, // □, only in games of four players
+}
+
+impl Player {
+    // The opponent in games of two players
+    pub fn other(self) -> Self {
+        self.next(2)
+    }
+
+    // Whose turn is after this player in games of `players` players
+    pub fn next(self, players: usize) -> Self {
+        Self::from_index((self.index() + 1) % players)
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            Player::First => 0,
+            Player::Second => 1,
+            Player::Third => 2,
+            Player::Fourth => 3,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Self {
+        match index {
+            0 => Player::First,
+            1 => Player::Second,
+            2 => Player::Third,
+            3 => Player::Fourth,
+            _ => panic!("player index out of bounds"),
+        }
+    }
+
+    pub fn cell(self) -> GameCell {
+        match self {
+            Player::First => GameCell::First,
+            Player::Second => GameCell::Second,
+            Player::Third => GameCell::Third,
+            Player::Fourth => GameCell::Fourth,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Ongoing,
+    Win { player: Player, line: usize }, // `line` is an index in the `Rules::lines`
+    Loss { player: Player, line: usize }, // The player has completed the line in a misère game
+    Blocked { player: Player }, // The player has filled the board without a line, Chaos in Order & Chaos
+    Draw,
+}
+
+impl Outcome {
+    pub fn winner(self) -> Option<Player> {
+        match self {
+            Outcome::Win { player, .. } => Some(player),
+            Outcome::Loss { player, .. } => Some(player.other()),
+            Outcome::Blocked { player } => Some(player),
+            Outcome::Ongoing | Outcome::Draw => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    OutOfBounds,
+    Occupied,
+    GameOver,
+    InactiveSubBoard, // Ultimate tic-tac-toe requires a move in another sub-board
+    DeadBoard, // The board of Notakto already has three in a row
+    UnavailableNumber, // The number is already placed or belongs to the other player in numerical tic-tac-toe
+    SameCell, // Both cells of the spooky mark of quantum tic-tac-toe are the same, or differ in the last free cell
+    CollapsePending, // The cycle of spooky marks has to be measured before the next move
+    InvalidCollapse, // There is no cycle, or the cell is not one of the cells of the mark which has closed it
+}
+
+// Size of the board and how many marks in a row are needed for a win
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Rules {
+    pub width: usize,
+    pub height: usize,
+    pub win_length: usize,
+    pub exact: bool, // Longer lines do not win, like in the standard gomoku
+    pub misere: bool, // The player who completes a line loses
+    pub players: usize, // Marks take turns in the order of `Player`, more than two only in tic-tac-toe
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            width: 3,
+            height: 3,
+            win_length: 3,
+            exact: false,
+            misere: false,
+            players: 2,
+        }
+    }
+}
+
+// Steps of lines in the order of their indexes
+const DIRECTIONS: [(isize, isize); 4] = [
+    (1, 0), // Horizontal
+    (0, 1), // Vertical
+    (1, 1), // Diagonal, left to right
+    (-1, 1), // Diagonal, right to left
+];
+
+impl Rules {
+    // The `win_length` must fit in the board at least in one direction
+    pub fn new(width: usize, height: usize, win_length: usize) -> Option<Self> {
+        let rules = Self { width, height, win_length, exact: false, misere: false, players: 2 };
+        let is_valid = width > 0 && height > 0 && win_length > 0 && win_length <= width.max(height);
+
+        is_valid.then_some(rules)
+    }
+
+    // Five in a row on the 15×15 board, the free-style one allows longer lines
+    pub fn gomoku(exact: bool) -> Self {
+        Self {
+            width: 15,
+            height: 15,
+            win_length: 5,
+            exact,
+            misere: false,
+            players: 2,
+        }
+    }
+
+    // The original 3×3 game
+    pub fn is_classic(self) -> bool {
+        self == Self::default()
+    }
+
+    pub fn cells(self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn describe(self) -> String {
+        let description = if self.exact {
+            format!("{}×{}, exactly {} in a row", self.width, self.height, self.win_length)
+        }
+        else {
+            format!("{}×{}, {} in a row", self.width, self.height, self.win_length)
+        };
+
+        if self.misere {
+            format!("{}, misère", description)
+        }
+        else if self.players > 2 {
+            format!("{}, {} players", description, self.players)
+        }
+        else {
+            description
+        }
+    }
+
+    fn is_win_length(self, length: usize) -> bool {
+        if self.exact {
+            length == self.win_length
+        }
+        else {
+            length >= self.win_length
+        }
+    }
+
+    // Cells of every line of `win_length` cells. Lines are sorted by the direction
+    // (horizontal, vertical, diagonal, anti-diagonal) and then by the first cell,
+    // so indexes match the `LINES` for the classic board.
+    pub fn lines(self) -> Vec<Vec<usize>> {
+        (0..self.line_count()).map(|id| self.line(id)).collect()
+    }
+
+    pub fn line_count(self) -> usize {
+        (0..DIRECTIONS.len()).map(|direction| self.line_starts(direction).len()).sum()
+    }
+
+    // Cells of the line with the index `id`
+    pub fn line(self, id: usize) -> Vec<usize> {
+        let mut id = id;
+
+        for direction in 0..DIRECTIONS.len() {
+            let starts = self.line_starts(direction);
+
+            if id < starts.len() {
+                return self.line_cells(starts[id], direction);
+            }
+
+            id -= starts.len();
+        }
+
+        panic!("line index out of bounds");
+    }
+
+    // Index of the line which starts at the `start` cell in the `direction`
+    fn line_id(self, start: usize, direction: usize) -> usize {
+        let before: usize = (0..direction).map(|val| self.line_starts(val).len()).sum();
+        let pos = self.line_starts(direction).iter().position(|&val| val == start).unwrap();
+
+        before + pos
+    }
+
+    // First cells of all lines in the `direction`
+    fn line_starts(self, direction: usize) -> Vec<usize> {
+        (0..self.cells())
+            .filter(|&cell| self.line_fits(cell, direction))
+            .collect()
+    }
+
+    fn line_fits(self, start: usize, direction: usize) -> bool {
+        let (dx, dy) = DIRECTIONS[direction];
+        let steps = self.win_length as isize - 1;
+
+        let x = (start % self.width) as isize + dx * steps;
+        let y = (start / self.width) as isize + dy * steps;
+
+        (0..self.width as isize).contains(&x) && (0..self.height as isize).contains(&y)
+    }
+
+    fn line_cells(self, start: usize, direction: usize) -> Vec<usize> {
+        let (dx, dy) = DIRECTIONS[direction];
+        let step = dy * self.width as isize + dx;
+
+        (0..self.win_length)
+            .map(|i| (start as isize + step * i as isize) as usize)
+            .collect()
+    }
+
+    // Neighbour of the cell in the direction, if it is on the board
+    fn step(self, cell: usize, (dx, dy): (isize, isize)) -> Option<usize> {
+        let x = (cell % self.width) as isize + dx;
+        let y = (cell / self.width) as isize + dy;
+
+        let is_inside = (0..self.width as isize).contains(&x) && (0..self.height as isize).contains(&y);
+        is_inside.then(|| y as usize * self.width + x as usize)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Board {
+    rules: Rules,
+    cells: Vec<GameCell>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(Rules::default())
+    }
+}
+
+impl Board {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            rules,
+            cells: vec![GameCell::None; rules.cells()],
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn width(&self) -> usize {
+        self.rules.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rules.height
+    }
+
+    pub fn cells(&self) -> &[GameCell] {
+        &self.cells
+    }
+
+    pub fn get(&self, cell: usize) -> GameCell {
+        self.cells[cell]
+    }
+
+    pub fn is_empty(&self, cell: usize) -> bool {
+        self.cells[cell] == GameCell::None
+    }
+
+    // For boards with their own rules of moves, like the ultimate tic-tac-toe
+    pub fn set(&mut self, cell: usize, value: GameCell) {
+        self.cells[cell] = value;
+    }
+
+    pub fn is_full(&self) -> bool {
+        !self.cells.contains(&GameCell::None)
+    }
+
+    // Whether the mark of the `player` in the empty `cell` would win
+    pub fn is_winning_move(&self, cell: usize, player: Player) -> bool {
+        (0..DIRECTIONS.len()).any(|direction| {
+            let (_, length) = self.run_through(cell, player, direction);
+            self.rules.is_win_length(length)
+        })
+    }
+
+    // Winning line which goes through the `cell`, faster than checking all lines.
+    // The line is of the mark in the `cell`.
+    pub fn winning_line_through(&self, cell: usize) -> Option<(Player, usize)> {
+        let player = self.cells[cell].player()?;
+
+        (0..DIRECTIONS.len()).find_map(|direction| {
+            let (start, length) = self.run_through(cell, player, direction);
+
+            self.rules.is_win_length(length)
+                .then(|| (player, self.rules.line_id(start, direction)))
+        })
+    }
+
+    // First cell and length of the row of the `player` marks through the `cell`
+    // in the `direction`, the `cell` itself is counted as a mark of the `player`
+    fn run_through(&self, cell: usize, player: Player, direction: usize) -> (usize, usize) {
+        let (dx, dy) = DIRECTIONS[direction];
+
+        let mut start = cell;
+        let mut length = 1;
+
+        while let Some(prev) = self.rules.step(start, (-dx, -dy)) {
+            if self.cells[prev] != player.cell() {
+                break;
+            }
+
+            start = prev;
+            length += 1;
+        }
+
+        let mut end = cell;
+
+        while let Some(next) = self.rules.step(end, (dx, dy)) {
+            if self.cells[next] != player.cell() {
+                break;
+            }
+
+            end = next;
+            length += 1;
+        }
+
+        (start, length)
+    }
+}
+
+// Board with a turn tracking and a history of moves
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Position {
+    board: Board,
+    moves: Vec<usize>,
+    outcome: Option<Outcome>, // Cached outcome of the last move
+}
+
+impl Position {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            board: Board::new(rules),
+            moves: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.board.rules
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    // Players take turns in order, usually X and O
+    pub fn turn(&self) -> Player {
+        Player::from_index(self.moves.len() % self.rules().players)
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome.unwrap_or(Outcome::Ongoing)
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_move(cell).is_ok()
+    }
+
+    pub fn legal_moves(&self) -> Vec<usize> {
+        if self.outcome() != Outcome::Ongoing {
+            return Vec::new();
+        }
+
+        (0..self.board.cells.len()).filter(|&cell| self.board.is_empty(cell)).collect()
+    }
+
+    pub fn apply_move(&mut self, cell: usize) -> Result<Outcome, MoveError> {
+        self.check_move(cell)?;
+
+        self.board.cells[cell] = self.turn().cell();
+        self.moves.push(cell);
+
+        let outcome = if let Some((player, line)) = self.board.winning_line_through(cell) {
+            if self.rules().misere {
+                Outcome::Loss { player, line }
+            }
+            else {
+                Outcome::Win { player, line }
+            }
+        }
+        else if self.board.is_full() {
+            Outcome::Draw
+        }
+        else {
+            Outcome::Ongoing
+        };
+
+        self.outcome = Some(outcome);
+        Ok(outcome)
+    }
+
+    // Takes back the last move, used by the search of the computer player
+    pub fn undo_move(&mut self) -> Option<usize> {
+        let cell = self.moves.pop()?;
+
+        self.board.cells[cell] = GameCell::None;
+        self.outcome = None; // The game was not finished before the last move
+
+        Some(cell)
+    }
+
+    fn check_move(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= self.board.cells.len() {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome() != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays the moves in order and returns the outcome of the last one
+    fn play(rules: Rules, moves: &[usize]) -> (Position, Outcome) {
+        let mut position = Position::new(rules);
+        let mut outcome = Outcome::Ongoing;
+
+        for &cell in moves {
+            outcome = position.apply_move(cell).unwrap();
+        }
+
+        (position, outcome)
+    }
+
+    #[test]
+    fn classic_lines_match_rules() {
+        let lines: Vec<Vec<usize>> = LINES.iter().map(|line| line.to_vec()).collect();
+
+        assert_eq!(Rules::default().lines(), lines);
+        assert_eq!(Rules::new(4, 4, 3).unwrap().line_count(), 8 + 8 + 4 + 4);
+        assert_eq!(Rules::new(3, 3, 4), None);
+    }
+
+    #[test]
+    fn applies_moves() {
+        let mut position = Position::default();
+
+        assert_eq!(position.apply_move(4), Ok(Outcome::Ongoing));
+        assert_eq!(position.board().get(4), GameCell::First);
+        assert_eq!(position.apply_move(4), Err(MoveError::Occupied));
+        assert_eq!(position.apply_move(9), Err(MoveError::OutOfBounds));
+        assert_eq!(position.legal_moves(), vec![0, 1, 2, 3, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn detects_wins() {
+        // X takes the left column
+        let (mut position, outcome) = play(Rules::default(), &[0, 1, 3, 2, 6]);
+
+        assert_eq!(outcome, Outcome::Win { player: Player::First, line: 3 });
+        assert_eq!(outcome.winner(), Some(Player::First));
+        assert_eq!(position.apply_move(8), Err(MoveError::GameOver));
+        assert!(position.legal_moves().is_empty());
+
+        // O takes the anti-diagonal
+        let (_, outcome) = play(Rules::default(), &[0, 2, 1, 4, 5, 6]);
+        assert_eq!(outcome, Outcome::Win { player: Player::Second, line: 7 });
+    }
+
+    #[test]
+    fn detects_draws() {
+        let (_, outcome) = play(Rules::default(), &[0, 4, 8, 1, 7, 6, 2, 5, 3]);
+
+        assert_eq!(outcome, Outcome::Draw);
+        assert_eq!(outcome.winner(), None);
+    }
+
+    #[test]
+    fn exact_rules_ignore_overlines() {
+        let rules = Rules::gomoku(true);
+
+        // X fills the first row with a gap in the middle, then closes it for six in a row
+        let moves = [0, 30, 1, 31, 2, 32, 4, 33, 5, 48, 3];
+        let (_, outcome) = play(rules, &moves);
+        assert_eq!(outcome, Outcome::Ongoing);
+
+        let (_, outcome) = play(Rules::gomoku(false), &moves);
+        assert_eq!(outcome.winner(), Some(Player::First));
+
+        // Exactly five still wins
+        let (_, outcome) = play(rules, &[0, 30, 1, 31, 2, 32, 3, 33, 4]);
+        assert_eq!(outcome, Outcome::Win { player: Player::First, line: 0 });
+    }
+
+    #[test]
+    fn misere_line_loses() {
+        let rules = Rules { misere: true, ..Rules::default() };
+        let (_, outcome) = play(rules, &[0, 1, 3, 2, 6]);
+
+        assert_eq!(outcome, Outcome::Loss { player: Player::First, line: 3 });
+        assert_eq!(outcome.winner(), Some(Player::Second));
+    }
+
+    #[test]
+    fn undoes_moves() {
+        let (mut position, outcome) = play(Rules::default(), &[0, 1, 3, 2, 6]);
+        assert_ne!(outcome, Outcome::Ongoing);
+
+        assert_eq!(position.undo_move(), Some(6));
+        assert_eq!(position.outcome(), Outcome::Ongoing);
+        assert_eq!(position.board().get(6), GameCell::None);
+        assert_eq!(position.turn(), Player::First);
+        assert_eq!(position.board(), play(Rules::default(), &[0, 1, 3, 2]).0.board());
+
+        while position.undo_move().is_some() {}
+        assert_eq!(position.board(), &Board::default());
+        assert!(position.moves().is_empty());
+    }
+
+    #[test]
+    fn tracks_turns() {
+        let rules = Rules { players: 3, ..Rules::new(5, 5, 4).unwrap() };
+        let (position, _) = play(rules, &[0, 1, 2, 3]);
+
+        assert_eq!(position.turn(), Player::Second);
+        assert_eq!(position.board().get(2), GameCell::Third);
+        assert_eq!(position.board().get(3), GameCell::First);
+
+        assert_eq!(Player::Third.next(3), Player::First);
+        assert_eq!(Player::Third.next(4), Player::Fourth);
+        assert_eq!(Player::Second.other(), Player::First);
+    }
+}
@@ -0,0 +1,137 @@
+pub const DEFAULT_MOVE_SECONDS: i64 = 60;
+
+// Time before the timeout when the player is warned
+pub const WARNING_SECONDS: i64 = 15;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeControl {
+    PerMove { seconds: i64 }, // Fixed time for every move
+    Total { seconds: i64, increment: i64 }, // Chess-style time for the whole game
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl::PerMove { seconds: DEFAULT_MOVE_SECONDS }
+    }
+}
+
+impl TimeControl {
+    pub fn describe(self) -> String {
+        match self {
+            TimeControl::PerMove { seconds } => format!("{} per move", format_duration(seconds)),
+            TimeControl::Total { seconds, increment: 0 } => format!("{} per game", format_duration(seconds)),
+            TimeControl::Total { seconds, increment } => {
+                format!("{} per game + {}s per move", format_duration(seconds), increment)
+            }
+        }
+    }
+}
+
+// All times are Unix times in seconds
+#[derive(Clone, Debug)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: Vec<i64>, // Seconds of every seat, only for the total time control
+    turn_started_at: i64,
+}
+
+impl Clock {
+    // Seats are indexes of players in the game, in the order of turns
+    pub fn new(control: TimeControl, seats: usize, now: i64) -> Self {
+        let remaining = match control {
+            TimeControl::PerMove { seconds } => seconds,
+            TimeControl::Total { seconds, .. } => seconds,
+        };
+
+        Self {
+            control,
+            remaining: vec![remaining; seats],
+            turn_started_at: now,
+        }
+    }
+
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    // When the time of the player whose turn it is runs out
+    pub fn deadline(&self, turn: usize) -> i64 {
+        match self.control {
+            TimeControl::PerMove { seconds } => self.turn_started_at + seconds,
+            TimeControl::Total { .. } => self.turn_started_at + self.remaining[turn],
+        }
+    }
+
+    pub fn remaining(&self, seat: usize, turn: usize, now: i64) -> i64 {
+        if seat == turn {
+            (self.deadline(turn) - now).max(0)
+        }
+        else {
+            self.remaining[seat]
+        }
+    }
+
+    // Called after the move of the `seat`
+    pub fn switch(&mut self, seat: usize, now: i64) {
+        if let TimeControl::Total { increment, .. } = self.control {
+            let spent = now - self.turn_started_at;
+            let remaining = &mut self.remaining[seat];
+
+            *remaining = (*remaining - spent).max(0) + increment;
+        }
+
+        self.turn_started_at = now;
+    }
+}
+
+// Like 4:05
+pub fn format_duration(seconds: i64) -> String {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_move_deadline_restarts_every_move() {
+        let mut clock = Clock::new(TimeControl::default(), 2, 1000);
+
+        assert_eq!(clock.deadline(0), 1000 + DEFAULT_MOVE_SECONDS);
+
+        // Time which is left does not carry over to the next move
+        clock.switch(0, 1010);
+        assert_eq!(clock.deadline(1), 1010 + DEFAULT_MOVE_SECONDS);
+
+        clock.switch(1, 1070);
+        assert_eq!(clock.deadline(0), 1070 + DEFAULT_MOVE_SECONDS);
+        assert_eq!(clock.remaining(0, 0, 1080), DEFAULT_MOVE_SECONDS - 10);
+    }
+
+    #[test]
+    fn total_time_adds_increment() {
+        let mut clock = Clock::new(TimeControl::Total { seconds: 300, increment: 5 }, 2, 0);
+
+        clock.switch(0, 100);
+        assert_eq!(clock.remaining(0, 1, 100), 300 - 100 + 5);
+        assert_eq!(clock.deadline(1), 100 + 300);
+
+        clock.switch(1, 150);
+        assert_eq!(clock.remaining(1, 0, 150), 300 - 50 + 5);
+        assert_eq!(clock.deadline(0), 150 + 205);
+
+        // Spent time is never more than the time which is left
+        assert_eq!(clock.remaining(0, 0, 1000), 0);
+        clock.switch(0, 1000);
+        assert_eq!(clock.remaining(0, 1, 1000), 5);
+    }
+
+    #[test]
+    fn warns_before_the_deadline() {
+        let clock = Clock::new(TimeControl::PerMove { seconds: 30 }, 2, 0);
+        let warned_at = clock.deadline(0) - WARNING_SECONDS;
+
+        assert_eq!(warned_at, 15);
+        assert_eq!(clock.remaining(0, 0, warned_at), WARNING_SECONDS);
+    }
+}
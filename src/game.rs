@@ -2,37 +2,113 @@ use std::io::{BufWriter, Cursor};
 use std::sync::Arc;
 
 use image::{Rgb, ImageOutputFormat, ImageBuffer, Rgba, ColorType};
-use imageproc::drawing::{draw_filled_rect_mut, Canvas};
+use image::imageops::FilterType;
+use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut, Canvas};
 use imageproc::rect::Rect;
 
-use serenity::all::{CommandInteraction, ComponentInteraction, ButtonStyle};
-use serenity::builder::{CreateActionRow, CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage, CreateEmbed, CreateMessage, EditInteractionResponse, CreateAttachment, CreateButton, CreateEmbedAuthor, EditMessage};
+use serenity::all::{CommandInteraction, ComponentInteraction, ComponentInteractionDataKind, ButtonStyle, CommandOptionType, ResolvedOption, ResolvedValue};
+use serenity::builder::{CreateActionRow, CreateCommand, CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateEmbed, CreateMessage, EditInteractionResponse, CreateAttachment, CreateButton, CreateEmbedAuthor, CreateEmbedFooter, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, CreateThread, EditMessage};
 use serenity::http::Http;
-use serenity::model::prelude::{UserId, Message};
+use serenity::model::mention::Mentionable;
+use serenity::model::prelude::{ChannelId, GuildId, UserId, Message, MessageId};
 use serenity::prelude::Context;
 
 use tokio::sync::Mutex;
 
+use crate::ai::Difficulty;
+use crate::board::{GameCell, MoveError, Outcome, Player, Rules, LINES, MAX_PLAYERS};
+use crate::clock::{self, Clock, TimeControl};
+use crate::notakto::{self, NotaktoPosition};
+use crate::notation::{self, ParsedGame};
+use crate::numerical::{self, NumericalPosition};
+use crate::quantum::{self, QuantumPosition};
+use crate::qubic::{self, QubicPosition};
+use crate::queue::{Queue, QueueEntry, QueueKey, QueueScope};
+use crate::storage::{self, EndReason, GameRecord, GameResult, Storage};
+use crate::tournament::{self, Bracket, Format, MatchResult, Slot, Source, Status, Tournament};
+use crate::ultimate::{self, SubBoardState, UltimatePosition};
+use crate::variant::{GamePosition, Mode, Piece};
+use crate::wild::WildPosition;
+
 const BACKGROUND: Rgb<u8> = Rgb([42, 44, 47]);
 const GRAY: Rgb<u8> = Rgb([232, 232, 232]);
 const RED: Rgb<u8> = Rgb([196, 57, 57]);
+const GREEN: Rgb<u8> = Rgb([87, 242, 135]);
+const BLUE: Rgb<u8> = Rgb([88, 101, 242]);
+const YELLOW: Rgb<u8> = Rgb([254, 231, 92]);
+const PANEL: Rgb<u8> = Rgb([64, 68, 75]); // Boxes of the tournament bracket
+
+const DEFAULT_CHALLENGE_MINUTES: i64 = 5;
+// Interaction tokens are valid only for 15 minutes, so the game
+// message of the challenger can not be edited after that time
+const MAX_CHALLENGE_MINUTES: u64 = 10;
+// Like challenges, the interaction of the first player who accepts has to live through the next game
+const PENDING_GAME_MINUTES: i64 = 5;
+
+// Limits of the time controls. They keep most games within the lifetime of interaction tokens,
+// but long games on big boards can outlive it, so failed edits of their messages are only logged
+const MIN_MOVE_SECONDS: u64 = 30;
+const MAX_MOVE_SECONDS: u64 = 90;
+const MAX_TOTAL_MINUTES: u64 = 5;
+const MAX_INCREMENT_SECONDS: u64 = 10;
+
+const CELL_SIZE: u32 = 100;
+
+const MAX_BOARD_SIZE: usize = 7;
+// Discord allows only 5 rows of 5 buttons
+const MAX_BUTTONS_BOARD_SIZE: usize = 5;
+
+// Bigger boards are shown zoomed around the cursor or the last move
+const ZOOM_CELLS: usize = 9;
+
+// Delays of frames in milliseconds
+const REPLAY_MOVE_DELAY: u32 = 800;
+const REPLAY_LINE_DELAY: u32 = 100;
+const REPLAY_END_DELAY: u32 = 4000;
+const REPLAY_LINE_FRAMES: u32 = 6;
+// Frames of big boards are scaled down, so replays of long games are not too heavy
+const MAX_REPLAY_SIZE: u32 = 450;
+// From 1 to 30, faster encoding uses worse colors
+const REPLAY_ENCODING_SPEED: i32 = 30;
+// Bytes of the imported record
+const MAX_IMPORT_SIZE: u32 = 64 * 1024;
+
+const MULTI_PLAYER_WIN_LENGTH: usize = 4;
+
+const MAX_TOURNAMENT_NAME_LENGTH: u16 = 50;
+// Registrations which have not been started are cancelled, so they do not block their channels
+const MAX_REGISTRATION_HOURS: i64 = 24;
+// Embed fields are limited to 1024 characters
+const MAX_LISTED_MATCHES: usize = 10;
+
+const MAX_THREAD_NAME_LENGTH: usize = 100;
+// Embed descriptions are limited to 4096 characters, older moves are cut so the result fits too
+const MAX_MOVE_LIST_LENGTH: usize = 3500;
+// Added to the live message of spectators, so others can react with one click
+const SPECTATOR_REACTIONS: [char; 4] = ['👏', '🔥', '😮', '🍿'];
+
+// Second cell of the second row in the first layer, near the strong center of the cube
+const QUBIC_START_CELL: usize = 5;
+
+pub struct Game {
+    sprites: Arc<Sprites>,
 
-const CELLS: [(i32, i32); 9] = [
-    (0, 0),
-    (100, 0),
-    (200, 0),
+    wait_users: Mutex<Queue<WaitUser>>,
 
-    (0, 100),
-    (100, 100),
-    (200, 100),
+    challenges: Mutex<Vec<Challenge>>,
 
-    (0, 200),
-    (100, 200),
-    (200, 200),
-];
+    pending_games: Mutex<Vec<PendingGame>>,
 
-#[derive(Default)]
-pub struct Game {
+    tournaments: Mutex<Vec<Tournament>>, // Not finished ones
+    live_matches: Mutex<Vec<(i64, usize)>>, // Announced matches of tournaments which are not finished yet
+
+    sessions: Mutex<Vec<ActiveSession>>, // Locked only for a moment, never while waiting for a session
+
+    storage: Arc<Storage>,
+}
+
+// Images of marks and end lines, shared with replays which are encoded on blocking threads
+struct Sprites {
     x_image: ImageBuffer<Rgb<u8>, Vec<u8>>,
     o_image: ImageBuffer<Rgb<u8>, Vec<u8>>,
 
@@ -40,859 +116,4822 @@ pub struct Game {
     vertical_scratch: ImageBuffer<Rgba<u8>, Vec<u8>>,
     diagonal_scratch_1: ImageBuffer<Rgba<u8>, Vec<u8>>, // Left to right
     diagonal_scratch_2: ImageBuffer<Rgba<u8>, Vec<u8>>, // Right to left
-
-    new_game_canvas: ImageBuffer<Rgb<u8>, Vec<u8>>,
-
-    wait_user: Mutex<Option<(UserId, CommandInteraction, String, Message)>>,
-
-    sessions: Mutex<Vec<Arc<Mutex<GameSession>>>>,
 }
 
+type WaitUser = (UserId, CommandInteraction, String, Message, InputMode, GameOptions); // Third element is a name of player
+
+// Chosen by the player who starts the game
 #[derive(Clone, Copy, PartialEq)]
-enum GameCell {
-    None,
-    First,
-    Second,
+struct GameOptions {
+    mode: Mode,
+    rules: Rules,
+    time_control: TimeControl,
+    swap2: bool, // Only for gomoku
+    teams: bool, // Two teams of two players, teammates take turns with the mark of their team
+    series: Option<usize>, // Best of 3, 5 or 7 games
 }
 
-impl Default for GameCell {
-    fn default() -> Self {
-        GameCell::None
+impl GameOptions {
+    // Tournaments are played on the default board of the mode with the default time
+    fn for_tournament(mode: Mode) -> Self {
+        let rules = match mode {
+            Mode::Classic | Mode::Wild => Rules::default(),
+            Mode::Gomoku => Rules::gomoku(false),
+            Mode::Ultimate => UltimatePosition::rules(),
+            Mode::Qubic => QubicPosition::rules(),
+            Mode::Notakto => NotaktoPosition::rules(1),
+            Mode::OrderChaos => WildPosition::order_and_chaos_rules(),
+            Mode::Numerical => NumericalPosition::rules(),
+            Mode::Quantum => QuantumPosition::rules(),
+        };
+
+        Self {
+            mode,
+            rules,
+            time_control: TimeControl::default(),
+            swap2: false,
+            teams: false,
+            series: None,
+        }
+    }
+
+    fn game_name(&self) -> &'static str {
+        game_name(self.mode)
     }
-}
 
-struct GameSession {
-    player: (UserId, CommandInteraction, String, Message), // Third element is a name of player
-    player2: (UserId, CommandInteraction, String, Option<Message>), // No message in a same channel
+    // Players are matched only for the same board
+    fn is_matching(&self, other: &GameOptions) -> bool {
+        self.mode == other.mode && self.rules == other.rules && self.swap2 == other.swap2 && self.teams == other.teams && self.series == other.series
+    }
 
-    stage: usize,
-    cursor_pos: usize,
+    // Players who have to join before the game starts
+    fn seats(&self) -> usize {
+        if self.teams {
+            2 * self.rules.players
+        }
+        else {
+            self.rules.players
+        }
+    }
 
-    map: [GameCell; 9],
-    canvas: ImageBuffer<Rgb<u8>, Vec<u8>>,
-}
+    fn describe_board(&self) -> String {
+        let board = self.describe_rules();
 
-impl Game {
-    pub fn new() -> Self {
-        let x_image = image::open("./resources/x.png").expect("x.png").into_rgb8();
-        let o_image = image::open("./resources/o.png").expect("o.png").into_rgb8();
+        match (self.teams, self.series) {
+            (true, _) => format!("{}, two teams of two players", board),
+            (false, Some(series)) => format!("{}, best of {} games", board, series),
+            (false, None) => board,
+        }
+    }
+
+    fn describe_rules(&self) -> String {
+        if self.mode == Mode::Ultimate {
+            "3×3 grid of 3×3 boards".to_string()
+        }
+        else if self.mode == Mode::Qubic {
+            "4×4×4 cube, 4 in a row".to_string()
+        }
+        else if self.mode == Mode::Notakto {
+            match self.rules.width / 3 {
+                1 => "3×3, three in a row loses".to_string(),
+                boards => format!("{} boards of 3×3, three in a row kills a board", boards),
+            }
+        }
+        else if self.mode == Mode::OrderChaos {
+            "6×6, Order makes five same marks in a row, Chaos prevents it".to_string()
+        }
+        else if self.mode == Mode::Numerical {
+            format!("3×3, numbers from 1 to {}, the sum of {} in a row", numerical::MAX_NUMBER, numerical::MAGIC_SUM)
+        }
+        else if self.mode == Mode::Quantum {
+            "3×3, spooky marks in two cells, cycles are measured".to_string()
+        }
+        else if self.mode == Mode::Wild {
+            format!("{}, X or O on every move", self.rules.describe())
+        }
+        else if self.swap2 {
+            format!("{}, swap2 opening", self.rules.describe())
+        }
+        else {
+            self.rules.describe()
+        }
+    }
 
-        let horizontal_scratch = image::open("./resources/1.png").expect("1.png").into_rgba8();
-        let vertical_scratch = image::open("./resources/2.png").expect("2.png").into_rgba8();
-        let diagonal_scratch_1 = image::open("./resources/3.png").expect("3.png").into_rgba8();
-        let diagonal_scratch_2 = image::open("./resources/4.png").expect("4.png").into_rgba8();
+    // The command for joining the game
+    fn play_command(&self) -> String {
+        let mut command = "/play".to_string();
+
+        match self.mode {
+            Mode::Classic if !Rules { misere: false, ..self.rules }.is_classic() => {
+                command += &format!(" size:{}x{} win_length:{}", self.rules.width, self.rules.height, self.rules.win_length);
+            }
+            Mode::Classic => (),
+            Mode::Wild if !self.rules.is_classic() => {
+                command += &format!(" mode:wild size:{}x{} win_length:{}", self.rules.width, self.rules.height, self.rules.win_length);
+            }
+            Mode::Wild => command += " mode:wild",
+            Mode::OrderChaos => command += " mode:order_chaos",
+            Mode::Numerical => command += " mode:numerical",
+            Mode::Quantum => command += " mode:quantum",
+            Mode::Notakto if self.rules.width > 3 => command += &format!(" mode:notakto boards:{}", self.rules.width / 3),
+            Mode::Notakto => command += " mode:notakto",
+            Mode::Gomoku if self.rules.exact => command += " mode:gomoku_standard",
+            Mode::Gomoku => command += " mode:gomoku",
+            Mode::Ultimate => command += " mode:ultimate",
+            Mode::Qubic => command += " mode:qubic",
+        }
 
-        let new_game_canvas = draw_new_game_canvas();
+        if self.swap2 {
+            command += " swap2:True";
+        }
 
-        Self {
-            x_image,
-            o_image,
+        if self.rules.misere {
+            command += " misere:True";
+        }
 
-            horizontal_scratch,
-            vertical_scratch,
-            diagonal_scratch_1,
-            diagonal_scratch_2,
+        if self.rules.players > 2 {
+            command += &format!(" players:{}", self.rules.players);
+        }
 
-            new_game_canvas,
+        if self.teams {
+            command += " teams:True";
+        }
 
-            ..Default::default()
+        if let Some(series) = self.series {
+            command += &format!(" series:{}", series);
         }
+
+        command
     }
+}
 
-    pub fn register_play() -> CreateCommand {
-        CreateCommand::new("play")
-            .description("Start the game")
+// The swap2 opening of gomoku, the first player places three marks,
+// then the second player chooses a mark or places two more marks
+// and lets the first player choose
+#[derive(Clone, Copy, PartialEq)]
+enum Swap2Stage {
+    PlaceThree, // The first player places X, O and X
+    Choose, // The second player chooses X, O or placing of two more marks
+    PlaceTwo, // The second player places O and X
+    ChooseAgain, // The first player chooses X or O
+    Done, // Or there was no swap2 opening
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum InputMode {
+    Arrows, // Moving the selection square on the image
+    Buttons, // One button per cell
+    Coordinates, // Selecting a column and a row, with arrows for small steps
+}
+
+// Interaction whose response is the private game message of the player
+enum PlayerInteraction {
+    Command(CommandInteraction),
+    Component(ComponentInteraction), // The player has accepted a challenge or has used the game message
+}
+
+impl PlayerInteraction {
+    async fn edit_response(&self, http: &Http, builder: EditInteractionResponse) -> serenity::Result<Message> {
+        match self {
+            PlayerInteraction::Command(val) => val.edit_response(http, builder).await,
+            PlayerInteraction::Component(val) => val.edit_response(http, builder).await,
+        }
     }
 
-    pub fn register_stop() -> CreateCommand {
-        CreateCommand::new("stop")
-            .description("Unimplemented")
+    async fn delete_response(&self, http: &Http) -> serenity::Result<()> {
+        match self {
+            PlayerInteraction::Command(val) => val.delete_response(http).await,
+            PlayerInteraction::Component(val) => val.delete_response(http).await,
+        }
     }
 
-    pub async fn command(&self, ctx: Context, interaction: CommandInteraction) {
-        if interaction.data.name == "stop" {
-            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .ephemeral(true)
-                    .content("Unimplemented!")
-            ))
-            .await
-            .unwrap();
+    async fn create_followup(&self, http: &Http, builder: CreateInteractionResponseFollowup) -> serenity::Result<Message> {
+        match self {
+            PlayerInteraction::Command(val) => val.create_followup(http, builder).await,
+            PlayerInteraction::Component(val) => val.create_followup(http, builder).await,
+        }
+    }
 
-            return;
+    fn guild_id(&self) -> Option<GuildId> {
+        match self {
+            PlayerInteraction::Command(val) => val.guild_id,
+            PlayerInteraction::Component(val) => val.guild_id,
         }
+    }
 
-        if self.is_player_already_in_game(&ctx.http, &interaction).await {
-            return;
+    fn channel_id(&self) -> ChannelId {
+        match self {
+            PlayerInteraction::Command(val) => val.channel_id,
+            PlayerInteraction::Component(val) => val.channel_id,
         }
+    }
+}
 
-        let (player, player2) = {
-            let val = {
-                self.wait_user.lock().await.take()
-            };
+// Best of `length` games between two players, seats do not change between the games
+#[derive(Clone)]
+struct Series {
+    length: usize,
+    wins: [usize; 2], // Of the first and the second player of the session
+}
 
-            let name = match &interaction.member {
-                Some(val) => val.nick.clone().unwrap_or_else(|| interaction.user.name.clone()),
-                None => interaction.user.name.clone(),
-            };
+impl Series {
+    fn new(length: usize) -> Self {
+        Self {
+            length,
+            wins: [0; 2],
+        }
+    }
 
-            if let Some(val) = val {
-                interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .ephemeral(true)
-                        .embed(
-                            CreateEmbed::new()
-                                .title("Please, wait")
-                        )
-                    )
-                )
-                .await
-                .unwrap();
+    // Draws are not counted
+    fn finish_game(&mut self, winner: Option<usize>) {
+        if let Some(seat) = winner {
+            self.wins[seat] += 1;
+        }
+    }
 
-                // Channel ids are unique
-                if interaction.channel_id != val.1.channel_id {
-                    let message = interaction.channel_id.send_message(&ctx.http, 
-                        CreateMessage::new()
-                            .embed(
-                                CreateEmbed::new()
-                                    .title(
-                                        format!(
-                                            "The game between {} and {} in progress!",
-                                            val.2,
-                                            name,
-                                        )
-                                    )
-                            )
-                    )
-                    .await
-                    .unwrap();
+    fn winner(&self) -> Option<usize> {
+        self.wins.iter().position(|&wins| wins > self.length / 2)
+    }
+}
 
-                    (
-                        val,
-                        (interaction.user.id, interaction, name, Some(message)),
-                    )
-                }
-                else {
-                    (
-                        val,
-                        (interaction.user.id, interaction, name, None),
-                    )
-                }
-            }
-            else {
-                let icon_url = interaction.user.avatar_url().unwrap_or_else(||
-                    interaction.user.default_avatar_url()
-                );
+// Game which starts when all players press its button, like the rematch of a finished game
+// or a match of the tournament
+struct PendingGame {
+    players: Vec<SessionPlayer>, // Interactions of the players who have accepted
+    bot: Option<Difficulty>,
+    options: GameOptions,
+    series: Option<Series>,
+    swapped: bool, // Marks are moved by one seat, so the second player plays X
+    tournament_match: Option<(i64, usize)>, // Ids of the tournament and of the match
+    expires_at: i64, // Unix time in seconds
+}
 
-                let message = interaction.channel_id.send_message(&ctx.http, CreateMessage::new()
-                    .embed(
-                        CreateEmbed::new()
-                        .author(
-                            CreateEmbedAuthor::new(name.clone())
-                                .icon_url(icon_url)
-                        )
-                        .title(format!("{} wants to play tic-tac-toe game!", name))
-                        .description("You can join to him/her/them by using the `/play` command.")
-                    )
-                )
-                .await
-                .unwrap();
+impl PendingGame {
+    fn is_accepted(&self) -> bool {
+        self.players.iter().all(|val| val.1.is_some() || Some(val.0) == self.bot_id())
+    }
 
-                interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
-                    CreateInteractionResponseMessage::new()
-                        .ephemeral(true)
-                        .embed(
-                            CreateEmbed::new()
-                                .title("Please, wait for second player...")
-                        )
-                    )
-                )
-                .await
-                .unwrap();
+    // The computer always takes the second seat
+    fn bot_id(&self) -> Option<UserId> {
+        self.bot.map(|_| self.players[1].0)
+    }
+}
 
-                *self.wait_user.lock().await = Some((interaction.user.id, interaction, name, message)); 
-                return;
-            }
-        };
+struct Challenge {
+    id: u64,
+    challenger: (UserId, CommandInteraction, String, InputMode), // Third element is a name of player
+    opponent: (UserId, String),
+    message: Message,
+    expires_at: i64, // Unix time in seconds
+    options: GameOptions,
+}
 
-        let new_game = Arc::new(Mutex::new(GameSession {
-            player,
-            player2,
+type SessionPlayer = (UserId, Option<PlayerInteraction>, String, Option<Message>, InputMode); // No interaction for the bot, no message in a channel which has one already
 
-            stage: 0,
-            cursor_pos: 4,
+type ActiveSession = (Vec<UserId>, Arc<Mutex<GameSession>>); // Human players are kept outside of the lock, seats never change
 
-            map: Default::default(),
-            canvas: self.new_game_canvas.clone(),
-        }));
+struct GameSession {
+    players: Vec<SessionPlayer>, // The first player always has an interaction and a message
 
-        {
-            self.sessions.lock().await.push(Arc::clone(&new_game));
-        }
+    bot: Option<Difficulty>, // The second player is a computer
+    cursor_pos: usize,
+    cursor_in_sub_board: bool, // Ultimate tic-tac-toe with arrows, otherwise a sub-board is selected
+    piece: Piece, // Chosen by the current player, like the mark in wild tic-tac-toe or the number in numerical tic-tac-toe
 
-        self.process_session(&ctx.http, &mut *new_game.lock().await).await;
-    }
+    options: GameOptions,
+    position: GamePosition,
+    canvas: ImageBuffer<Rgb<u8>, Vec<u8>>,
 
-    async fn is_player_already_in_game(&self, http: &Http, interaction: &CommandInteraction) -> bool {
-        let message = CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new()
-                .ephemeral(true)
-                .embed(
-                    CreateEmbed::new()
-                        .title("Start a new game")
-                        .description("You have already in the game. For starting a new game you should use the `/stop` command.")
-                )
-        );
+    clock: Clock, // Players are the first and the second player of the session, not marks
+    warned: bool, // The current player has been warned about the timeout
 
-        {
-            if let Some(val) = self.wait_user.lock().await.as_ref() {
-                if val.0 == interaction.user.id {
-                    interaction.create_response(http, message)
-                    .await
-                    .unwrap();
+    swap2: Swap2Stage,
+    swapped: bool, // Marks are moved by one seat, so the second player plays X
+    suggestion: Option<usize>, // The cell suggested by the teammate of the current player
+    series: Option<Series>,
+    tournament_match: Option<(i64, usize)>, // Ids of the tournament and of the match
 
-                    return true;
-                }
-            }
-        }
+    move_log: Vec<String>, // Like "X B2", for spectators
+    spectators: Vec<UserId>,
+    thread: Option<Message>, // The live message in the thread of spectators, created by the first of them
 
-        let sessions = self.sessions.lock().await;
+    started_at: i64,
+}
 
-        for session in &*sessions {
-            let session = session.lock().await;
+// Seats are indexes in the `players` of the session, marks are
+// played in the order of seats unless swapped in the swap2 opening
+impl GameSession {
+    fn new(players: Vec<SessionPlayer>, bot: Option<Difficulty>, options: GameOptions) -> Self {
+        let position = GamePosition::new(options.mode, options.rules);
 
-            if session.player.0 == interaction.user.id
-                || session.player2.0 == interaction.user.id
-            {
-                interaction.create_response(http, message)
-                .await
-                .unwrap();
+        Self {
+            clock: Clock::new(options.time_control, players.len(), storage::now()),
+            players,
 
-                return true;
-            }
+            bot,
+            cursor_pos: initial_cursor(&position),
+            cursor_in_sub_board: false,
+            piece: position.default_piece(),
+
+            options,
+            position,
+            canvas: draw_new_game_canvas(options.mode, options.rules),
+
+            warned: false,
+
+            swap2: if options.swap2 { Swap2Stage::PlaceThree } else { Swap2Stage::Done },
+            swapped: false,
+            suggestion: None,
+            series: options.series.map(Series::new),
+            tournament_match: None,
+
+            move_log: Vec::new(),
+            spectators: Vec::new(),
+            thread: None,
+
+            started_at: storage::now(),
         }
+    }
 
-        false
+    // Titles of all embeds show the score of the series
+    fn title(&self, title: &str) -> String {
+        match &self.series {
+            Some(series) => format!(
+                "{} (best of {}: {} {}–{} {})",
+                title,
+                series.length,
+                self.name(0),
+                series.wins[0],
+                series.wins[1],
+                self.name(1),
+            ),
+            None => title.to_string(),
+        }
     }
 
-    async fn process_session(&self, http: &Http, session: &mut GameSession) {
-        match session.stage {
-            0 => {
-                show_game_message(
-                    http,
-                    &session.player.1,
-                    session.cursor_pos,
-                    &session.map,
-                    &session.canvas,
-                ).await;
-
-                show_wait_and_common_message(
-                    http,
-                    &session.player2.1,
-                    &session.canvas,
-                    &session.player.2,
-                    &session.player2.2,
-                    &mut session.player.3,
-                    session.player2.3.as_mut(),
-                ).await;
-            }
-            1 => {
-                show_game_message(
-                    http,
-                    &session.player2.1,
-                    session.cursor_pos,
-                    &session.map,
-                    &session.canvas,
-                ).await;
-
-                show_wait_and_common_message(
-                    http,
-                    &session.player.1,
-                    &session.canvas,
-                    &session.player.2,
-                    &session.player2.2,
-                    &mut session.player.3,
-                    session.player2.3.as_mut(),
-                ).await;
-            }
-            _ => unreachable!(),
+    // Who has to make a move or a choice
+    fn current_seat(&self) -> usize {
+        match self.swap2 {
+            Swap2Stage::PlaceThree | Swap2Stage::ChooseAgain => 0,
+            Swap2Stage::Choose | Swap2Stage::PlaceTwo => 1,
+
+            // Teammates take turns after every move of their mark
+            Swap2Stage::Done if self.is_team_game() => self.seat_of(self.position.turn()) + 2 * (self.position.moves().len() / 2 % 2),
+            Swap2Stage::Done => self.seat_of(self.position.turn()),
         }
     }
 
-    pub async fn component(&self, ctx: Context, component: ComponentInteraction) {
-        // We are calling this because we are editing the component
-        // interaction or answering to the original interaction in the progress_game()
-        component.create_response(&ctx.http, CreateInteractionResponse::Acknowledge).await.unwrap();
+    // Seats of the second half play the marks of the first half
+    fn is_team_game(&self) -> bool {
+        self.players.len() > self.position.rules().players
+    }
 
-        let original_session = self.get_current_game(&component).await.unwrap();
-        let mut session = original_session.lock().await;
+    fn teammate(&self, seat: usize) -> Option<usize> {
+        self.is_team_game().then(|| (seat + 2) % 4)
+    }
 
-        match component.data.custom_id.as_str() {
-            "left" => {
-                if ![0, 3, 6].contains(&session.cursor_pos) {
-                    session.cursor_pos -= 1;
-                }
+    // Like "A" or "the team of A and C"
+    fn side_name(&self, seat: usize) -> String {
+        match self.teammate(seat) {
+            Some(teammate) => format!("the team of {} and {}", self.name(seat), self.name(teammate)),
+            None => self.name(seat).clone(),
+        }
+    }
 
-                update_game_message(&ctx.http, &component, &session).await;
-            }
+    // Who plays the mark, swapped marks are moved by one seat
+    fn seat_of(&self, mark: Player) -> usize {
+        (mark.index() + self.swapped as usize) % self.position.rules().players
+    }
 
-            "down" => {
-                if session.cursor_pos <= 5 {
-                    session.cursor_pos += 3
-                }
+    fn mark_of(&self, seat: usize) -> Player {
+        let players = self.position.rules().players;
 
-                update_game_message(&ctx.http, &component, &session).await;
-            }
+        Player::from_index((seat + players - self.swapped as usize) % players)
+    }
 
-            "up" => {
-                if session.cursor_pos >= 3 {
-                    session.cursor_pos -= 3
-                }
+    fn seat_of_user(&self, user_id: UserId) -> Option<usize> {
+        self.players.iter().position(|val| val.0 == user_id)
+    }
 
-                update_game_message(&ctx.http, &component, &session).await;
-            }
+    fn name(&self, seat: usize) -> &String {
+        &self.players[seat].2
+    }
 
-            "right" => {
-                if ![2, 5, 8].contains(&session.cursor_pos) {
-                    session.cursor_pos += 1
-                }
+    // Like "A, B and C"
+    fn player_names(&self) -> String {
+        join_names(self.players.iter().map(|val| val.2.as_str()))
+    }
 
-                update_game_message(&ctx.http, &component, &session).await;
-            }
+    fn current_player_id(&self) -> UserId {
+        self.players[self.current_seat()].0
+    }
 
-            "send" => {
-                'condition: {
-                    if component.user.id == session.player.0 {
-                        if session.map[session.cursor_pos] != GameCell::None { // Unreachable in default situation
-                            break 'condition;
-                        }
+    fn current_input_mode(&self) -> InputMode {
+        self.players[self.current_seat()].4
+    }
 
-                        let cursor_pos = session.cursor_pos;
-                        session.map[cursor_pos] = GameCell::First;
-                        self.draw_x(&mut session.canvas, cursor_pos);
-                    }
-                    else {
-                        if session.map[session.cursor_pos] != GameCell::None {
-                            break 'condition;
-                        }
+    fn current_interaction(&self) -> Option<&PlayerInteraction> {
+        self.players[self.current_seat()].1.as_ref()
+    }
 
-                        let cursor_pos = session.cursor_pos;
-                        session.map[cursor_pos] = GameCell::Second;
-                        self.draw_o(&mut session.canvas, cursor_pos);
-                    }
-                };
+    // Public messages of the game, one per channel of players
+    fn messages_mut(&mut self) -> impl Iterator<Item = &mut Message> {
+        self.players.iter_mut().filter_map(|val| val.3.as_mut())
+    }
 
-                let map = &session.map;
-                
-                // Checking for win
-                // 0 1 2
-                // 3 4 5
-                // 6 7 8
-                let (win_player, id) = if map[0] != GameCell::None && (map[0] == map[1]) && (map[1] == map[2]) {
-                    (map[0], 0)
-                }
-                else if map[3] != GameCell::None && (map[3] == map[4]) && (map[4] == map[5]) {
-                    (map[3], 1)
-                }
-                else if map[6] != GameCell::None && (map[6] == map[7]) && (map[7] == map[8]) {
-                    (map[6], 2)
-                }
+    // Someone has to choose a mark in the swap2 opening
+    fn is_choosing(&self) -> bool {
+        matches!(self.swap2, Swap2Stage::Choose | Swap2Stage::ChooseAgain)
+    }
 
-                else if map[0] != GameCell::None && (map[0] == map[3]) && (map[3] == map[6]) {
-                    (map[0], 3)
-                }
-                else if map[1] != GameCell::None && (map[1] == map[4]) && (map[4] == map[7]) {
-                    (map[1], 4)
-                }
-                else if map[2] != GameCell::None && (map[2] == map[5]) && (map[5] == map[8]) {
-                    (map[2], 5)
-                }
+    // The player whose turn it is chooses the cell for the measurement of the cycle in quantum tic-tac-toe
+    fn is_measuring(&self) -> bool {
+        matches!(&self.position, GamePosition::Quantum(position) if position.cycle().is_some())
+    }
+}
 
-                else if map[0] != GameCell::None && (map[0] == map[4]) && (map[4] == map[8]) {
-                    (map[0], 6)
-                }
-                else if map[2] != GameCell::None && (map[2] == map[4]) && (map[4] == map[6]) {
-                    (map[2], 7)
-                }
+impl Game {
+    pub fn new(storage: Arc<Storage>) -> Self {
+        let tournaments = storage.active_tournaments().unwrap_or_else(|err| {
+            eprintln!("Failed to load tournaments: {err:?}");
+            Vec::new()
+        });
 
-                else {
-                    let mut was_none = false;
-                    for cell in map {
-                        if *cell == GameCell::None {
-                            was_none = true;
-                            break;
-                        }
-                    }
-                    
-                    if !was_none {
-                        let message = EditMessage::new()
-                            .add_embed(CreateEmbed::new()
-                                .title(
-                                    format!(
-                                        "The game between {} and {} has finished!",
-                                        session.player.2,
-                                        session.player2.2,
-                                    )
-                                )
-                                .description("No one wins!")
-                                .attachment("canvas.png")
-                            )
-                            .attachment(generate_attachment_rgb8(&session.canvas, "canvas.png"));
+        Self {
+            sprites: Arc::new(Sprites::load()),
 
-                        self.end_game_with_message(&ctx.http, &mut session, &original_session, message).await;
-                        return;
-                    }
+            wait_users: Mutex::new(Queue::default()),
 
-                    session.stage = (session.stage + 1) % 2;
-                    session.cursor_pos = 4;
+            challenges: Mutex::new(Vec::new()),
 
-                    self.process_session(&ctx.http, &mut session).await;
-                    return;
-                };
+            pending_games: Mutex::new(Vec::new()),
+
+            tournaments: Mutex::new(tournaments),
+            live_matches: Mutex::new(Vec::new()),
+
+            sessions: Mutex::new(Vec::new()),
+
+            storage,
+        }
+    }
+
+    pub fn register_play() -> CreateCommand {
+        CreateCommand::new("play")
+            .description("Start the game")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "mode", "What do you want to play")
+                    .add_string_choice("tic-tac-toe", "classic")
+                    .add_string_choice("gomoku (free-style, five or more in a row)", "gomoku")
+                    .add_string_choice("gomoku (standard, exactly five in a row)", "gomoku_standard")
+                    .add_string_choice("ultimate tic-tac-toe", "ultimate")
+                    .add_string_choice("3D tic-tac-toe (4×4×4)", "qubic")
+                    .add_string_choice("Notakto (both play X, three in a row loses)", "notakto")
+                    .add_string_choice("wild tic-tac-toe (X or O on every move)", "wild")
+                    .add_string_choice("Order & Chaos (6×6, five same marks in a row)", "order_chaos")
+                    .add_string_choice("numerical tic-tac-toe (odd and even numbers, the sum of 15 in a row)", "numerical")
+                    .add_string_choice("quantum tic-tac-toe (spooky marks in two cells)", "quantum")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "vs", "Who do you want to play against")
+                    .add_string_choice("human", "human")
+                    .add_string_choice("bot", "bot")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "difficulty", "Difficulty of the bot")
+                    .add_string_choice("easy", "easy")
+                    .add_string_choice("medium", "medium")
+                    .add_string_choice("perfect (full search up to 10 empty cells, a limited one on bigger boards)", "perfect")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "input", "How do you want to select cells")
+                    .add_string_choice("arrows", "arrows")
+                    .add_string_choice("buttons", "buttons")
+                    .add_string_choice("coordinates", "coordinates")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::User, "opponent", "Challenge a specific user")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "expires", "Minutes to accept the challenge")
+                    .min_int_value(1)
+                    .max_int_value(MAX_CHALLENGE_MINUTES)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "size", "Size of the board, like 4 or 5x4")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "win_length", "How many marks in a row are needed for a win")
+                    .min_int_value(3)
+                    .max_int_value(MAX_BOARD_SIZE as u64)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "move_time", "Seconds for every move")
+                    .min_int_value(MIN_MOVE_SECONDS)
+                    .max_int_value(MAX_MOVE_SECONDS)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "total_time", "Minutes for all moves of every player, instead of the time per move")
+                    .min_int_value(1)
+                    .max_int_value(MAX_TOTAL_MINUTES)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "increment", "Seconds added after every move, only with the total time")
+                    .min_int_value(0)
+                    .max_int_value(MAX_INCREMENT_SECONDS)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "swap2", "Use the swap2 opening in gomoku")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "misere", "The player who completes a line loses")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "players", "Number of players in tic-tac-toe, bigger games are on bigger boards")
+                    .min_int_value(2)
+                    .max_int_value(MAX_PLAYERS as u64)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "series", "Play the best of several games, the next one is started by a button")
+                    .add_int_choice("Best of 3", 3)
+                    .add_int_choice("Best of 5", 5)
+                    .add_int_choice("Best of 7", 7)
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Boolean, "teams", "Two teams of two players, teammates take turns with the same mark")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "boards", "Number of boards in Notakto")
+                    .min_int_value(1)
+                    .max_int_value(notakto::MAX_BOARDS as u64)
+            )
+    }
+
+    pub fn register_stop() -> CreateCommand {
+        CreateCommand::new("stop")
+            .description("Stop waiting for the second player or resign the current game")
+    }
+
+    pub fn register_queue() -> CreateCommand {
+        CreateCommand::new("queue")
+            .description("Players who are waiting for the second player")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "show", "Shows who is waiting for the second player")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "leave", "Stop waiting for the second player")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "scope", "Sets where players are matched (requires the Manage Server permission)")
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "mode", "Where players are matched")
+                            .required(true)
+                            .add_string_choice("guild", "guild")
+                            .add_string_choice("channel", "channel")
+                    )
+            )
+    }
+
+    pub fn register_tournament() -> CreateCommand {
+        CreateCommand::new("tournament")
+            .description("Tournaments of players in this channel")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "create", "Creates a tournament in this channel")
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "format", "How players are paired")
+                            .required(true)
+                            .add_string_choice("single elimination", Format::SingleElimination.as_str())
+                            .add_string_choice("double elimination", Format::DoubleElimination.as_str())
+                            .add_string_choice("round robin", Format::RoundRobin.as_str())
+                            .add_string_choice("Swiss", Format::Swiss.as_str())
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "mode", "What is played, on the default board")
+                            .add_string_choice("tic-tac-toe", "classic")
+                            .add_string_choice("gomoku", "gomoku")
+                            .add_string_choice("ultimate tic-tac-toe", "ultimate")
+                            .add_string_choice("3D tic-tac-toe", "qubic")
+                            .add_string_choice("Notakto", "notakto")
+                            .add_string_choice("wild tic-tac-toe", "wild")
+                            .add_string_choice("Order & Chaos", "order_chaos")
+                            .add_string_choice("numerical tic-tac-toe", "numerical")
+                            .add_string_choice("quantum tic-tac-toe", "quantum")
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(CommandOptionType::String, "name", "Name of the tournament")
+                            .max_length(MAX_TOURNAMENT_NAME_LENGTH)
+                    )
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "join", "Joins the tournament of this channel")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "start", "Starts the tournament (only for its creator), players start their matches by a button")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "status", "Shows players, matches and the bracket of the tournament")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "cancel", "Cancels the tournament of this channel (only for its creator)")
+            )
+    }
+
+    pub fn register_replay() -> CreateCommand {
+        CreateCommand::new("replay")
+            .description("Shows the animated replay of the finished game")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "game_id", "Number of the game, shown under its result")
+                    .required(true)
+                    .min_int_value(1)
+            )
+    }
+
+    pub fn register_export() -> CreateCommand {
+        CreateCommand::new("export")
+            .description("Sends the record of the finished game as a text file")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "game_id", "Number of the game, shown under its result")
+                    .required(true)
+                    .min_int_value(1)
+            )
+    }
+
+    pub fn register_import() -> CreateCommand {
+        CreateCommand::new("import")
+            .description("Replays the game from its record, without counting it in statistics")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Attachment, "file", "Text file of the record, like one from /export")
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "text", "Text of the record, if there is no file")
+            )
+    }
+
+    pub async fn command(&self, ctx: Context, interaction: CommandInteraction) {
+        match interaction.data.name.as_str() {
+            "stop" => {
+                self.stop(ctx, interaction).await;
+                return;
+            }
+            "queue" => {
+                self.queue(ctx, interaction).await;
+                return;
+            }
+            "tournament" => {
+                self.tournament(ctx, interaction).await;
+                return;
+            }
+            "replay" => {
+                self.replay(ctx, interaction).await;
+                return;
+            }
+            "export" => {
+                self.export(ctx, interaction).await;
+                return;
+            }
+            "import" => {
+                self.import(ctx, interaction).await;
+                return;
+            }
+            _ => (),
+        }
+
+        if self.is_player_already_in_game(&ctx.http, &interaction).await {
+            return;
+        }
+
+        let mut vs_bot = false;
+        let mut difficulty = Difficulty::Medium;
+        let mut mode = Mode::Classic;
+        let mut exact = false;
+        let mut input_mode = None;
+        let mut opponent = None;
+        let mut expires_in = DEFAULT_CHALLENGE_MINUTES * 60;
+        let mut move_time = clock::DEFAULT_MOVE_SECONDS;
+        let mut total_time = None;
+        let mut increment = 0;
+        let mut size = None;
+        let mut win_length = None;
+        let mut swap2 = false;
+        let mut misere = false;
+        let mut boards = None;
+        let mut players = 2;
+        let mut teams = false;
+        let mut series = None;
+
+        for option in interaction.data.options() {
+            match (option.name, option.value) {
+                ("vs", ResolvedValue::String(val)) => vs_bot = val == "bot",
+                ("difficulty", ResolvedValue::String(val)) => {
+                    difficulty = Difficulty::from_name(val).unwrap_or(difficulty);
+                }
+                ("mode", ResolvedValue::String(val)) => {
+                    mode = match val {
+                        "gomoku" | "gomoku_standard" => Mode::Gomoku,
+                        "ultimate" => Mode::Ultimate,
+                        "qubic" => Mode::Qubic,
+                        "notakto" => Mode::Notakto,
+                        "wild" => Mode::Wild,
+                        "order_chaos" => Mode::OrderChaos,
+                        "numerical" => Mode::Numerical,
+                        "quantum" => Mode::Quantum,
+                        _ => Mode::Classic,
+                    };
+                    exact = val == "gomoku_standard";
+                }
+                ("input", ResolvedValue::String(val)) => input_mode = match val {
+                    "buttons" => Some(InputMode::Buttons),
+                    "coordinates" => Some(InputMode::Coordinates),
+                    _ => Some(InputMode::Arrows),
+                },
+                ("opponent", ResolvedValue::User(user, member)) => {
+                    let name = member.and_then(|val| val.nick.clone()).unwrap_or_else(|| user.name.clone());
+                    opponent = Some((user.id, name, user.bot));
+                }
+                ("expires", ResolvedValue::Integer(val)) => expires_in = val * 60,
+                ("move_time", ResolvedValue::Integer(val)) => move_time = val,
+                ("total_time", ResolvedValue::Integer(val)) => total_time = Some(val * 60),
+                ("increment", ResolvedValue::Integer(val)) => increment = val,
+                ("size", ResolvedValue::String(val)) => size = Some(parse_size(val)),
+                ("win_length", ResolvedValue::Integer(val)) => win_length = Some(val as usize),
+                ("swap2", ResolvedValue::Boolean(val)) => swap2 = val,
+                ("misere", ResolvedValue::Boolean(val)) => misere = val,
+                ("boards", ResolvedValue::Integer(val)) => boards = Some(val as usize),
+                ("players", ResolvedValue::Integer(val)) => players = val as usize,
+                ("teams", ResolvedValue::Boolean(val)) => teams = val,
+                ("series", ResolvedValue::Integer(val)) => series = Some(val as usize),
+                _ => (),
+            }
+        }
+
+        let time_control = match total_time {
+            Some(seconds) => TimeControl::Total { seconds, increment },
+            None => TimeControl::PerMove { seconds: move_time },
+        };
+
+        let rules = match mode {
+            Mode::Classic | Mode::Wild => size
+                .unwrap_or(Some(default_size(players)))
+                .and_then(|(width, height)| Rules::new(width, height, win_length.unwrap_or(default_win_length(width, height, players))))
+                .filter(|&rules| is_playable_board(mode, rules))
+                .map(|rules| Rules { misere, players, ..rules }),
+            Mode::Gomoku => Some(Rules::gomoku(exact)),
+            Mode::Ultimate => Some(UltimatePosition::rules()),
+            Mode::Qubic => Some(QubicPosition::rules()),
+            Mode::Notakto => Some(NotaktoPosition::rules(boards.unwrap_or(1))),
+            Mode::OrderChaos => Some(WildPosition::order_and_chaos_rules()),
+            Mode::Numerical => Some(NumericalPosition::rules()),
+            Mode::Quantum => Some(QuantumPosition::rules()),
+        };
+
+        let input_mode = input_mode.unwrap_or(default_input_mode(mode));
+
+        let error = match rules {
+            None => Some(format!(
+                "Boards can be from 3×3 to {0}×{0} and marks in a row must fit in the board.",
+                MAX_BOARD_SIZE,
+            )),
+            Some(_) if !matches!(mode, Mode::Classic | Mode::Wild) && (size.is_some() || win_length.is_some()) => Some(format!(
+                "The board of {} can not be changed.",
+                match mode {
+                    Mode::Gomoku => "gomoku",
+                    Mode::Ultimate => "ultimate tic-tac-toe",
+                    Mode::Qubic => "3D tic-tac-toe",
+                    Mode::OrderChaos => "Order & Chaos",
+                    Mode::Numerical => "numerical tic-tac-toe",
+                    Mode::Quantum => "quantum tic-tac-toe",
+                    _ => "Notakto",
+                },
+            )),
+            Some(_) if mode != Mode::Classic && players > 2 => Some(
+                "Only tic-tac-toe can be played by more than two players.".to_string()
+            ),
+            Some(_) if players > 2 && (vs_bot || opponent.is_some()) => Some(
+                "Games of more than two players start when the lobby is full, the computer and challenges are only for two players.".to_string()
+            ),
+            Some(_) if teams && players > 2 => Some(
+                "Teams can be used only in games of two marks.".to_string()
+            ),
+            Some(_) if teams && (vs_bot || opponent.is_some()) => Some(
+                "Team games start when four players have joined, the computer and challenges are only for two players.".to_string()
+            ),
+            // Measurements are made by the player whose mark has not closed the cycle, so turns of teammates would not alternate
+            Some(_) if teams && mode == Mode::Quantum => Some(
+                "Quantum tic-tac-toe can not be played by teams.".to_string()
+            ),
+            Some(_) if teams && swap2 => Some(
+                "The swap2 opening can not be used by teams.".to_string()
+            ),
+            Some(_) if series.is_some() && (teams || players > 2) => Some(
+                "Series can be played only by two players.".to_string()
+            ),
+            Some(_) if players > 2 && misere => Some(
+                "The misère rule can be used only in games of two players.".to_string()
+            ),
+            Some(_) if mode != Mode::Classic && misere => Some(
+                "The misère rule can be used only in tic-tac-toe, Notakto is misère already.".to_string()
+            ),
+            Some(_) if mode != Mode::Notakto && boards.is_some() => Some(
+                "The number of boards can be changed only in Notakto.".to_string()
+            ),
+            Some(_) if mode == Mode::Qubic && input_mode == InputMode::Coordinates => Some(
+                "Layers of 3D tic-tac-toe are selected by buttons, please use arrows.".to_string()
+            ),
+            // Spooky marks are drawn only on the image
+            Some(_) if mode == Mode::Quantum && input_mode == InputMode::Buttons => Some(
+                "Spooky marks of quantum tic-tac-toe can not be shown on buttons, please use arrows or coordinates.".to_string()
+            ),
+            Some(_) if mode != Mode::Gomoku && swap2 => Some(
+                "The swap2 opening can be used only in gomoku.".to_string()
+            ),
+            Some(rules) if input_mode == InputMode::Buttons && rules.width.max(rules.height) > MAX_BUTTONS_BOARD_SIZE => Some(format!(
+                "Buttons can be used only on boards up to {0}×{0}, please use arrows or coordinates for bigger boards.",
+                MAX_BUTTONS_BOARD_SIZE,
+            )),
+            // One row of buttons is taken by marks
+            Some(rules) if input_mode == InputMode::Buttons && mode == Mode::Wild && rules.height == MAX_BUTTONS_BOARD_SIZE => Some(format!(
+                "Buttons can be used in wild tic-tac-toe only on boards up to {} rows, please use arrows or coordinates for bigger boards.",
+                MAX_BUTTONS_BOARD_SIZE - 1,
+            )),
+            Some(_) => None,
+        };
+
+        if let Some(error) = error {
+            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Invalid board")
+                            .description(error)
+                    )
+                )
+            )
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        let options = GameOptions {
+            mode,
+            rules: rules.unwrap(),
+            time_control,
+            swap2,
+            teams,
+            series,
+        };
+
+        if let Some(opponent) = opponent {
+            self.challenge(&ctx.http, interaction, opponent, expires_in, input_mode, options).await;
+            return;
+        }
+
+        if vs_bot {
+            let bot_id = ctx.cache.current_user().id;
+            self.start_bot_game(&ctx.http, interaction, bot_id, difficulty, input_mode, options).await;
+            return;
+        }
+
+        let key = self.queue_key(&interaction);
+        let name = display_name(&interaction);
+
+        // The game starts when there are enough waiting players, usually one
+        let lobby = {
+            self.wait_users.lock().await.pop(key, options.seats() - 1, |val| val.5.is_matching(&options))
+        };
+
+        let Some(lobby) = lobby else {
+            self.join_lobby(&ctx.http, interaction, key, name, input_mode, options).await;
+            return;
+        };
+
+        interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Please, wait")
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        let player = (interaction.user.id, None, name, None, input_mode);
+        self.start_lobby_game(&ctx.http, interaction, lobby, player, options).await;
+    }
+
+    // The `player` has completed the lobby by the `interaction`, which has a response already
+    async fn start_lobby_game(&self, http: &Http, interaction: CommandInteraction, lobby: Vec<QueueEntry<WaitUser>>, player: SessionPlayer, options: GameOptions) {
+        // The player who has been waiting for the longest time chooses the time control
+        let options = GameOptions { time_control: lobby[0].value.5.time_control, ..options };
+
+        let mut players: Vec<SessionPlayer> = lobby.into_iter()
+            .map(|entry| {
+                let (user_id, interaction, name, message, input_mode, _) = entry.value;
+                (user_id, Some(PlayerInteraction::Command(interaction)), name, Some(message), input_mode)
+            })
+            .collect();
+
+        players.push(player);
+
+        let names = join_names(players.iter().map(|val| val.2.as_str()));
+
+        // Only one public message is kept in every channel, channel ids are unique
+        let mut channels = Vec::new();
+
+        for player in &mut players {
+            let channel_id = player.3.as_ref().map_or(interaction.channel_id, |val| val.channel_id);
+
+            if channels.contains(&channel_id) {
+                if let Some(message) = player.3.take() {
+                    message.delete(http).await.unwrap();
+                }
+            }
+            else if player.3.is_none() {
+                let message = channel_id.send_message(http,
+                    CreateMessage::new()
+                        .embed(
+                            CreateEmbed::new()
+                                .title(format!("The game between {} in progress!", names))
+                        )
+                )
+                .await
+                .unwrap();
+
+                player.3 = Some(message);
+            }
+
+            channels.push(channel_id);
+        }
+
+        players.last_mut().unwrap().1 = Some(PlayerInteraction::Command(interaction));
+
+        let new_game = match self.add_session(GameSession::new(players, None, options)).await {
+            Ok(val) => val,
+            Err(session) => {
+                discard_session(http, session).await;
+                return;
+            }
+        };
+
+        self.process_session(http, &mut *new_game.lock().await).await;
+    }
+
+    // The player waits for others, public messages of the lobby show everyone who is waiting.
+    // Discord is called without the lock of the queue, so players of other servers do not wait for it.
+    async fn join_lobby(&self, http: &Http, interaction: CommandInteraction, key: QueueKey, name: String, input_mode: InputMode, options: GameOptions) {
+        let (host, mut names) = {
+            let wait_users = self.wait_users.lock().await;
+            let lobby = wait_users.matching(key, |val| val.5.is_matching(&options));
+
+            let host = lobby.first().map(|entry| entry.value.1.clone());
+            let names: Vec<String> = lobby.iter().map(|entry| entry.value.2.clone()).collect();
+
+            (host, names)
+        };
+
+        names.push(name.clone());
+
+        let embed = waiting_embed(host.as_ref().unwrap_or(&interaction), &options, &names.iter().map(String::as_str).collect::<Vec<_>>());
+
+        let message = interaction.channel_id.send_message(http, CreateMessage::new().embed(embed))
+            .await
+            .unwrap();
+
+        let title = match options.seats() {
+            2 => "Please, wait for second player...".to_string(),
+            players => format!("Please, wait for other players, {} of {} are here...", names.len(), players),
+        };
+
+        interaction.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title(title)
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        // Others could have joined, left or completed the lobby meanwhile
+        let joined = {
+            let mut wait_users = self.wait_users.lock().await;
+
+            if wait_users.contains(interaction.user.id) {
+                None
+            }
+            else if let Some(lobby) = wait_users.pop(key, options.seats() - 1, |val| val.5.is_matching(&options)) {
+                Some(Err(lobby))
+            }
+            else {
+                let lobby = wait_users.matching(key, |val| val.5.is_matching(&options));
+
+                let host = lobby.first().map_or(&interaction, |entry| &entry.value.1);
+                let mut lobby_names: Vec<_> = lobby.iter().map(|entry| entry.value.2.as_str()).collect();
+                lobby_names.push(&name);
+
+                // The message of the player is edited only if it is outdated
+                let is_outdated = lobby_names != names.iter().map(String::as_str).collect::<Vec<_>>();
+                let embed = waiting_embed(host, &options, &lobby_names);
+
+                let mut messages: Vec<_> = lobby.iter().map(|entry| (entry.value.3.channel_id, entry.value.3.id)).collect();
+                if is_outdated {
+                    messages.push((message.channel_id, message.id));
+                }
+
+                wait_users.push(key, QueueEntry {
+                    user_id: interaction.user.id,
+                    joined_at: storage::now(),
+                    value: (interaction.user.id, interaction.clone(), name.clone(), message.clone(), input_mode, options),
+                });
+
+                Some(Ok((messages, embed)))
+            }
+        };
+
+        match joined {
+            // The same player has joined twice at once
+            None => {
+                if let Err(err) = message.delete(http).await {
+                    eprintln!("Failed to delete the lobby message: {err:?}");
+                }
+
+                if let Err(err) = interaction.delete_response(http).await {
+                    eprintln!("Failed to delete the lobby response: {err:?}");
+                }
+            }
+
+            Some(Ok((messages, embed))) => {
+                for (channel_id, message_id) in messages {
+                    if let Err(err) = channel_id.edit_message(http, message_id, EditMessage::new().embed(embed.clone())).await {
+                        eprintln!("Failed to edit the lobby message: {err:?}");
+                    }
+                }
+            }
+
+            // The last player has joined while this one was sending the message
+            Some(Err(lobby)) => {
+                let player = (interaction.user.id, None, name, Some(message), input_mode);
+                self.start_lobby_game(http, interaction, lobby, player, options).await;
+            }
+        }
+    }
+
+    async fn start_bot_game(
+        &self,
+        http: &Http,
+        interaction: CommandInteraction,
+        bot_id: UserId,
+        difficulty: Difficulty,
+        input_mode: InputMode,
+        options: GameOptions,
+    ) {
+        let name = display_name(&interaction);
+        let bot_name = format!("Computer ({})", difficulty.name());
+
+        interaction.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Please, wait")
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        let message = interaction.channel_id.send_message(http,
+            CreateMessage::new()
+                .embed(
+                    CreateEmbed::new()
+                        .title(
+                            format!(
+                                "The game between {} and {} in progress!",
+                                name,
+                                bot_name,
+                            )
+                        )
+                )
+        )
+        .await
+        .unwrap();
+
+        let session = GameSession::new(
+            vec![
+                (interaction.user.id, Some(PlayerInteraction::Command(interaction)), name, Some(message), input_mode),
+                (bot_id, None, bot_name, None, InputMode::Arrows),
+            ],
+            Some(difficulty),
+            options,
+        );
+
+        let new_game = match self.add_session(session).await {
+            Ok(val) => val,
+            Err(session) => {
+                discard_session(http, session).await;
+                return;
+            }
+        };
+
+        self.process_session(http, &mut *new_game.lock().await).await;
+    }
+
+    async fn challenge(
+        &self,
+        http: &Http,
+        interaction: CommandInteraction,
+        opponent: (UserId, String, bool), // Last element is true for bots
+        expires_in: i64,
+        input_mode: InputMode,
+        options: GameOptions,
+    ) {
+        let error = if opponent.0 == interaction.user.id {
+            Some("You can not challenge yourself.")
+        }
+        else if opponent.2 {
+            Some("You can not challenge bots. For playing against the computer you should use the `vs` option.")
+        }
+        else {
+            None
+        };
+
+        if let Some(error) = error {
+            interaction.create_response(http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Invalid challenge")
+                            .description(error)
+                    )
+                )
+            )
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        let name = display_name(&interaction);
+        let id = interaction.id.get();
+        let expires_at = storage::now() + expires_in;
+
+        let icon_url = interaction.user.avatar_url().unwrap_or_else(||
+            interaction.user.default_avatar_url()
+        );
+
+        let accept = CreateButton::new(format!("challenge_accept_{id}"))
+            .label("Accept")
+            .style(ButtonStyle::Success);
+
+        let decline = CreateButton::new(format!("challenge_decline_{id}"))
+            .label("Decline")
+            .style(ButtonStyle::Danger);
+
+        // Mentions in embeds do not notify users, so it is in the content
+        let message = interaction.channel_id.send_message(http, CreateMessage::new()
+            .content(opponent.0.mention().to_string())
+            .embed(
+                CreateEmbed::new()
+                    .author(
+                        CreateEmbedAuthor::new(name.clone())
+                            .icon_url(icon_url)
+                    )
+                    .title(format!("{} challenges {} to a {} game!", name, opponent.1, options.game_name()))
+                    .description(format!("The challenge expires <t:{}:R>.", expires_at))
+                    .field("Board", options.describe_board(), true)
+                    .field("Time control", options.time_control.describe(), true)
+            )
+            .components(vec![CreateActionRow::Buttons(vec![accept, decline])])
+        )
+        .await
+        .unwrap();
+
+        interaction.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title(format!("Please, wait for {} to accept the challenge...", opponent.1))
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        self.challenges.lock().await.push(Challenge {
+            id,
+            challenger: (interaction.user.id, interaction, name, input_mode),
+            opponent: (opponent.0, opponent.1),
+            message,
+            expires_at,
+            options,
+        });
+    }
+
+    async fn challenge_component(&self, http: &Http, component: ComponentInteraction) {
+        let custom_id = component.data.custom_id.as_str();
+
+        let (accept, id) = match custom_id.strip_prefix("challenge_accept_") {
+            Some(val) => (true, val),
+            None => (false, custom_id.strip_prefix("challenge_decline_").unwrap()),
+        };
+
+        let id: u64 = id.parse().unwrap();
+
+        let (is_opponent, is_challenger) = {
+            let challenges = self.challenges.lock().await;
+
+            match challenges.iter().find(|val| val.id == id) {
+                Some(val) => (val.opponent.0 == component.user.id, val.challenger.0 == component.user.id),
+                None => {
+                    // Already accepted, declined or expired
+                    component.create_response(http, CreateInteractionResponse::Acknowledge).await.unwrap();
+                    return;
+                }
+            }
+        };
+
+        // The challenger can only cancel the challenge
+        let is_allowed = is_opponent || (is_challenger && !accept);
+
+        let error = if !is_allowed {
+            Some("This challenge is not for you.")
+        }
+        else if accept && self.is_busy(component.user.id).await {
+            Some("You have already in the game. For accepting the challenge you should use the `/stop` command.")
+        }
+        else {
+            None
+        };
+
+        if let Some(error) = error {
+            component.create_response(http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(error)
+            ))
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        let challenge = {
+            let mut challenges = self.challenges.lock().await;
+
+            match challenges.iter().position(|val| val.id == id) {
+                Some(pos) => challenges.swap_remove(pos),
+                None => {
+                    component.create_response(http, CreateInteractionResponse::Acknowledge).await.unwrap();
+                    return;
+                }
+            }
+        };
+
+        if !accept {
+            let description = if is_challenger {
+                format!("{} has cancelled the challenge.", challenge.challenger.2)
+            }
+            else {
+                format!("{} has declined the challenge.", challenge.opponent.1)
+            };
+
+            component.create_response(http, CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content("")
+                    .embed(
+                        CreateEmbed::new()
+                            .title(format!("{} has challenged {} to a {} game", challenge.challenger.2, challenge.opponent.1, challenge.options.game_name()))
+                            .description(description)
+                    )
+                    .components(Vec::new())
+            ))
+            .await
+            .unwrap();
+
+            challenge.challenger.1.delete_response(http).await.unwrap();
+            return;
+        }
+
+        let Challenge { challenger, opponent, message, options, .. } = challenge;
+
+        // The accepting player has not chosen an input mode
+        let session = GameSession::new(
+            vec![
+                (challenger.0, Some(PlayerInteraction::Command(challenger.1)), challenger.2, Some(message), challenger.3),
+                (opponent.0, Some(PlayerInteraction::Component(component.clone())), opponent.1, None, default_input_mode(options.mode)),
+            ],
+            None,
+            options,
+        );
+
+        // Both players could have started other games since the challenge
+        let new_game = match self.add_session(session).await {
+            Ok(val) => val,
+            Err(mut session) => {
+                component.create_response(http, CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content("")
+                        .embed(
+                            CreateEmbed::new()
+                                .title(format!("{} has challenged {} to a {} game", session.players[0].2, session.players[1].2, options.game_name()))
+                                .description("One of the players has started another game meanwhile.")
+                        )
+                        .components(Vec::new())
+                ))
+                .await
+                .unwrap();
+
+                // The challenge message is the response of the component, it has been updated instead
+                session.players[0].3 = None;
+                session.players[1].1 = None;
+                discard_session(http, session).await;
+                return;
+            }
+        };
+
+        let mut session = new_game.lock().await;
+
+        // The response of this interaction becomes the private game message
+        component.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Please, wait")
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        if let Some(message) = session.players[0].3.as_mut() {
+            message.edit(http, EditMessage::new()
+                .content("")
+                .components(Vec::new())
+            )
+            .await
+            .unwrap();
+        }
+
+        self.process_session(http, &mut session).await;
+    }
+
+    // The game starts when everyone has pressed the button, rematches start with swapped marks
+    async fn pending_game_component(&self, http: &Arc<Http>, component: ComponentInteraction) {
+        let error = {
+            let pending_games = self.pending_games.lock().await;
+
+            match pending_games.iter().find(|val| val.players.iter().any(|player| player.3.as_ref().is_some_and(|message| message.id == component.message.id))) {
+                None => Some("The game is not available anymore."),
+                Some(pending) if !pending.players.iter().any(|val| val.0 == component.user.id) => Some("This game is not for you."),
+                Some(_) => None,
+            }
+        };
+
+        let error = match error {
+            None if self.is_busy(component.user.id).await => Some("You have already in the game. For starting this game you should use the `/stop` command."),
+            error => error,
+        };
+
+        if let Some(error) = error {
+            component.create_response(http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(error)
+            ))
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        // The response of this interaction becomes the private game message
+        component.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Please, wait")
+                        .description("The game starts when everyone has pressed the button.")
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        let pending = {
+            let mut pending_games = self.pending_games.lock().await;
+
+            let Some(pos) = pending_games.iter().position(|val| val.players.iter().any(|player| player.3.as_ref().is_some_and(|message| message.id == component.message.id))) else {
+                return;
+            };
+
+            let pending = &mut pending_games[pos];
+            let seat = pending.players.iter().position(|val| val.0 == component.user.id).unwrap();
+
+            // The latest press of the button is kept
+            if let Some(interaction) = pending.players[seat].1.replace(PlayerInteraction::Component(component)) {
+                interaction.delete_response(http).await.unwrap();
+            }
+
+            if !pending.is_accepted() {
+                return;
+            }
+
+            pending_games.swap_remove(pos)
+        };
+
+        let PendingGame { players, bot, options, series, swapped, tournament_match, .. } = pending;
+
+        let mut session = GameSession::new(players, bot, options);
+        session.swapped = swapped;
+        session.series = series.or(session.series);
+        session.tournament_match = tournament_match;
+
+        // Someone could have started another game while waiting for others
+        let new_game = match self.add_session(session).await {
+            Ok(val) => val,
+            Err(session) => {
+                for interaction in session.players.iter().filter_map(|val| val.1.as_ref()) {
+                    interaction.delete_response(http).await.unwrap();
+                }
+
+                // The match is announced again
+                if let Some(val) = tournament_match {
+                    self.live_matches.lock().await.retain(|&live| live != val);
+                }
+
+                return;
+            }
+        };
+
+        let mut session = new_game.lock().await;
+
+        for message in session.players.iter_mut().filter_map(|val| val.3.as_mut()) {
+            message.edit(http, EditMessage::new().components(Vec::new())).await.unwrap();
+        }
+
+        // The computer could have to make the first move
+        if let (Some(difficulty), 1) = (session.bot, session.current_seat()) {
+            let (cell, piece) = choose_bot_move(&session.position, difficulty).await;
+            session.piece = piece;
+
+            self.play_move(http, &mut session, &new_game, cell).await;
+            return;
+        }
+
+        self.process_session(http, &mut session).await;
+    }
+
+    // Called periodically for handling of timeouts
+    pub async fn tick(&self, http: &Arc<Http>) {
+        let now = storage::now();
+
+        let expired: Vec<Challenge> = {
+            let mut challenges = self.challenges.lock().await;
+
+            let (expired, active) = std::mem::take(&mut *challenges).into_iter()
+                .partition(|val| val.expires_at <= now);
+
+            *challenges = active;
+            expired
+        };
+
+        // Errors of Discord are only logged, so one deleted message does not stop the timers
+        for mut challenge in expired {
+            let edited = challenge.message.edit(http, EditMessage::new()
+                .content("")
+                .embed(
+                    CreateEmbed::new()
+                        .title(format!("{} has challenged {} to a {} game", challenge.challenger.2, challenge.opponent.1, challenge.options.game_name()))
+                        .description("The challenge has expired.")
+                )
+                .components(Vec::new())
+            )
+            .await;
+
+            if let Err(err) = edited {
+                eprintln!("Failed to edit the expired challenge: {err:?}");
+            }
+
+            if let Err(err) = challenge.challenger.1.delete_response(http).await {
+                eprintln!("Failed to delete the challenge response: {err:?}");
+            }
+        }
+
+        let expired: Vec<PendingGame> = {
+            let mut pending_games = self.pending_games.lock().await;
+
+            let (expired, active) = std::mem::take(&mut *pending_games).into_iter()
+                .partition(|val| val.expires_at <= now);
+
+            *pending_games = active;
+            expired
+        };
+
+        for mut pending in expired {
+            for interaction in pending.players.iter().filter_map(|val| val.1.as_ref()) {
+                if let Err(err) = interaction.delete_response(http).await {
+                    eprintln!("Failed to delete the response of the expired game: {err:?}");
+                }
+            }
+
+            for message in pending.players.iter_mut().filter_map(|val| val.3.as_mut()) {
+                if let Err(err) = message.edit(http, EditMessage::new().components(Vec::new())).await {
+                    eprintln!("Failed to edit the message of the expired game: {err:?}");
+                }
+            }
+
+            self.forfeit_tournament_match(http, &mut pending).await;
+        }
+
+        self.expire_tournament_registrations(http).await;
+        self.announce_tournament_matches(http).await;
+
+        let sessions: Vec<_> = self.sessions.lock().await.iter().map(|val| Arc::clone(&val.1)).collect();
+
+        for original_session in sessions {
+            let mut session = original_session.lock().await;
+
+            // The game could have been finished while waiting for the lock
+            if !self.is_active(&original_session).await {
+                continue;
+            }
+
+            let deadline = session.clock.deadline(session.current_seat());
+
+            if deadline <= now {
+                self.time_out(http, &mut session, &original_session).await;
+            }
+            else if deadline - now <= clock::WARNING_SECONDS && !session.warned {
+                session.warned = true;
+
+                if let Some(interaction) = session.current_interaction() {
+                    let warning = interaction.create_followup(http, CreateInteractionResponseFollowup::new()
+                        .ephemeral(true)
+                        .embed(
+                            CreateEmbed::new()
+                                .title("Hurry up!")
+                                .description(format!("Your time runs out <t:{}:R>, then you lose the game.", deadline))
+                        )
+                    )
+                    .await;
+
+                    // The token of the interaction could have expired in a long game
+                    if let Err(err) = warning {
+                        eprintln!("Failed to warn about the time: {err:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    // The current player has run out of time and loses the game
+    async fn time_out(&self, http: &Arc<Http>, session: &mut GameSession, original_session: &Arc<Mutex<GameSession>>) {
+        let loser = session.current_seat();
+        let result = forfeit_result(session.players.len(), session.is_team_game(), loser);
+
+        let description = match result.winner() {
+            Some(winner) => format!("⏰ {} has run out of time, {} has won! ⏰", session.name(loser), session.side_name(winner.index())),
+            None => format!("⏰ {} has run out of time, everyone else has won! ⏰", session.name(loser)),
+        };
+
+        self.end_game_with_message(http, session, original_session, description, result, EndReason::Timeout).await;
+    }
+
+    fn queue_key(&self, interaction: &CommandInteraction) -> QueueKey {
+        let scope = match interaction.guild_id {
+            Some(guild_id) => self.storage.queue_scope(guild_id).unwrap_or_else(|err| {
+                eprintln!("Failed to load the queue scope: {err:?}");
+                QueueScope::default()
+            }),
+            None => QueueScope::Channel,
+        };
+
+        QueueKey::new(scope, interaction.guild_id, interaction.channel_id)
+    }
+
+    async fn queue(&self, ctx: Context, interaction: CommandInteraction) {
+        let options = interaction.data.options();
+        let Some(option) = options.first() else {
+            return;
+        };
+
+        match (option.name, &option.value) {
+            ("show", _) => self.show_queue(&ctx.http, &interaction).await,
+
+            ("leave", _) => {
+                let has_left = self.leave_queue(&ctx.http, &interaction).await;
+
+                if !has_left {
+                    interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .embed(
+                                CreateEmbed::new()
+                                    .title("Nothing to leave")
+                                    .description("You are not waiting for the second player.")
+                            )
+                        )
+                    )
+                    .await
+                    .unwrap();
+                }
+            }
+
+            ("scope", ResolvedValue::SubCommand(options)) => self.set_queue_scope(&ctx.http, &interaction, options).await,
+
+            _ => (),
+        }
+    }
+
+    async fn show_queue(&self, http: &Http, interaction: &CommandInteraction) {
+        let scope = match interaction.guild_id {
+            Some(guild_id) => self.storage.queue_scope(guild_id).unwrap_or_default(),
+            None => QueueScope::Channel,
+        };
+
+        let description = {
+            let wait_users = self.wait_users.lock().await;
+            let entries = wait_users.entries(interaction.guild_id, interaction.channel_id);
+
+            if entries.is_empty() {
+                "No one is waiting for the second player.".to_string()
+            }
+            else {
+                entries.iter()
+                    .enumerate()
+                    .map(|(i, entry)| format!(
+                        "**{}.** {} in {}, waiting since <t:{}:R>",
+                        i + 1,
+                        entry.value.2,
+                        entry.value.1.channel_id.mention(),
+                        entry.joined_at,
+                    ))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+        };
+
+        let scope_description = match scope {
+            QueueScope::Guild => "Players are matched with anyone on the server.",
+            QueueScope::Channel => "Players are matched only with players in the same channel.",
+        };
+
+        interaction.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Queue")
+                        .description(description)
+                        .footer(CreateEmbedFooter::new(scope_description))
+                )
+            )
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn set_queue_scope(&self, http: &Http, interaction: &CommandInteraction, options: &[ResolvedOption<'_>]) {
+        let scope = options.iter()
+            .find_map(|option| match option.value {
+                ResolvedValue::String(val) => QueueScope::from_str(val),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let can_manage = interaction.member.as_ref()
+            .and_then(|member| member.permissions)
+            .is_some_and(|permissions| permissions.manage_guild());
+
+        let description = match interaction.guild_id {
+            Some(guild_id) if can_manage => match self.storage.set_queue_scope(guild_id, scope) {
+                Ok(()) => match scope {
+                    QueueScope::Guild => "Now players are matched with anyone on the server.",
+                    QueueScope::Channel => "Now players are matched only with players in the same channel.",
+                },
+                Err(err) => {
+                    eprintln!("Failed to save the queue scope: {err:?}");
+                    "Failed to save the queue scope!"
+                }
+            },
+            Some(_) => "You need the Manage Server permission for changing the queue scope.",
+            None => "The queue scope can be changed only on servers.",
+        };
+
+        interaction.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Queue scope")
+                        .description(description)
+                )
+            )
+        )
+        .await
+        .unwrap();
+    }
+
+    // Returns false if the user is not waiting for the second player
+    async fn leave_queue(&self, http: &Http, interaction: &CommandInteraction) -> bool {
+        let wait_user = self.wait_users.lock().await.remove(interaction.user.id);
+
+        let Some(QueueEntry { value: (_, wait_interaction, _, message, ..), .. }) = wait_user else {
+            return false;
+        };
+
+        // The token of the interaction expires after 15 minutes of waiting
+        if let Err(err) = wait_interaction.delete_response(http).await {
+            eprintln!("Failed to delete the lobby response: {err:?}");
+        }
+
+        if let Err(err) = message.delete(http).await {
+            eprintln!("Failed to delete the lobby message: {err:?}");
+        }
+
+        interaction.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Stopped")
+                        .description("You are no longer waiting for the second player.")
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        true
+    }
+
+    async fn tournament(&self, ctx: Context, interaction: CommandInteraction) {
+        let options = interaction.data.options();
+        let Some(option) = options.first() else {
+            return;
+        };
+
+        let response = match (option.name, &option.value) {
+            ("create", ResolvedValue::SubCommand(options)) => self.create_tournament(&interaction, options).await,
+            ("join", _) => self.join_tournament(&interaction).await,
+            ("start", _) => self.start_tournament(&interaction).await,
+            ("status", _) => self.tournament_status(&interaction).await,
+            ("cancel", _) => self.cancel_tournament(&interaction).await,
+            _ => return,
+        };
+
+        let message = response.unwrap_or_else(|error| {
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Tournament")
+                        .description(error)
+                )
+        });
+
+        interaction.create_response(&ctx.http, CreateInteractionResponse::Message(message))
+            .await
+            .unwrap();
+    }
+
+    // Only one tournament at a time in every channel, the creator is the first player
+    async fn create_tournament(&self, interaction: &CommandInteraction, options: &[ResolvedOption<'_>]) -> Result<CreateInteractionResponseMessage, String> {
+        let mut format = Format::SingleElimination;
+        let mut mode = Mode::Classic;
+        let mut name = None;
+
+        for option in options {
+            match (option.name, &option.value) {
+                ("format", ResolvedValue::String(val)) => format = Format::from_str(val).unwrap_or(format),
+                ("mode", ResolvedValue::String(val)) => mode = Mode::from_str(val).unwrap_or(mode),
+                ("name", ResolvedValue::String(val)) => name = Some(val.trim().to_string()),
+                _ => (),
+            }
+        }
+
+        let mut tournaments = self.tournaments.lock().await;
+
+        if tournaments.iter().any(|val| val.channel_id == interaction.channel_id) {
+            return Err("This channel has a tournament already, it can be joined with `/tournament join`.".to_string());
+        }
+
+        let mut tournament = Tournament {
+            id: 0,
+            guild_id: interaction.guild_id,
+            channel_id: interaction.channel_id,
+            creator_id: interaction.user.id,
+
+            name: name.filter(|val| !val.is_empty())
+                .unwrap_or_else(|| format!("Tournament of {}", game_name(mode))),
+            format,
+            mode,
+            status: Status::Registration,
+
+            players: vec![(interaction.user.id, display_name(interaction))],
+            matches: Vec::new(),
+
+            created_at: storage::now(),
+        };
+
+        tournament.id = self.storage.save_tournament(&tournament).map_err(|err| {
+            eprintln!("Failed to save the tournament: {err:?}");
+            "Failed to save the tournament!".to_string()
+        })?;
+
+        let message = CreateInteractionResponseMessage::new()
+            .embed(
+                tournament_embed(&tournament)
+                    .description(format!(
+                        "{} has created a tournament! Join it with `/tournament join`, then {} starts it with `/tournament start`.",
+                        tournament.players[0].1,
+                        tournament.players[0].1,
+                    ))
+            );
+
+        tournaments.push(tournament);
+
+        Ok(message)
+    }
+
+    async fn join_tournament(&self, interaction: &CommandInteraction) -> Result<CreateInteractionResponseMessage, String> {
+        let mut tournaments = self.tournaments.lock().await;
+
+        let Some(tournament) = tournaments.iter_mut().find(|val| val.channel_id == interaction.channel_id) else {
+            return Err("There is no tournament in this channel, it can be created with `/tournament create`.".to_string());
+        };
+
+        if tournament.status != Status::Registration {
+            return Err("The tournament has started already.".to_string());
+        }
+
+        if tournament.players.iter().any(|val| val.0 == interaction.user.id) {
+            return Err("You have joined the tournament already.".to_string());
+        }
+
+        if tournament.players.len() >= tournament::MAX_PLAYERS {
+            return Err(format!("The tournament is full, it can have up to {} players.", tournament::MAX_PLAYERS));
+        }
+
+        let name = display_name(interaction);
+        tournament.players.push((interaction.user.id, name.clone()));
+
+        if let Err(err) = self.storage.save_tournament(tournament) {
+            eprintln!("Failed to save the tournament: {err:?}");
+        }
+
+        Ok(CreateInteractionResponseMessage::new()
+            .embed(
+                tournament_embed(tournament)
+                    .description(format!("{} has joined the tournament!", name))
+            ))
+    }
+
+    // Matches are announced in the channel by the next tick
+    async fn start_tournament(&self, interaction: &CommandInteraction) -> Result<CreateInteractionResponseMessage, String> {
+        let mut tournaments = self.tournaments.lock().await;
+
+        let Some(tournament) = tournaments.iter_mut().find(|val| val.channel_id == interaction.channel_id) else {
+            return Err("There is no tournament in this channel, it can be created with `/tournament create`.".to_string());
+        };
+
+        if tournament.creator_id != interaction.user.id {
+            return Err("Only the creator of the tournament can start it.".to_string());
+        }
+
+        if tournament.status != Status::Registration {
+            return Err("The tournament has started already.".to_string());
+        }
+
+        if tournament.players.len() < tournament.format.min_players() {
+            return Err(format!(
+                "{} tournaments need at least {} players.",
+                tournament.format.name(),
+                tournament.format.min_players(),
+            ));
+        }
+
+        tournament.start();
+
+        if let Err(err) = self.storage.save_tournament(tournament) {
+            eprintln!("Failed to save the tournament: {err:?}");
+        }
+
+        Ok(CreateInteractionResponseMessage::new()
+            .embed(
+                tournament_embed(tournament)
+                    .description("The tournament has started! Players of every match get a button for starting their game.")
+                    .attachment("bracket.png")
+            )
+            .add_file(generate_attachment_rgb8(&draw_bracket(tournament), "bracket.png")))
+    }
+
+    async fn tournament_status(&self, interaction: &CommandInteraction) -> Result<CreateInteractionResponseMessage, String> {
+        let tournaments = self.tournaments.lock().await;
+
+        let Some(tournament) = tournaments.iter().find(|val| val.channel_id == interaction.channel_id) else {
+            return Err("There is no tournament in this channel, it can be created with `/tournament create`.".to_string());
+        };
+
+        let mut embed = tournament_embed(tournament);
+        let mut message = CreateInteractionResponseMessage::new().ephemeral(true);
+
+        if tournament.status != Status::Registration {
+            embed = embed.attachment("bracket.png");
+            message = message.add_file(generate_attachment_rgb8(&draw_bracket(tournament), "bracket.png"));
+        }
+
+        Ok(message.embed(embed))
+    }
+
+    // Games of the tournament which are in progress are finished as usual, but their results are not recorded
+    async fn cancel_tournament(&self, interaction: &CommandInteraction) -> Result<CreateInteractionResponseMessage, String> {
+        let mut tournaments = self.tournaments.lock().await;
+
+        let Some(pos) = tournaments.iter().position(|val| val.channel_id == interaction.channel_id) else {
+            return Err("There is no tournament in this channel, it can be created with `/tournament create`.".to_string());
+        };
+
+        if tournaments[pos].creator_id != interaction.user.id {
+            return Err("Only the creator of the tournament can cancel it.".to_string());
+        }
+
+        let mut tournament = tournaments.swap_remove(pos);
+        self.close_tournament(&mut tournament).await;
+
+        Ok(CreateInteractionResponseMessage::new()
+            .embed(
+                tournament_embed(&tournament)
+                    .description(format!("{} has cancelled the tournament.", tournament.players[0].1))
+            ))
+    }
+
+    async fn expire_tournament_registrations(&self, http: &Http) {
+        let expired: Vec<Tournament> = {
+            let mut tournaments = self.tournaments.lock().await;
+            let expires_at = storage::now() - MAX_REGISTRATION_HOURS * 60 * 60;
+
+            let (expired, active) = std::mem::take(&mut *tournaments).into_iter()
+                .partition(|val| val.status == Status::Registration && val.created_at <= expires_at);
+
+            *tournaments = active;
+            expired
+        };
+
+        for mut tournament in expired {
+            self.close_tournament(&mut tournament).await;
+
+            let sent = tournament.channel_id.send_message(http,
+                CreateMessage::new()
+                    .embed(
+                        tournament_embed(&tournament)
+                            .description(format!("The tournament has not been started in {} hours, so it is cancelled.", MAX_REGISTRATION_HOURS))
+                    )
+            )
+            .await;
+
+            if let Err(err) = sent {
+                eprintln!("Failed to announce the expired tournament: {err:?}");
+            }
+        }
+    }
+
+    // The tournament has been removed from `tournaments` already
+    async fn close_tournament(&self, tournament: &mut Tournament) {
+        tournament.status = Status::Cancelled;
+        self.live_matches.lock().await.retain(|val| val.0 != tournament.id);
+
+        if let Err(err) = self.storage.save_tournament(tournament) {
+            eprintln!("Failed to save the tournament: {err:?}");
+        }
+    }
+
+    // Posts a button for every match which can be played now, unless one of its players has another match in progress
+    async fn announce_tournament_matches(&self, http: &Http) {
+        let announcements: Vec<_> = {
+            let tournaments = self.tournaments.lock().await;
+            let mut live_matches = self.live_matches.lock().await;
+            let mut announcements = Vec::new();
+
+            for tournament in tournaments.iter().filter(|val| val.status == Status::Running) {
+                let mut busy: Vec<usize> = live_matches.iter()
+                    .filter(|val| val.0 == tournament.id)
+                    .flat_map(|val| tournament.slots(val.1))
+                    .filter_map(|slot| match slot {
+                        Slot::Player(player) => Some(player),
+                        _ => None,
+                    })
+                    .collect();
+
+                for index in tournament.ready_matches() {
+                    let [Slot::Player(first), Slot::Player(second)] = tournament.slots(index) else {
+                        continue;
+                    };
+
+                    if live_matches.contains(&(tournament.id, index)) || busy.contains(&first) || busy.contains(&second) {
+                        continue;
+                    }
+
+                    live_matches.push((tournament.id, index));
+                    busy.extend([first, second]);
+
+                    announcements.push((
+                        tournament.id,
+                        index,
+                        tournament.channel_id,
+                        tournament.name.clone(),
+                        tournament.mode,
+                        [tournament.players[first].clone(), tournament.players[second].clone()],
+                    ));
+                }
+            }
+
+            announcements
+        };
+
+        // Private game messages are responses to interactions of the players,
+        // so matches wait for both players to press the button instead of starting by themselves
+        for (tournament_id, index, channel_id, name, mode, [first, second]) in announcements {
+            let expires_at = storage::now() + PENDING_GAME_MINUTES * 60;
+
+            let message = channel_id.send_message(http,
+                CreateMessage::new()
+                    .content(format!("{} {}", first.0.mention(), second.0.mention()))
+                    .embed(
+                        CreateEmbed::new()
+                            .title(format!("{}: {} vs {}", name, first.1, second.1))
+                            .description(format!(
+                                "Press the button for starting the game. A player who has not pressed it <t:{}:R> loses the match.",
+                                expires_at,
+                            ))
+                    )
+                    .components(vec![CreateActionRow::Buttons(vec![
+                        CreateButton::new("tournament_match").label("Start the game").style(ButtonStyle::Success)
+                    ])])
+            )
+            .await;
+
+            // Without the message no one can press the button, so the match is forfeited when it expires
+            let message = match message {
+                Ok(message) => Some(message),
+                Err(err) => {
+                    eprintln!("Failed to announce the tournament match: {err:?}");
+                    None
+                }
+            };
+
+            let input_mode = default_input_mode(mode);
+
+            self.pending_games.lock().await.push(PendingGame {
+                players: vec![
+                    (first.0, None, first.1, message, input_mode),
+                    (second.0, None, second.1, None, input_mode),
+                ],
+                bot: None,
+                options: GameOptions::for_tournament(mode),
+                series: None,
+                swapped: false,
+                tournament_match: Some((tournament_id, index)),
+                expires_at,
+            });
+        }
+    }
+
+    // Players who have not come lose the match, if no one has come the first player
+    // goes on in elimination formats and the match is drawn in others
+    async fn forfeit_tournament_match(&self, http: &Http, pending: &mut PendingGame) {
+        let Some((tournament_id, index)) = pending.tournament_match else {
+            return;
+        };
+
+        let (result, description) = match (pending.players[0].1.is_some(), pending.players[1].1.is_some()) {
+            (true, _) => (MatchResult::First, format!("{} has not come, {} has won the match.", pending.players[1].2, pending.players[0].2)),
+            (false, true) => (MatchResult::Second, format!("{} has not come, {} has won the match.", pending.players[0].2, pending.players[1].2)),
+            (false, false) => {
+                let is_elimination = self.tournaments.lock().await.iter()
+                    .find(|val| val.id == tournament_id)
+                    .is_some_and(|val| val.format.is_elimination());
+
+                if is_elimination {
+                    (MatchResult::First, format!("No one has come, {} goes on by the seeding.", pending.players[0].2))
+                }
+                else {
+                    (MatchResult::Draw, "No one has come, the match is drawn.".to_string())
+                }
+            }
+        };
+
+        let title = format!("{} vs {}", pending.players[0].2, pending.players[1].2);
+
+        if let Some(message) = pending.players[0].3.as_mut() {
+            let edited = message.edit(http, EditMessage::new()
+                .embed(
+                    CreateEmbed::new()
+                        .title(title)
+                        .description(description)
+                )
+            )
+            .await;
+
+            // Also called by the timer, which must not stop on a deleted channel
+            if let Err(err) = edited {
+                eprintln!("Failed to edit the tournament match: {err:?}");
+            }
+        }
+
+        self.finish_tournament_match(http, tournament_id, index, result).await;
+    }
+
+    // The winner of the tournament is announced in its channel
+    async fn finish_tournament_match(&self, http: &Http, tournament_id: i64, index: usize, result: MatchResult) {
+        let finished = {
+            let mut tournaments = self.tournaments.lock().await;
+            self.live_matches.lock().await.retain(|&val| val != (tournament_id, index));
+
+            let Some(pos) = tournaments.iter().position(|val| val.id == tournament_id) else {
+                return;
+            };
+
+            let tournament = &mut tournaments[pos];
+            tournament.record(index, result);
+
+            if let Err(err) = self.storage.save_tournament(tournament) {
+                eprintln!("Failed to save the tournament: {err:?}");
+            }
+
+            (tournament.status == Status::Finished).then(|| tournaments.swap_remove(pos))
+        };
+
+        let Some(tournament) = finished else {
+            return;
+        };
+
+        let description = match tournament.winner() {
+            Some(winner) => format!("🏆 {} has won the tournament! 🏆", tournament.players[winner].1),
+            None => "The tournament is over.".to_string(),
+        };
+
+        let sent = tournament.channel_id.send_message(http,
+            CreateMessage::new()
+                .embed(
+                    tournament_embed(&tournament)
+                        .description(description)
+                        .attachment("bracket.png")
+                )
+                .add_file(generate_attachment_rgb8(&draw_bracket(&tournament), "bracket.png"))
+        )
+        .await;
+
+        if let Err(err) = sent {
+            eprintln!("Failed to announce the winner of the tournament: {err:?}");
+        }
+    }
+
+    // Spectators are added to the thread of the game, its live message is updated after every move
+    async fn watch_component(&self, http: &Http, component: ComponentInteraction) {
+        let original_session = self.get_game_by_message(component.message.id).await;
+
+        let error = match &original_session {
+            None => Some("The game has finished."),
+            Some(_) if component.guild_id.is_none() => Some("Games can be watched only on servers."),
+            Some(_) => None,
+        };
+
+        if let Some(error) = error {
+            component.create_response(http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(error)
+            ))
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        let original_session = original_session.unwrap();
+        let mut session = original_session.lock().await;
+
+        if session.seat_of_user(component.user.id).is_some() {
+            component.create_response(http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content("You are playing this game.")
+            ))
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        if session.thread.is_none() {
+            let name: String = format!("Game between {}", session.player_names()).chars().take(MAX_THREAD_NAME_LENGTH).collect();
+
+            let message = match component.channel_id.create_thread_from_message(http, component.message.id, CreateThread::new(name)).await {
+                Ok(thread) => thread.id.send_message(http,
+                    CreateMessage::new()
+                        .embed(spectator_embed(&session))
+                        .add_file(generate_attachment_rgb8(&render_board(&session), "canvas.png"))
+                )
+                .await,
+                Err(err) => Err(err),
+            };
+
+            let message = match message {
+                Ok(val) => val,
+                Err(err) => {
+                    eprintln!("Failed to create the thread for spectators: {err:?}");
+
+                    component.create_response(http, CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content("The thread for spectators could not be created, the bot may be missing permissions for threads.")
+                    ))
+                    .await
+                    .unwrap();
+
+                    return;
+                }
+            };
+
+            // Reactions are only a nicety, the thread works without them
+            for reaction in SPECTATOR_REACTIONS {
+                if let Err(err) = message.react(http, reaction).await {
+                    eprintln!("Failed to react to the spectator message: {err:?}");
+                    break;
+                }
+            }
+
+            session.thread = Some(message);
+        }
+
+        let thread_id = session.thread.as_ref().unwrap().channel_id;
+
+        if !session.spectators.contains(&component.user.id) {
+            if let Err(err) = thread_id.add_thread_member(http, component.user.id).await {
+                eprintln!("Failed to add a spectator to the thread: {err:?}");
+
+                component.create_response(http, CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(format!("You could not be added to {}, the thread may be archived or locked.", thread_id.mention()))
+                ))
+                .await
+                .unwrap();
+
+                return;
+            }
+
+            session.spectators.push(component.user.id);
+        }
+
+        component.create_response(http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Watching")
+                        .description(format!(
+                            "The board, the move list and clocks are updated after every move in {}. React to the board there, players are not disturbed.",
+                            thread_id.mention(),
+                        ))
+                )
+            )
+        )
+        .await
+        .unwrap();
+    }
+
+    // Games of other servers are not shown, except to their players
+    async fn replay(&self, ctx: Context, interaction: CommandInteraction) {
+        let id = interaction.data.options().iter()
+            .find_map(|option| match option.value {
+                ResolvedValue::Integer(val) => Some(val),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        // Drawing of long games takes more time than an interaction can wait for the response
+        interaction.create_response(&ctx.http, CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new()))
+            .await
+            .unwrap();
+
+        let record = self.visible_game(&interaction, id);
+        let replay = match &record {
+            Some(record) => Some(self.generate_replay(record.clone()).await),
+            None => None,
+        };
+
+        let response = match (record, replay) {
+            (Some(record), Some(Ok(replay))) => EditInteractionResponse::new()
+                .embed(replay_embed(&record, id))
+                .new_attachment(replay),
+            (_, Some(Err(err))) => {
+                eprintln!("Failed to replay the game: {err:?}");
+
+                EditInteractionResponse::new()
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Replay")
+                            .description(format!("Moves of the game #{} can not be replayed.", id))
+                    )
+            }
+            _ => EditInteractionResponse::new()
+                .embed(
+                    CreateEmbed::new()
+                        .title("Replay")
+                        .description(format!("The game #{} is not found.", id))
+                ),
+        };
+
+        interaction.edit_response(&ctx.http, response).await.unwrap();
+    }
+
+    async fn export(&self, ctx: Context, interaction: CommandInteraction) {
+        let id = interaction.data.options().iter()
+            .find_map(|option| match option.value {
+                ResolvedValue::Integer(val) => Some(val),
+                _ => None,
+            })
+            .unwrap_or_default();
+
+        let text = self.visible_game(&interaction, id).map(|val| notation::write(&val));
+
+        let response = match text {
+            Some(Ok(text)) => CreateInteractionResponseMessage::new()
+                .content(format!("The record of the game #{}, it can be loaded again with /import.", id))
+                .add_file(CreateAttachment::bytes(text.into_bytes(), format!("game_{}.txt", id))),
+            Some(Err(err)) => {
+                eprintln!("Failed to export the game: {err:?}");
+
+                CreateInteractionResponseMessage::new()
+                    .content(format!("Moves of the game #{} can not be exported.", id))
+                    .ephemeral(true)
+            }
+            None => CreateInteractionResponseMessage::new()
+                .content(format!("The game #{} is not found.", id))
+                .ephemeral(true),
+        };
+
+        interaction.create_response(&ctx.http, CreateInteractionResponse::Message(response))
+            .await
+            .unwrap();
+    }
+
+    // The imported game is replayed without saving, so it does not change statistics and ratings
+    async fn import(&self, ctx: Context, interaction: CommandInteraction) {
+        let mut file = None;
+        let mut text = None;
+
+        for option in interaction.data.options() {
+            match (option.name, option.value) {
+                ("file", ResolvedValue::Attachment(val)) => file = Some(val.clone()),
+                ("text", ResolvedValue::String(val)) => text = Some(val.to_string()),
+                _ => (),
+            }
+        }
+
+        interaction.create_response(&ctx.http, CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new()))
+            .await
+            .unwrap();
+
+        let text = match (file, text) {
+            (Some(file), _) if file.size > MAX_IMPORT_SIZE => Err(format!("The file is too big, records can have up to {} KB.", MAX_IMPORT_SIZE / 1024)),
+            (Some(file), _) => match file.download().await {
+                Ok(bytes) => String::from_utf8(bytes).map_err(|_| "The file is not a text.".to_string()),
+                Err(err) => {
+                    eprintln!("Failed to download the record: {err:?}");
+                    Err("The file can not be downloaded.".to_string())
+                }
+            },
+            (None, Some(text)) => Ok(text),
+            (None, None) => Err("Attach the file of the record or paste its text.".to_string()),
+        };
+
+        let game = text.and_then(|val| notation::parse(&val).map_err(|err| err.describe()));
+
+        let response = match game {
+            Ok(game) => {
+                let record = imported_record(&game, &interaction);
+
+                match self.generate_replay(record.clone()).await {
+                    Ok(replay) => EditInteractionResponse::new()
+                        .embed(import_embed(&record, game.date))
+                        .new_attachment(replay),
+                    Err(err) => {
+                        eprintln!("Failed to replay the imported game: {err:?}");
+                        EditInteractionResponse::new().content("Moves of the game can not be replayed.")
+                    }
+                }
+            }
+            Err(err) => EditInteractionResponse::new()
+                .embed(
+                    CreateEmbed::new()
+                        .title("Import")
+                        .description(format!("The record can not be loaded. {}", err))
+                ),
+        };
+
+        interaction.edit_response(&ctx.http, response).await.unwrap();
+    }
+
+    // Games of other servers are found only for their players
+    fn visible_game(&self, interaction: &CommandInteraction, id: i64) -> Option<GameRecord> {
+        let record = self.storage.game(id).unwrap_or_else(|err| {
+            eprintln!("Failed to load the game: {err:?}");
+            None
+        });
+
+        record.filter(|val| {
+            (val.guild_id.is_some() && val.guild_id == interaction.guild_id)
+                || [&val.player, &val.player2].into_iter().chain(&val.others).any(|player| player.0 == interaction.user.id)
+        })
+    }
+
+    async fn stop(&self, ctx: Context, interaction: CommandInteraction) {
+        if self.leave_queue(&ctx.http, &interaction).await {
+            return;
+        }
+
+        let challenge = {
+            let mut challenges = self.challenges.lock().await;
+
+            challenges.iter()
+                .position(|val| val.challenger.0 == interaction.user.id)
+                .map(|pos| challenges.swap_remove(pos))
+        };
+
+        if let Some(mut challenge) = challenge {
+            challenge.challenger.1.delete_response(&ctx.http).await.unwrap();
+
+            challenge.message.edit(&ctx.http, EditMessage::new()
+                .content("")
+                .embed(
+                    CreateEmbed::new()
+                        .title(format!("{} has challenged {} to a {} game", challenge.challenger.2, challenge.opponent.1, challenge.options.game_name()))
+                        .description(format!("{} has cancelled the challenge.", challenge.challenger.2))
+                )
+                .components(Vec::new())
+            )
+            .await
+            .unwrap();
+
+            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Stopped")
+                            .description("Your challenge has been cancelled.")
+                    )
+                )
+            )
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        let Some(original_session) = self.get_current_game(interaction.user.id).await else {
+            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Nothing to stop")
+                            .description("You are not in the game. For starting a new game you should use the `/play` command.")
+                    )
+                )
+            )
+            .await
+            .unwrap();
+
+            return;
+        };
+
+        let mut session = original_session.lock().await;
+
+        // A move or a timeout could have finished the game while waiting for the lock
+        if !self.is_active(&original_session).await {
+            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .embed(
+                        CreateEmbed::new()
+                            .title("Nothing to stop")
+                            .description("The game has already finished.")
+                    )
+                )
+            )
+            .await
+            .unwrap();
+
+            return;
+        }
+
+        let loser = session.seat_of_user(interaction.user.id).unwrap();
+        let result = forfeit_result(session.players.len(), session.is_team_game(), loser);
+
+        let description = match result.winner() {
+            Some(winner) => format!("🏳️ {} has resigned, {} has won! 🏳️", session.name(loser), session.side_name(winner.index())),
+            None => format!("🏳️ {} has resigned, everyone else has won! 🏳️", session.name(loser)),
+        };
+
+        interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Stopped")
+                        .description("You have resigned the game.")
+                )
+            )
+        )
+        .await
+        .unwrap();
+
+        self.end_game_with_message(&ctx.http, &mut session, &original_session, description, result, EndReason::Resignation).await;
+    }
+
+    async fn is_player_already_in_game(&self, http: &Http, interaction: &CommandInteraction) -> bool {
+        let message = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(
+                    CreateEmbed::new()
+                        .title("Start a new game")
+                        .description("You have already in the game. For starting a new game you should use the `/stop` command.")
+                )
+        );
+
+        if !self.is_busy(interaction.user.id).await {
+            return false;
+        }
+
+        interaction.create_response(http, message)
+            .await
+            .unwrap();
+
+        true
+    }
+
+    // The user is waiting for the second player, challenging someone or playing
+    async fn is_busy(&self, user_id: UserId) -> bool {
+        if self.wait_users.lock().await.contains(user_id) {
+            return true;
+        }
+
+        if self.challenges.lock().await.iter().any(|val| val.challenger.0 == user_id) {
+            return true;
+        }
+
+        self.get_current_game(user_id).await.is_some()
+    }
+
+    async fn process_session(&self, http: &Http, session: &mut GameSession) {
+        show_game_message(http, session).await;
+        show_wait_and_common_message(http, session).await;
+    }
+
+    pub async fn component(&self, ctx: Context, component: ComponentInteraction) {
+        if component.data.custom_id.starts_with("challenge_") {
+            self.challenge_component(&ctx.http, component).await;
+            return;
+        }
+
+        if matches!(component.data.custom_id.as_str(), "rematch" | "tournament_match") {
+            self.pending_game_component(&ctx.http, component).await;
+            return;
+        }
+
+        if component.data.custom_id == "watch" {
+            self.watch_component(&ctx.http, component).await;
+            return;
+        }
+
+        // Buttons of finished games stay on their messages
+        let Some(original_session) = self.get_current_game(component.user.id).await else {
+            let response = component.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content("This game is over.")
+            )).await;
+
+            if let Err(err) = response {
+                eprintln!("Failed to answer a button of a finished game: {err:?}");
+            }
+
+            return;
+        };
+
+        // We are calling this because we are editing the component
+        // interaction or answering to the original interaction in the progress_game()
+        component.create_response(&ctx.http, CreateInteractionResponse::Acknowledge).await.unwrap();
+
+        let mut session = original_session.lock().await;
+
+        // The game could have been finished by a resignation or a timeout while waiting for the lock
+        if !self.is_active(&original_session).await {
+            return;
+        }
+
+        // Interaction tokens are valid only for 15 minutes, so the game
+        // message is edited by the latest interaction of the player
+        if let Some(seat) = session.seat_of_user(component.user.id) {
+            session.players[seat].1 = Some(PlayerInteraction::Component(component.clone()));
+        }
+
+        let Rules { width, height, .. } = session.position.rules();
+        let area = cursor_area(&session, session.current_input_mode());
+        let [can_left, can_down, can_up, can_right] = area.can_move(session.cursor_pos, width);
+
+        match component.data.custom_id.as_str() {
+            "left" => {
+                if can_left {
+                    session.cursor_pos -= area.step;
+                }
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "down" => {
+                if can_down {
+                    session.cursor_pos += area.step * width
+                }
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "up" => {
+                if can_up {
+                    session.cursor_pos -= area.step * width
+                }
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "right" => {
+                if can_right {
+                    session.cursor_pos += area.step
+                }
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            // Two-level cursor of ultimate tic-tac-toe
+            "enter" => {
+                if !is_selecting_sub_board(&session) || !session.position.active_sub_boards().contains(&ultimate::sub_board_of(session.cursor_pos)) {
+                    return;
+                }
+
+                session.cursor_in_sub_board = true;
+                session.cursor_pos = ultimate::sub_board_center(ultimate::sub_board_of(session.cursor_pos));
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "back" => {
+                session.cursor_in_sub_board = false;
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            custom_id if custom_id.starts_with("layer_") => {
+                let Some(layer) = custom_id.strip_prefix("layer_").and_then(|val| val.parse().ok()).filter(|&val| val < qubic::SIZE) else {
+                    return;
+                };
+
+                session.cursor_pos = qubic::move_to_layer(session.cursor_pos, layer);
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "symbol_x" | "symbol_o" => {
+                if component.user.id != session.current_player_id() || !matches!(session.piece, Piece::Symbol(_)) {
+                    return;
+                }
+
+                session.piece = match component.data.custom_id.as_str() {
+                    "symbol_x" => Piece::Symbol(Player::First),
+                    _ => Piece::Symbol(Player::Second),
+                };
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "suggest" => {
+                let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+                    return;
+                };
+
+                let Some(cell) = values.first().and_then(|val| val.parse::<usize>().ok()) else {
+                    return;
+                };
+
+                let teammate = session.teammate(session.current_seat());
+                if teammate.is_none() || teammate != session.seat_of_user(component.user.id) || !session.position.is_legal(cell) {
+                    return;
+                }
+
+                session.suggestion = Some(cell);
+
+                show_game_message(&ctx.http, &session).await;
+                show_wait_and_common_message(&ctx.http, &mut session).await;
+            }
+
+            "number" => {
+                let GamePosition::Numerical(position) = &session.position else {
+                    return;
+                };
+
+                let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+                    return;
+                };
+
+                let Some(number) = values.first().and_then(|val| val.parse::<u8>().ok()) else {
+                    return;
+                };
+
+                if component.user.id != session.current_player_id() || !position.remaining_numbers(position.turn()).contains(&number) {
+                    return;
+                }
+
+                session.piece = Piece::Number(number);
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "spooky_cancel" => {
+                if component.user.id != session.current_player_id() || !matches!(session.piece, Piece::Spooky(_)) {
+                    return;
+                }
+
+                session.piece = Piece::Mark;
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            custom_id if custom_id.starts_with("collapse_") => {
+                let Some(cell) = custom_id.strip_prefix("collapse_").and_then(|val| val.parse().ok()) else {
+                    return;
+                };
+
+                if component.user.id != session.current_player_id() || !session.is_measuring() {
+                    return;
+                }
+
+                session.piece = Piece::Collapse;
+                self.play_move(&ctx.http, &mut session, &original_session, cell).await;
+            }
+
+            "column" | "row" => {
+                let ComponentInteractionDataKind::StringSelect { values } = &component.data.kind else {
+                    return;
+                };
+
+                let Some(val) = values.first().and_then(|val| val.parse::<usize>().ok()) else {
+                    return;
+                };
+
+                let (column, row) = (session.cursor_pos % width, session.cursor_pos / width);
+
+                session.cursor_pos = match component.data.custom_id.as_str() {
+                    "column" => row * width + val.min(width - 1),
+                    _ => val.min(height - 1) * width + column,
+                };
+
+                update_game_message(&ctx.http, &component, &session).await;
+            }
+
+            "swap2_x" | "swap2_o" | "swap2_two" => {
+                if component.user.id != session.current_player_id() || !session.is_choosing() {
+                    return;
+                }
+
+                self.choose_swap2(&ctx.http, &mut session, component.data.custom_id.as_str()).await;
+            }
+
+            "send" => {
+                if component.user.id != session.current_player_id() {
+                    return;
+                }
+
+                let cursor_pos = session.cursor_pos;
+                self.play_move(&ctx.http, &mut session, &original_session, cursor_pos).await;
+            }
+
+            custom_id => {
+                let Some(cell) = custom_id.strip_prefix("cell_").and_then(|val| val.parse().ok()) else {
+                    return;
+                };
+
+                if component.user.id != session.current_player_id() {
+                    return;
+                }
+
+                self.play_move(&ctx.http, &mut session, &original_session, cell).await;
+            }
+        }
+    }
+
+    // Applies the move of the current player and then moves
+    // of the bot until it is a turn of a human or the game is over
+    async fn play_move(&self, http: &Arc<Http>, session: &mut GameSession, original_session: &Arc<Mutex<GameSession>>, cell: usize) {
+        // The first cell of the spooky mark is kept until the second one is chosen
+        if let GamePosition::Quantum(position) = &session.position {
+            if session.piece == Piece::Mark && position.needs_two_cells() && position.is_legal(cell) {
+                session.piece = Piece::Spooky(cell);
+                show_game_message(http, session).await;
+                return;
+            }
+        }
+
+        let mut cell = cell;
+        let mut piece = session.piece;
+
+        loop {
+            // Marks can not be placed while a mark is being chosen in the swap2 opening
+            if session.is_choosing() {
+                return;
+            }
+
+            let seat = session.current_seat();
+            let text = move_text(&session.position, cell, piece);
+
+            let outcome = match session.position.apply_move(cell, piece) {
+                Ok(val) => val,
+                Err(_) => return, // Unreachable in default situation
+            };
+
+            session.move_log.push(text);
+            self.sprites.draw_move(&mut session.canvas, &session.position, cell);
+
+            session.clock.switch(seat, storage::now());
+            session.warned = false;
+            session.suggestion = None;
+
+            session.swap2 = match (session.swap2, session.position.moves().len()) {
+                (Swap2Stage::PlaceThree, 3) => Swap2Stage::Choose,
+                (Swap2Stage::PlaceTwo, 5) => Swap2Stage::ChooseAgain,
+                (stage, _) => stage,
+            };
+
+            match outcome {
+                Outcome::Ongoing => {
+                    if let (Some(difficulty), 1) = (session.bot, session.current_seat()) {
+                        // The computer always keeps O in the swap2 opening
+                        if session.swap2 == Swap2Stage::Choose {
+                            session.swap2 = Swap2Stage::Done;
+                        }
+
+                        (cell, piece) = choose_bot_move(&session.position, difficulty).await;
+                        continue;
+                    }
+
+                    reset_cursor(session);
+                    session.piece = session.position.default_piece();
+                    self.process_session(http, session).await;
+                }
+
+                // Chaos wins without a line in Order & Chaos
+                Outcome::Draw | Outcome::Blocked { .. } => {
+                    let description = result_description(session, outcome);
+
+                    let result = match outcome.winner() {
+                        Some(mark) => GameResult::won_by(Player::from_index(session.seat_of(mark))),
+                        None => GameResult::Draw,
+                    };
+
+                    self.end_game_with_message(http, session, original_session, description, result, EndReason::BoardFull).await;
+                }
+
+                Outcome::Win { line, .. } | Outcome::Loss { line, .. } => {
+                    self.sprites.draw_end_line(&mut session.canvas, &session.position, line);
+
+                    let winner = session.seat_of(outcome.winner().unwrap());
+                    let description = result_description(session, outcome);
+
+                    let result = GameResult::won_by(Player::from_index(winner));
+
+                    let reason = match outcome {
+                        Outcome::Loss { .. } => EndReason::LosingLine(line),
+                        _ => EndReason::Line(line),
+                    };
+
+                    self.end_game_with_message(http, session, original_session, description, result, reason).await;
+                }
+            }
+
+            return;
+        }
+    }
+
+    // `choice` is the custom id of the pressed button
+    async fn choose_swap2(&self, http: &Http, session: &mut GameSession, choice: &str) {
+        let chooser = session.current_seat();
+
+        let mark = match choice {
+            "swap2_x" => Player::First,
+            "swap2_o" => Player::Second,
+
+            // Only the second player can place two more marks
+            _ if session.swap2 == Swap2Stage::Choose => {
+                session.swap2 = Swap2Stage::PlaceTwo;
+                session.clock.switch(chooser, storage::now());
+                session.warned = false;
+
+                self.process_session(http, session).await;
+                return;
+            }
+            _ => return,
+        };
+
+        session.swapped = (chooser == 1) == (mark == Player::First);
+        session.swap2 = Swap2Stage::Done;
+
+        session.clock.switch(chooser, storage::now());
+        session.warned = false;
+
+        self.process_session(http, session).await;
+    }
+
+    async fn get_current_game(&self, user_id: UserId) -> Option<Arc<Mutex<GameSession>>> {
+        self.sessions.lock().await.iter()
+            .find(|val| val.0.contains(&user_id))
+            .map(|val| Arc::clone(&val.1))
+    }
+
+    // Players of the new game are checked again under the lock, so nobody ends up in two games.
+    // The session is given back when one of them has started another game meanwhile.
+    async fn add_session(&self, session: GameSession) -> Result<Arc<Mutex<GameSession>>, GameSession> {
+        // The computer plays many games at once
+        let bot_id = session.bot.map(|_| session.players[1].0);
+        let user_ids: Vec<UserId> = session.players.iter()
+            .map(|val| val.0)
+            .filter(|&val| Some(val) != bot_id)
+            .collect();
+
+        let mut sessions = self.sessions.lock().await;
+
+        if sessions.iter().any(|val| val.0.iter().any(|user_id| user_ids.contains(user_id))) {
+            return Err(session);
+        }
+
+        let new_game = Arc::new(Mutex::new(session));
+        sessions.push((user_ids, Arc::clone(&new_game)));
+
+        Ok(new_game)
+    }
+
+    // Sessions lock `sessions` while they are locked themselves, so the list is copied before locking them
+    async fn get_game_by_message(&self, message_id: MessageId) -> Option<Arc<Mutex<GameSession>>> {
+        let sessions: Vec<_> = self.sessions.lock().await.iter().map(|val| Arc::clone(&val.1)).collect();
+
+        for session in sessions.iter() {
+            if session.lock().await.players.iter().any(|val| val.3.as_ref().is_some_and(|message| message.id == message_id)) {
+                return Some(Arc::clone(session));
+            }
+        }
+
+        None
+    }
+
+    // Encoding takes seconds for long games, so it runs on a blocking thread
+    async fn generate_replay(&self, record: GameRecord) -> Result<CreateAttachment, MoveError> {
+        let sprites = Arc::clone(&self.sprites);
+
+        tokio::task::spawn_blocking(move || sprites.generate_replay_attachment(&record))
+            .await
+            .expect("replay encoding has panicked")
+    }
+
+    // The game has not been finished yet, called with the lock of the session
+    async fn is_active(&self, session: &Arc<Mutex<GameSession>>) -> bool {
+        self.sessions.lock().await.iter().any(|val| Arc::ptr_eq(&val.1, session))
+    }
+
+    // The `result` is for seats of the session, not for marks
+    async fn end_game_with_message(
+        &self,
+        http: &Arc<Http>,
+        session: &mut GameSession,
+        original_session: &Arc<Mutex<GameSession>>,
+        description: String,
+        result: GameResult,
+        reason: EndReason,
+    ) {
+        if let Some(series) = &mut session.series {
+            series.finish_game(result.winner().map(|val| val.index()));
+        }
+
+        // Records keep players in the order of their marks, the player who plays X is the first
+        let mut seats: Vec<usize> = (0..session.players.len()).collect();
+        seats[..session.position.rules().players].rotate_left(session.swapped as usize);
+
+        let record_result = match result {
+            GameResult::Lost(seat) => GameResult::Lost(session.mark_of(seat.index())),
+            _ => result.winner().map_or(result, |seat| GameResult::won_by(session.mark_of(seat.index()))),
+        };
+
+        let mut players = seats.into_iter().map(|seat| (session.players[seat].0, session.players[seat].2.clone()));
+        let player = players.next().unwrap();
+        let player2 = players.next().unwrap();
+
+        // The host of the game always has an interaction
+        let host = session.players[0].1.as_ref().unwrap();
+
+        let record = GameRecord {
+            guild_id: host.guild_id(),
+            channel_id: host.channel_id(),
+
+            player,
+            player2,
+            others: players.collect(),
+            bot: session.bot.map(|val| val.name().to_string()),
+
+            mode: session.options.mode,
+            rules: session.position.rules(),
+            moves: session.position.moves().to_vec(),
+            symbols: session.position.symbols().to_vec(),
+            numbers: session.position.numbers().to_vec(),
+            result: record_result,
+            reason,
+
+            started_at: session.started_at,
+            ended_at: storage::now(),
+        };
+
+        let id = match self.storage.save_game(&record) {
+            Ok(id) => Some(id),
+            Err(err) => {
+                eprintln!("Failed to save the game: {err:?}");
+                None
+            }
+        };
+
+        let title = session.title(&format!("The game between {} has finished!", session.player_names()));
+
+        let mut embed = CreateEmbed::new()
+            .title(&title)
+            .description(&description)
+            .attachment("canvas.png");
+
+        if let Some(id) = id {
+            embed = embed.footer(CreateEmbedFooter::new(format!("Game #{}, watch it again with /replay {}", id, id)));
+        }
+
+        let button = match &session.series {
+            Some(series) if series.winner().is_none() => CreateButton::new("rematch").label("Next game").style(ButtonStyle::Success),
+            _ => CreateButton::new("rematch").label("Rematch").style(ButtonStyle::Secondary),
+        };
+
+        if let Some(winner) = session.series.as_ref().and_then(|val| val.winner()) {
+            embed = embed.field("Series", format!("🏆 {} has won the series! 🏆", session.name(winner)), false);
+        }
+
+        // The tournament pairs players by itself
+        let components = match session.tournament_match {
+            Some(_) => Vec::new(),
+            None => vec![CreateActionRow::Buttons(vec![button])],
+        };
+
+        // The replay and the buttons are added later, see `add_replay`
+        let message = EditMessage::new()
+            .add_embed(embed.clone())
+            .attachment(generate_attachment_rgb8(&session.canvas, "canvas.png"))
+            .components(Vec::new());
+
+        let final_message = EditMessage::new()
+            .add_embed(embed)
+            .attachment(generate_attachment_rgb8(&session.canvas, "canvas.png"))
+            .components(components);
+
+        // Also called by the timer, which must not stop on expired interactions of long games
+        for interaction in session.players.iter().filter_map(|val| val.1.as_ref()) {
+            if let Err(err) = interaction.delete_response(http).await {
+                eprintln!("Failed to delete the game response: {err:?}");
+            }
+        }
+
+        for val in session.messages_mut() {
+            if let Err(err) = val.edit(http, message.clone()).await {
+                eprintln!("Failed to edit the game message: {err:?}");
+            }
+        }
+
+        let messages = session.messages_mut().map(|val| val.clone()).collect();
+        tokio::spawn(add_replay(Arc::clone(http), Arc::clone(&self.sprites), record, messages, final_message));
+
+        let spectator_message = EditMessage::new()
+            .embed(
+                CreateEmbed::new()
+                    .title(title)
+                    .description(format!("{}\n\n{}", description, move_list(session)))
+                    .attachment("canvas.png")
+            )
+            .attachment(generate_attachment_rgb8(&session.canvas, "canvas.png"));
+
+        if let Some(thread) = session.thread.as_mut() {
+            if let Err(err) = thread.edit(http, spectator_message).await {
+                eprintln!("Failed to edit the spectator message: {err:?}");
+            }
+        }
+
+        {
+            let mut games = self.sessions.lock().await;
+            let pos = games.iter().position(|val| Arc::ptr_eq(&val.1, original_session));
+            games.swap_remove(pos.unwrap());
+        }
+
+        if let Some((tournament_id, index)) = session.tournament_match {
+            let result = match result {
+                GameResult::FirstWon => MatchResult::First,
+                GameResult::SecondWon => MatchResult::Second,
+                _ => MatchResult::Draw,
+            };
+
+            self.finish_tournament_match(http, tournament_id, index, result).await;
+            return;
+        }
+
+        let mut players: Vec<SessionPlayer> = session.players.iter()
+            .map(|val| (val.0, None, val.2.clone(), val.3.clone(), val.4))
+            .collect();
+
+        // The computer always keeps the second seat, so marks are swapped instead of seats,
+        // the swap2 opening chooses marks by itself
+        if players.len() > 2 {
+            players.rotate_left(1);
+        }
+
+        self.pending_games.lock().await.push(PendingGame {
+            players,
+            bot: session.bot,
+            options: session.options,
+
+            // Finished series start again from zero
+            series: session.series.clone().filter(|val| val.winner().is_none()),
+            swapped: session.players.len() == 2 && !session.options.swap2 && !session.swapped,
+            tournament_match: None,
+            expires_at: storage::now() + PENDING_GAME_MINUTES * 60,
+        });
+    }
+}
+
+impl Sprites {
+    fn load() -> Self {
+        Self {
+            x_image: image::open("./resources/x.png").expect("x.png").into_rgb8(),
+            o_image: image::open("./resources/o.png").expect("o.png").into_rgb8(),
+
+            horizontal_scratch: image::open("./resources/1.png").expect("1.png").into_rgba8(),
+            vertical_scratch: image::open("./resources/2.png").expect("2.png").into_rgba8(),
+            diagonal_scratch_1: image::open("./resources/3.png").expect("3.png").into_rgba8(),
+            diagonal_scratch_2: image::open("./resources/4.png").expect("4.png").into_rgba8(),
+        }
+    }
+
+    fn draw_x(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell_index: usize) {
+        let (cell_x, cell_y) = cell_origin(image.width(), cell_index);
+
+        for y in 0..80 {
+            for x in 0..80 {
+                image.draw_pixel(
+                    cell_x + 10 + x,
+                    cell_y + 10 + y,
+                    *self.x_image.get_pixel(x, y),
+                );
+            }
+        }
+    }
+    
+    fn draw_o(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell_index: usize) {
+        let (cell_x, cell_y) = cell_origin(image.width(), cell_index);
+
+        for y in 0..80 {
+            for x in 0..80 {
+                image.draw_pixel(
+                    cell_x + 10 + x,
+                    cell_y + 10 + y,
+                    *self.o_image.get_pixel(x, y),
+                );
+            }
+        } 
+    }
+
+    // Large mark over the whole won sub-board of ultimate tic-tac-toe
+    fn draw_sub_board_winner(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, sub_board: usize, player: Player) {
+        // Only two players play ultimate tic-tac-toe
+        let mark = match player {
+            Player::First => &self.x_image,
+            Player::Second => &self.o_image,
+            _ => unreachable!(),
+        };
+
+        let (x, y) = cell_origin(image.width(), ultimate::sub_board_cells(sub_board)[0]);
+        let size = 3 * CELL_SIZE;
+        let scaled = image::imageops::resize(mark, size - 20, size - 20, FilterType::Triangle);
+
+        // Small marks and grid lines inside of the sub-board are hidden by the large mark
+        draw_filled_rect_mut(image, Rect::at(x as i32 + 4, y as i32 + 4).of_size(size - 8, size - 8), BACKGROUND);
+        image::imageops::overlay(image, &scaled, x as i64 + 10, y as i64 + 10);
+    }
+
+    // Classical marks are large and spooky marks are small, both with numbers of their moves
+    fn draw_quantum_board(&self, position: &QuantumPosition) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let mut canvas = draw_new_game_canvas(Mode::Quantum, QuantumPosition::rules());
+
+        let small_x = image::imageops::resize(&self.x_image, 16, 16, FilterType::Triangle);
+        let small_o = image::imageops::resize(&self.o_image, 16, 16, FilterType::Triangle);
+
+        for cell in 0..position.board().cells().len() {
+            let (x, y) = cell_origin(canvas.width(), cell);
+
+            if let Some(mark) = position.classical_mark(cell) {
+                match quantum::player_of(mark) {
+                    Player::First => self.draw_x(&mut canvas, cell),
+                    _ => self.draw_o(&mut canvas, cell),
+                }
+
+                draw_digit(&mut canvas, (x + 84, y + 78), 4, mark as u8 + 1, player_color(quantum::player_of(mark)));
+                continue;
+            }
+
+            // Spooky marks fill the 3×3 grid of slots in the order of moves
+            for (i, mark) in position.spooky_marks(cell).into_iter().enumerate() {
+                let (x, y) = (x + 4 + (i % 3) as u32 * 31, y + 4 + (i / 3) as u32 * 31);
+
+                let image = match quantum::player_of(mark) {
+                    Player::First => &small_x,
+                    _ => &small_o,
+                };
+
+                image::imageops::overlay(&mut canvas, image, x as i64 + 1, y as i64 + 7);
+                draw_digit(&mut canvas, (x + 19, y + 13), 5, mark as u8 + 1, player_color(quantum::player_of(mark)));
+            }
+        }
+
+        canvas
+    }
+
+    // Animated replay of the stored game, the end line is revealed from its first cell in the last frames
+    fn generate_replay_attachment(&self, record: &GameRecord) -> Result<CreateAttachment, MoveError> {
+        let moves = GamePosition::stored_moves(record.mode, record.rules, &record.moves, &record.symbols, &record.numbers)?;
+
+        let mut position = GamePosition::new(record.mode, record.rules);
+        let mut canvas = draw_new_game_canvas(record.mode, record.rules);
+        let mut encoder = ReplayEncoder::new(canvas.width(), canvas.height());
+
+        encoder.push(&canvas, REPLAY_MOVE_DELAY);
+
+        for (cell, piece) in moves {
+            position.apply_move(cell, piece)?;
+            self.draw_move(&mut canvas, &position, cell);
+
+            encoder.push(&canvas, REPLAY_MOVE_DELAY);
+        }
+
+        if let Some(line) = record.reason.line() {
+            let mut ended = canvas.clone();
+            self.draw_end_line(&mut ended, &position, line);
+
+            let cells = end_line_cells(&position, line);
+
+            for step in 1..=REPLAY_LINE_FRAMES {
+                encoder.push(&reveal_line(&canvas, &ended, &cells, step as f32 / REPLAY_LINE_FRAMES as f32), REPLAY_LINE_DELAY);
+            }
+        }
+
+        Ok(CreateAttachment::bytes(encoder.finish(REPLAY_END_DELAY), "replay.gif"))
+    }
+
+    // The `cell` of the last move of the position
+    fn draw_move(&self, canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, position: &GamePosition, cell: usize) {
+        // Both players place X in Notakto, and any mark in wild variants
+        match (position, position.number(cell), position.board().get(cell)) {
+            // Measurements change many cells, so the whole board is drawn again
+            (GamePosition::Quantum(position), _, _) => *canvas = self.draw_quantum_board(position),
+            (_, Some(number), _) => draw_number(canvas, cell, number),
+            (_, None, GameCell::Second) => self.draw_o(canvas, cell),
+            (_, None, GameCell::Third) => draw_triangle(canvas, cell),
+            (_, None, GameCell::Fourth) => draw_square(canvas, cell),
+            (_, None, _) => self.draw_x(canvas, cell),
+        }
+
+        match position {
+            GamePosition::Ultimate(position) => {
+                let sub_board = ultimate::sub_board_of(cell);
+
+                if let SubBoardState::Won(winner) = position.sub_board(sub_board) {
+                    self.draw_sub_board_winner(canvas, sub_board, winner);
+                }
+            }
+
+            // Moves are made only on alive boards, so the board has been killed by this move
+            GamePosition::Notakto(position) => {
+                if let Some(line) = position.dead_line(position.board_of(cell)) {
+                    draw_win_line(canvas, &line);
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    fn draw_end_line(&self, canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, position: &GamePosition, line: usize) {
+        // Scratches are drawn only for the classic board
+        match position {
+            // Through centers of the won sub-boards
+            GamePosition::Ultimate(_) => {
+                draw_win_line(canvas, &LINES[line].map(ultimate::sub_board_center));
+                return;
+            }
+            // The line has been drawn when the last board was killed
+            GamePosition::Notakto(_) => return,
+
+            // Cells of the line are in different layers, so they are highlighted one by one
+            GamePosition::Qubic(position) => {
+                for cell in position.lines()[line] {
+                    draw_cell_highlight(canvas, cell, RED);
+                }
+
+                return;
+            }
+            _ if !position.rules().is_classic() => {
+                draw_win_line(canvas, &position.rules().line(line));
+                return;
+            }
+            _ => (),
+        }
+
+        let id = line as u32;
+
+        match id {
+            0..=2 => {
+                for y in 100 * id..100 * (id + 1) {
+                    for x in 0..300 {
+                        fill_pixel(canvas, &self.horizontal_scratch, x, y);
+                    }
+                }
+            }
+
+            3..=5 => {
+                for y in 0..300 { 
+                    for x in 100 * (id - 3)..100 * (id - 2) {
+                        fill_pixel(canvas, &self.vertical_scratch, x, y);
+                    }
+                }
+            }
+
+            6 => {
+                for y in 0..300 { 
+                    for x in 0..300 {
+                        fill_pixel(canvas, &self.diagonal_scratch_1, x, y);
+                    }
+                }
+            }
+
+            7 => {
+                for y in 0..300 { 
+                    for x in 0..300 {
+                        fill_pixel(canvas, &self.diagonal_scratch_2, x, y);
+                    }
+                }
+            }
+
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Encoding of the replay takes seconds for long games, so it runs on a blocking thread after locks are released.
+// The rematch button comes with it, so a next game can not start in the messages before they are edited.
+async fn add_replay(http: Arc<Http>, sprites: Arc<Sprites>, record: GameRecord, messages: Vec<Message>, mut message: EditMessage) {
+    let replay = tokio::task::spawn_blocking(move || sprites.generate_replay_attachment(&record)).await;
+
+    match replay {
+        Ok(Ok(replay)) => {
+            message = message
+                .add_embed(CreateEmbed::new().title("Replay").attachment("replay.gif"))
+                .attachment(replay);
+        }
+        Ok(Err(err)) => eprintln!("Failed to replay the game: {err:?}"),
+        Err(err) => eprintln!("Replay encoding has panicked: {err:?}"),
+    }
+
+    for mut val in messages {
+        if let Err(err) = val.edit(&http, message.clone()).await {
+            eprintln!("Failed to add the replay to the game message: {err:?}");
+        }
+    }
+}
+
+// Perfect searches can take a while, so they run on a blocking thread instead of the runtime
+async fn choose_bot_move(position: &GamePosition, difficulty: Difficulty) -> (usize, Piece) {
+    let position = position.clone();
+
+    tokio::task::spawn_blocking(move || position.choose_move(difficulty))
+        .await
+        .expect("move search has panicked")
+}
+
+// The game has not started because one of its players is in another game already
+async fn discard_session(http: &Http, session: GameSession) {
+    for (_, interaction, _, message, _) in session.players {
+        if let Some(interaction) = interaction {
+            if let Err(err) = interaction.delete_response(http).await {
+                eprintln!("Failed to delete the response of the discarded game: {err:?}");
+            }
+        }
+
+        if let Some(message) = message {
+            if let Err(err) = message.delete(http).await {
+                eprintln!("Failed to delete the message of the discarded game: {err:?}");
+            }
+        }
+    }
+}
+
+// The seat of the opponent wins when a player leaves a game of two players or of two teams,
+// in bigger games the player who has left loses to everyone else
+fn forfeit_result(seats: usize, is_team_game: bool, loser: usize) -> GameResult {
+    if seats == 2 || is_team_game {
+        GameResult::won_by(Player::from_index(1 - loser % 2))
+    }
+    else {
+        GameResult::Lost(Player::from_index(loser))
+    }
+}
+
+// Why the game has finished with the `outcome`, by the rules of the mode
+fn result_description(session: &GameSession, outcome: Outcome) -> String {
+    let Some(winner) = outcome.winner() else {
+        return match session.position {
+            GamePosition::Ultimate(_) => "All boards are finished without three in a row, no one wins!".to_string(),
+            _ => "The board is full, no one wins!".to_string(),
+        };
+    };
+
+    // Only two players can lose by their own lines
+    let winner = session.seat_of(winner);
+    let (winner_name, loser_name) = (session.side_name(winner), session.side_name(winner ^ 1));
+
+    match (&session.position, outcome) {
+        (_, Outcome::Blocked { .. }) => format!("💥 The board is full without {} same marks in a row, {} has won as Chaos! 💥", session.position.rules().win_length, winner_name),
+        (GamePosition::Wild(position), _) if position.is_order_and_chaos() => format!("💥 {} same marks are in a row, {} has won as Order! 💥", position.rules().win_length, winner_name),
+        (GamePosition::Quantum(_), _) => format!("💥 {} has got three classical marks in a row! 💥", winner_name),
+        (GamePosition::Numerical(_), _) => format!("💥 {} has completed a line with the sum of {}! 💥", winner_name, numerical::MAGIC_SUM),
+        (GamePosition::Notakto(_), _) => format!("💥 {} has killed the last board, {} has won! 💥", loser_name, winner_name),
+        (_, Outcome::Loss { .. }) => format!("💥 {} has completed a line and lost, {} has won! 💥", loser_name, winner_name),
+        (GamePosition::Ultimate(_), _) => format!("💥 {} has won three boards in a row! 💥", winner_name),
+        (position, _) => format!("💥 {} has won with {} in a row! 💥", winner_name, position.rules().win_length),
+    }
+}
+
+// The live message of spectators, the clocks of the total time are shown as of the last move
+fn spectator_embed(session: &GameSession) -> CreateEmbed {
+    let seat = session.current_seat();
+
+    let mut embed = CreateEmbed::new()
+        .title(session.title(&format!("Game between {}", session.player_names())))
+        .description(move_list(session))
+        .field("Turn", format!("{}, the time runs out <t:{}:R>.", session.name(seat), session.clock.deadline(seat)), false)
+        .attachment("canvas.png")
+        .footer(CreateEmbedFooter::new(format!("Time control: {}", session.clock.control().describe())));
+
+    if let TimeControl::Total { .. } = session.clock.control() {
+        let now = storage::now();
+        let clocks = (0..session.players.len())
+            .map(|val| format!("{}: {}", session.name(val), clock::format_duration(session.clock.remaining(val, seat, now))))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        embed = embed.field("Clocks", clocks, false);
+    }
+
+    embed
+}
+
+// Players of the record are in the order of marks, teammates of X and O are the last ones
+fn replay_embed(record: &GameRecord, id: i64) -> CreateEmbed {
+    let names: Vec<_> = [&record.player, &record.player2].into_iter()
+        .chain(&record.others)
+        .map(|val| val.1.as_str())
+        .collect();
+
+    CreateEmbed::new()
+        .title(format!("Replay of the game between {}", join_names(names.iter().copied())))
+        .description(format!("The {} game has finished <t:{}:R>. {}", game_name(record.mode), record.ended_at, record_result(record, &names)))
+        .attachment("replay.gif")
+        .footer(CreateEmbedFooter::new(format!("Game #{}", id)))
+}
+
+fn import_embed(record: &GameRecord, date: Option<i64>) -> CreateEmbed {
+    let names: Vec<_> = [&record.player, &record.player2].into_iter()
+        .chain(&record.others)
+        .map(|val| val.1.as_str())
+        .collect();
+
+    let played = match date {
+        Some(date) => format!("The {} game played on <t:{}:D>.", game_name(record.mode), date),
+        None => format!("The {} game.", game_name(record.mode)),
+    };
+
+    CreateEmbed::new()
+        .title(format!("Replay of the imported game between {}", join_names(names.iter().copied())))
+        .description(format!("{} {}", played, record_result(record, &names)))
+        .attachment("replay.gif")
+        .footer(CreateEmbedFooter::new("Imported games are not counted in statistics"))
+}
+
+fn record_result(record: &GameRecord, names: &[&str]) -> String {
+    let result = match (record.result.winner(), record.result.loser()) {
+        (Some(mark), _) if names.len() > record.rules.players => format!("The team of {} and {} has won", names[mark.index()], names[mark.index() + 2]),
+        (Some(mark), _) => format!("{} has won", names[mark.index()]),
+        (None, Some(mark)) => format!("Everyone but {} has won", names[mark.index()]),
+        (None, None) => return "No one has won.".to_string(),
+    };
+
+    match record.reason {
+        EndReason::Resignation => format!("{} by a resignation!", result),
+        EndReason::Timeout => format!("{} on time!", result),
+        _ => format!("{}!", result),
+    }
+}
+
+// All players of the imported game are the user who has imported it
+fn imported_record(game: &ParsedGame, interaction: &CommandInteraction) -> GameRecord {
+    let mut players = game.players.iter().map(|name| (interaction.user.id, name.clone()));
+    let time = game.date.unwrap_or_else(storage::now);
+
+    GameRecord {
+        guild_id: interaction.guild_id,
+        channel_id: interaction.channel_id,
+
+        player: players.next().unwrap(),
+        player2: players.next().unwrap(),
+        others: players.collect(),
+        bot: game.bot.clone(),
+
+        mode: game.mode,
+        rules: game.rules,
+        moves: game.moves.clone(),
+        symbols: game.symbols.clone(),
+        numbers: game.numbers.clone(),
+        result: game.result,
+        reason: game.reason,
+
+        started_at: time,
+        ended_at: time,
+    }
+}
+
+// Players are listed by seeds before the start, also in cancelled registrations, then by points
+fn tournament_embed(tournament: &Tournament) -> CreateEmbed {
+    let status = match tournament.status {
+        Status::Registration => "Registration",
+        Status::Running => "In progress",
+        Status::Finished => "Finished",
+        Status::Cancelled => "Cancelled",
+    };
+
+    let players: Vec<_> = match tournament.matches.is_empty() {
+        true => tournament.players.iter()
+            .enumerate()
+            .map(|(seed, val)| format!("**{}.** {}", seed + 1, val.1))
+            .collect(),
+        false => tournament.standings().into_iter()
+            .map(|(seed, points)| format!("**{}.** {} ({} points)", seed + 1, tournament.players[seed].1, points))
+            .collect(),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(&tournament.name)
+        .field("Format", tournament.format.name(), true)
+        .field("Game", game_name(tournament.mode), true)
+        .field("Status", status, true)
+        .field(format!("Players ({} of {})", tournament.players.len(), tournament::MAX_PLAYERS), players.join("\n"), false);
+
+    let ready_matches = tournament.ready_matches();
+
+    if tournament.status == Status::Running && !ready_matches.is_empty() {
+        let mut matches: Vec<_> = ready_matches.iter()
+            .take(MAX_LISTED_MATCHES)
+            .map(|&index| {
+                let names = tournament.slots(index).map(|slot| match slot {
+                    Slot::Player(player) => tournament.players[player].1.as_str(),
+                    _ => "",
+                });
+
+                format!("{} vs {}", names[0], names[1])
+            })
+            .collect();
+
+        if ready_matches.len() > MAX_LISTED_MATCHES {
+            matches.push(format!("and {} more", ready_matches.len() - MAX_LISTED_MATCHES));
+        }
+
+        embed = embed.field("Matches to play", matches.join("\n"), false);
+    }
+
+    embed.footer(CreateEmbedFooter::new("Players are shown by their seeds on the bracket."))
+}
+
+// The public message of the waiting player, or of the lobby in games of more than two players
+fn waiting_embed(host: &CommandInteraction, options: &GameOptions, names: &[&str]) -> CreateEmbed {
+    let name = display_name(host);
+    let icon_url = host.user.avatar_url().unwrap_or_else(|| host.user.default_avatar_url());
+
+    let embed = CreateEmbed::new()
+        .author(
+            CreateEmbedAuthor::new(name.clone())
+                .icon_url(icon_url)
+        )
+        .title(format!("{} wants to play {} game!", name, options.game_name()))
+        .description(format!("You can join to him/her/them by using the `{}` command.", options.play_command()))
+        .field("Board", options.describe_board(), true)
+        .field("Time control", options.time_control.describe(), true);
+
+    if options.seats() == 2 {
+        return embed;
+    }
+
+    embed.field("Lobby", format!("{} ({} of {} players)", join_names(names.iter().copied()), names.len(), options.seats()), false)
+}
+
+// Names of players, the last one after "and"
+fn join_names<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let names: Vec<_> = names.collect();
+
+    match names.split_last() {
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+fn display_name(interaction: &CommandInteraction) -> String {
+    match &interaction.member {
+        Some(val) => val.nick.clone().unwrap_or_else(|| interaction.user.name.clone()),
+        None => interaction.user.name.clone(),
+    }
+}
+
+// Like "4" or "5x4"
+fn parse_size(val: &str) -> Option<(usize, usize)> {
+    let val = val.trim().to_lowercase();
+
+    match val.split_once(['x', '×']) {
+        Some((width, height)) => Some((width.trim().parse().ok()?, height.trim().parse().ok()?)),
+        None => {
+            let size = val.parse().ok()?;
+            Some((size, size))
+        }
+    }
+}
+
+// Boards which can be created by /play, imported games are limited to them too.
+// Marks, misère and players are checked separately, with their own errors.
+pub fn is_playable_board(mode: Mode, rules: Rules) -> bool {
+    match mode {
+        Mode::Classic | Mode::Wild => {
+            let sizes = 3..=MAX_BOARD_SIZE;
+
+            sizes.contains(&rules.width) && sizes.contains(&rules.height) && rules.win_length >= 3 && !rules.exact
+        }
+        Mode::Gomoku => rules == Rules::gomoku(rules.exact),
+        Mode::Ultimate => rules == UltimatePosition::rules(),
+        Mode::Qubic => rules == QubicPosition::rules(),
+        Mode::Notakto => (1..=notakto::MAX_BOARDS).any(|boards| rules == NotaktoPosition::rules(boards)),
+        Mode::OrderChaos => rules == WildPosition::order_and_chaos_rules(),
+        Mode::Numerical => rules == NumericalPosition::rules(),
+        Mode::Quantum => rules == QuantumPosition::rules(),
+    }
+}
+
+fn game_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Classic => "tic-tac-toe",
+        Mode::Gomoku => "gomoku",
+        Mode::Ultimate => "ultimate tic-tac-toe",
+        Mode::Qubic => "3D tic-tac-toe",
+        Mode::Notakto => "Notakto",
+        Mode::Wild => "wild tic-tac-toe",
+        Mode::OrderChaos => "Order & Chaos",
+        Mode::Numerical => "numerical tic-tac-toe",
+        Mode::Quantum => "quantum tic-tac-toe",
+    }
+}
+
+// Cursor stepping is too slow on the big board of gomoku
+fn default_input_mode(mode: Mode) -> InputMode {
+    match mode {
+        Mode::Gomoku => InputMode::Coordinates,
+        _ => InputMode::Arrows,
+    }
+}
+
+// Boards of games of three and four players are bigger
+fn default_size(players: usize) -> (usize, usize) {
+    match players {
+        2 => (3, 3),
+        3 => (5, 5),
+        _ => (6, 6),
+    }
+}
+
+// Lines through the whole board are too hard to complete by one of many players
+fn default_win_length(width: usize, height: usize, players: usize) -> usize {
+    match players {
+        2 => width.min(height),
+        _ => MULTI_PLAYER_WIN_LENGTH.min(width.min(height)),
+    }
+}
+
+fn center_cell(rules: Rules) -> usize {
+    rules.height / 2 * rules.width + rules.width / 2
+}
+
+// The center is hard to find on zoomed boards, so the cursor starts at the last move
+fn initial_cursor(position: &GamePosition) -> usize {
+    match (position, position.moves().last()) {
+        (_, Some(&cell)) if is_zoomed(position) => cell,
+
+        // The same layer as the last move, or the first layer
+        (GamePosition::Qubic(_), Some(&cell)) => qubic::move_to_layer(QUBIC_START_CELL, qubic::layer_of(cell)),
+        (GamePosition::Qubic(_), None) => QUBIC_START_CELL,
+
+        _ => center_cell(position.rules()),
+    }
+}
+
+// Called before every turn. In ultimate tic-tac-toe the cursor selects a sub-board
+// first, unless there is only one sub-board where the move can be made.
+fn reset_cursor(session: &mut GameSession) {
+    session.cursor_pos = initial_cursor(&session.position);
+    session.cursor_in_sub_board = false;
+
+    if let GamePosition::Ultimate(position) = &session.position {
+        let active = position.active_sub_boards();
+
+        if let [sub_board] = active[..] {
+            session.cursor_pos = ultimate::sub_board_center(sub_board);
+            session.cursor_in_sub_board = true;
+        }
+        else if !active.contains(&ultimate::sub_board_of(session.cursor_pos)) {
+            session.cursor_pos = ultimate::sub_board_center(active[0]);
+        }
+    }
+}
+
+fn is_selecting_sub_board(session: &GameSession) -> bool {
+    matches!(session.position, GamePosition::Ultimate(_))
+        && session.current_input_mode() == InputMode::Arrows
+        && !session.cursor_in_sub_board
+}
+
+// Cells where the cursor can be and the length of its steps
+struct CursorArea {
+    step: usize,
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+impl CursorArea {
+    // Left, down, up and right
+    fn can_move(&self, cursor_position: usize, width: usize) -> [bool; 4] {
+        let (x, y) = (cursor_position % width, cursor_position / width);
+
+        [
+            x >= self.left + self.step,
+            y + self.step <= self.bottom,
+            y >= self.top + self.step,
+            x + self.step <= self.right,
+        ]
+    }
+}
+
+fn cursor_area(session: &GameSession, input_mode: InputMode) -> CursorArea {
+    let rules = session.position.rules();
+
+    let board = CursorArea {
+        step: 1,
+        left: 0,
+        top: 0,
+        right: rules.width - 1,
+        bottom: rules.height - 1,
+    };
+
+    match session.position {
+        // Arrows move between sub-boards or inside of the selected one
+        GamePosition::Ultimate(_) if input_mode == InputMode::Arrows => {
+            if session.cursor_in_sub_board {
+                let first = ultimate::sub_board_cells(ultimate::sub_board_of(session.cursor_pos))[0];
+                let (x, y) = (first % rules.width, first / rules.width);
+
+                CursorArea { step: 1, left: x, top: y, right: x + 2, bottom: y + 2 }
+            }
+            else {
+                CursorArea { step: 3, ..board }
+            }
+        }
+        // Arrows move inside of the layer
+        GamePosition::Qubic(_) => {
+            let top = qubic::layer_of(session.cursor_pos) * qubic::SIZE;
+
+            CursorArea { top, bottom: top + qubic::SIZE - 1, ..board }
+        }
+        _ => board,
+    }
+}
+
+// Layers of the cube are always shown together
+fn is_zoomed(position: &GamePosition) -> bool {
+    let rules = position.rules();
+
+    !matches!(position, GamePosition::Qubic(_)) && (rules.width > ZOOM_CELLS || rules.height > ZOOM_CELLS)
+}
+
+// Top left corner of the cell on the canvas
+fn cell_origin(canvas_width: u32, cell: usize) -> (u32, u32) {
+    let width = (canvas_width / CELL_SIZE) as usize;
+
+    ((cell % width) as u32 * CELL_SIZE, (cell / width) as u32 * CELL_SIZE)
+}
+
+fn draw_new_game_canvas(mode: Mode, rules: Rules) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let width = rules.width as u32 * CELL_SIZE;
+    let height = rules.height as u32 * CELL_SIZE;
+
+    let mut canvas = ImageBuffer::new(width, height);
+
+    // Background
+    draw_filled_rect_mut(
+        &mut canvas,
+        Rect::at(0, 0).of_size(width, height),
+        BACKGROUND,
+    );
+
+    for column in 1..rules.width as i32 {
+        draw_filled_rect_mut(
+            &mut canvas,
+            Rect::at(column * CELL_SIZE as i32 - 2, 0).of_size(4, height),
+            GRAY,
+        );
+    }
+
+    for row in 1..rules.height as i32 {
+        draw_filled_rect_mut(
+            &mut canvas,
+            Rect::at(0, row * CELL_SIZE as i32 - 2).of_size(width, 4),
+            GRAY,
+        );
+    }
+
+    // Thick lines between boards of Notakto
+    if mode == Mode::Notakto {
+        for board in 1..rules.width as i32 / 3 {
+            let offset = board * 3 * CELL_SIZE as i32;
+
+            draw_filled_rect_mut(&mut canvas, Rect::at(offset - 8, 0).of_size(16, height), GRAY);
+        }
+    }
+
+    // Thick lines between layers of the cube
+    if mode == Mode::Qubic {
+        for layer in 1..qubic::SIZE as i32 {
+            let offset = layer * qubic::SIZE as i32 * CELL_SIZE as i32;
+
+            draw_filled_rect_mut(&mut canvas, Rect::at(0, offset - 8).of_size(width, 16), GRAY);
+        }
+    }
+
+    // Thick lines between sub-boards
+    if mode == Mode::Ultimate {
+        for i in 1..3 {
+            let offset = i * 3 * CELL_SIZE as i32;
+
+            draw_filled_rect_mut(&mut canvas, Rect::at(offset - 4, 0).of_size(8, height), GRAY);
+            draw_filled_rect_mut(&mut canvas, Rect::at(0, offset - 4).of_size(width, 8), GRAY);
+        }
+    }
+
+    canvas
+}
+
+// The canvas with things which are changing every turn,
+// like sub-boards of ultimate tic-tac-toe where the move can be made
+fn render_board(session: &GameSession) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let mut canvas = session.canvas.clone();
+
+    if let GamePosition::Ultimate(position) = &session.position {
+        for sub_board in position.active_sub_boards() {
+            draw_sub_board_outline(&mut canvas, sub_board, GREEN);
+        }
+    }
+
+    // Cells of the mark which has closed the cycle, or the first cell of the spooky mark
+    if let GamePosition::Quantum(position) = &session.position {
+        let cells = match (position.cycle(), session.piece) {
+            (Some(mark), _) => position.marks()[mark].to_vec(),
+            (None, Piece::Spooky(cell)) => vec![cell],
+            _ => Vec::new(),
+        };
+
+        for cell in cells {
+            draw_cell_highlight(&mut canvas, cell, GREEN);
+        }
+    }
+
+    canvas
+}
+
+// Cells from the start to the end of the line drawn by `draw_end_line`, sub-board centers in ultimate tic-tac-toe
+fn end_line_cells(position: &GamePosition, line: usize) -> Vec<usize> {
+    match position {
+        GamePosition::Ultimate(_) => LINES[line].map(ultimate::sub_board_center).to_vec(),
+        GamePosition::Qubic(position) => position.lines()[line].to_vec(),
+        _ if position.rules().is_classic() => LINES[line].to_vec(),
+        _ => position.rules().line(line),
+    }
+}
+
+// Frames of replays are scaled cell by cell, and only the cells changed since the previous frame
+// are scaled and encoded again, so long games on big boards are encoded quickly
+struct ReplayEncoder {
+    encoder: gif::Encoder<Vec<u8>>,
+    cell_size: u32, // Scaled size of cells
+    previous: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>, // The last pushed canvas, in the full size
+    scaled: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    last_frame: Option<gif::Frame<'static>>, // Written with the next one, so the delay of the last frame can be changed
+}
+
+impl ReplayEncoder {
+    fn new(width: u32, height: u32) -> Self {
+        let cell_size = (MAX_REPLAY_SIZE / (width.max(height) / CELL_SIZE)).min(CELL_SIZE);
+        let (width, height) = (width / CELL_SIZE * cell_size, height / CELL_SIZE * cell_size);
+
+        let mut encoder = gif::Encoder::new(Vec::new(), width as u16, height as u16, &[]).expect("failed to write in buffer");
+        encoder.set_repeat(gif::Repeat::Infinite).expect("failed to write in buffer");
+
+        Self {
+            encoder,
+            cell_size,
+            previous: None,
+            scaled: ImageBuffer::new(width, height),
+            last_frame: None,
+        }
+    }
+
+    // The `delay` is in milliseconds
+    fn push(&mut self, canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, delay: u32) {
+        let size = self.cell_size;
+        let mut changed: Option<(u32, u32, u32, u32)> = None; // The first and the last columns and rows
+
+        for row in 0..canvas.height() / CELL_SIZE {
+            for column in 0..canvas.width() / CELL_SIZE {
+                if self.previous.as_ref().is_some_and(|previous| !is_cell_changed(previous, canvas, column, row)) {
+                    continue;
+                }
+
+                let cell = image::imageops::crop_imm(canvas, column * CELL_SIZE, row * CELL_SIZE, CELL_SIZE, CELL_SIZE).to_image();
+                let cell = match size {
+                    CELL_SIZE => cell,
+                    _ => image::imageops::resize(&cell, size, size, FilterType::Triangle),
+                };
+
+                image::imageops::replace(&mut self.scaled, &cell, (column * size) as i64, (row * size) as i64);
+
+                changed = Some(match changed {
+                    Some((left, top, right, bottom)) => (left.min(column), top.min(row), right.max(column), bottom.max(row)),
+                    None => (column, row, column, row),
+                });
+            }
+        }
+
+        self.previous = Some(canvas.clone());
+
+        // The same picture is shown longer
+        let Some((left, top, right, bottom)) = changed else {
+            if let Some(frame) = self.last_frame.as_mut() {
+                frame.delay += (delay / 10) as u16;
+            }
+
+            return;
+        };
+
+        let (x, y) = (left * size, top * size);
+        let (width, height) = ((right - left + 1) * size, (bottom - top + 1) * size);
+        let region = image::imageops::crop_imm(&self.scaled, x, y, width, height).to_image();
+
+        let mut frame = gif::Frame::from_rgb_speed(width as u16, height as u16, region.as_raw(), REPLAY_ENCODING_SPEED);
+        frame.left = x as u16;
+        frame.top = y as u16;
+        frame.delay = (delay / 10) as u16; // In hundredths of a second
+
+        if let Some(last_frame) = self.last_frame.replace(frame) {
+            self.encoder.write_frame(&last_frame).expect("failed to write in buffer");
+        }
+    }
+
+    fn finish(mut self, last_delay: u32) -> Vec<u8> {
+        if let Some(mut last_frame) = self.last_frame.take() {
+            last_frame.delay = (last_delay / 10) as u16;
+            self.encoder.write_frame(&last_frame).expect("failed to write in buffer");
+        }
+
+        self.encoder.into_inner().expect("failed to write in buffer")
+    }
+}
+
+fn is_cell_changed(previous: &ImageBuffer<Rgb<u8>, Vec<u8>>, canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, column: u32, row: u32) -> bool {
+    let stride = canvas.width() as usize * 3;
+    let (x, y) = ((column * CELL_SIZE) as usize * 3, (row * CELL_SIZE) as usize);
+    let length = CELL_SIZE as usize * 3;
+
+    (y..y + CELL_SIZE as usize).any(|y| {
+        let start = y * stride + x;
+        previous.as_raw()[start..start + length] != canvas.as_raw()[start..start + length]
+    })
+}
+
+// Pixels of the end line up to the `progress` from its first cell to its last one
+fn reveal_line(
+    canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    ended: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    cells: &[usize],
+    progress: f32,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let center = |cell: usize| {
+        let (x, y) = cell_origin(canvas.width(), cell);
+        ((x + CELL_SIZE / 2) as f32, (y + CELL_SIZE / 2) as f32)
+    };
+
+    let ((start_x, start_y), (end_x, end_y)) = match cells {
+        [first, .., last] => (center(*first), center(*last)),
+        _ => return ended.clone(),
+    };
+
+    let (dx, dy) = (end_x - start_x, end_y - start_y);
+    let length = dx * dx + dy * dy;
+
+    let mut frame = canvas.clone();
+
+    for (x, y, pixel) in frame.enumerate_pixels_mut() {
+        let t = ((x as f32 - start_x) * dx + (y as f32 - start_y) * dy) / length;
+
+        if t <= progress {
+            *pixel = *ended.get_pixel(x, y);
+        }
+    }
+
+    frame
+}
+
+// Line from the center of the first cell to the center of the last one
+fn draw_win_line(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, line: &[usize]) {
+    let center = |cell| {
+        let (x, y) = cell_origin(canvas.width(), cell);
+        ((x + CELL_SIZE / 2) as f32, (y + CELL_SIZE / 2) as f32)
+    };
+
+    let (start_x, start_y) = center(line[0]);
+    let (end_x, end_y) = center(line[line.len() - 1]);
+
+    let steps = (end_x - start_x).abs().max((end_y - start_y).abs()) as i32;
+
+    for i in 0..=steps {
+        let t = i as f32 / steps as f32;
+        let x = start_x + (end_x - start_x) * t;
+        let y = start_y + (end_y - start_y) * t;
+
+        draw_filled_circle_mut(canvas, (x as i32, y as i32), 5, RED);
+    }
+}
+
+async fn show_wait_and_common_message(http: &Http, session: &mut GameSession) {
+    let attachment = generate_attachment_rgb8(&render_board(session), "canvas.png");
+
+    for waiting in (0..session.players.len()).filter(|&val| val != session.current_seat()) {
+        // The bot does not need any messages
+        let (_, Some(interaction), _, _, input_mode) = &session.players[waiting] else {
+            continue;
+        };
+
+        // The teammate of the current player can suggest a move
+        let suggesting = session.teammate(session.current_seat()) == Some(waiting);
+
+        let mut embed = CreateEmbed::new()
+            .title(session.title("Game in process"))
+            .description(format!("Waiting for the turn of {}.", session.name(session.current_seat())))
+            .thumbnail("attachment://thumbnail.png");
+
+        if suggesting {
+            let suggestion = session.suggestion.map_or("None yet, choose a cell below.".to_string(), |cell| cell_coordinates(session.position.rules(), cell));
+
+            embed = embed.field("Your suggestion", suggestion, false);
+        }
+
+        // Only the total time is kept between the moves
+        if let TimeControl::Total { .. } = session.clock.control() {
+            let remaining = session.clock.remaining(waiting, session.current_seat(), storage::now());
+
+            embed = embed.field("Your time", clock::format_duration(remaining), false);
+        }
+
+        let rules = session.position.rules();
+        let last_move = session.position.moves().last().copied().unwrap_or_else(|| center_cell(rules));
+
+        let mut components = vec![generate_disabled_action_row()];
+        if suggesting {
+            components.push(generate_suggestion_action_row(session));
+        }
+
+        let response = match input_mode {
+            // The board is visible on the buttons, so the image is not needed
+            InputMode::Buttons if !suggesting => EditInteractionResponse::new()
+                .add_embed(embed)
+                .components(generate_cell_action_rows(&session.position, true)),
+
+            _ if is_zoomed(&session.position) => {
+                let (image, view) = zoom(&session.canvas, rules, last_move);
+
+                EditInteractionResponse::new()
+                    .add_embed(embed.field("View", view, false))
+                    .components(components)
+                    .new_attachment(generate_attachment_rgb8(&image, "canvas.png"))
+            }
+
+            _ => EditInteractionResponse::new()
+                .add_embed(embed)
+                .components(components)
+                .new_attachment(attachment.clone()),
+        };
+
+        if let Err(err) = interaction.edit_response(http, response).await {
+            eprintln!("Failed to edit the waiting message: {err:?}");
+        }
+    }
+
+    let edited_message = EditMessage::new()
+        .embed(CreateEmbed::new()
+            .title(session.title(&format!("Game between {} in the progress!", session.player_names())))
+            .description("You can play this game too by using the `/play` command, or watch it in a thread with the move list.")
+            .attachment("canvas.png")
+        )
+        .attachment(attachment.clone())
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("watch").label("Watch").emoji('👀').style(ButtonStyle::Secondary)
+        ])]);
+
+    for val in session.messages_mut() {
+        if let Err(err) = val.edit(http, edited_message.clone()).await {
+            eprintln!("Failed to edit the game message: {err:?}");
+        }
+    }
+
+    let embed = spectator_embed(session);
+
+    if let Some(message) = session.thread.as_mut() {
+        if let Err(err) = message.edit(http, EditMessage::new().embed(embed).attachment(attachment)).await {
+            eprintln!("Failed to edit the spectator message: {err:?}");
+        }
+    }
+}
+
+async fn show_game_message(http: &Http, session: &GameSession) {
+    // The bot does not need any messages
+    let (Some(interaction), input_mode) = (session.current_interaction(), session.current_input_mode()) else {
+        return;
+    };
+
+    let response = if session.is_choosing() {
+        swap2_choice_response(session)
+    }
+    else if session.is_measuring() {
+        collapse_choice_response(session)
+    }
+    else if input_mode == InputMode::Buttons {
+        buttons_response(session)
+    }
+    else {
+        cursor_response(session, input_mode)
+    };
+
+    if let Err(err) = interaction.edit_response(http, response).await {
+        eprintln!("Failed to edit the game message: {err:?}");
+    }
+}
+
+async fn update_game_message(http: &Http, interaction: &ComponentInteraction, session: &GameSession) {
+    let response = match session.current_input_mode() {
+        InputMode::Buttons => buttons_response(session),
+        input_mode => cursor_response(session, input_mode),
+    };
+
+    if let Err(err) = interaction.edit_response(http, response).await {
+        eprintln!("Failed to edit the game message: {err:?}");
+    }
+}
+
+// The game message for the buttons input mode
+fn buttons_response(session: &GameSession) -> EditInteractionResponse {
+    let embed = your_turn_embed(session, "Press a button of an empty cell for making a move.");
+    let mut components = generate_cell_action_rows(&session.position, false);
+
+    components.extend(generate_piece_action_row(session));
+
+    EditInteractionResponse::new()
+        .embed(embed)
+        .components(components)
+}
+
+// The game message for the arrows and coordinates input modes
+fn cursor_response(session: &GameSession, input_mode: InputMode) -> EditInteractionResponse {
+    let rules = session.position.rules();
+    let cursor_pos = session.cursor_pos;
+
+    let is_selecting_sub_board = is_selecting_sub_board(session);
+    let sub_board = ultimate::sub_board_of(cursor_pos);
+
+    let description = match input_mode {
+        InputMode::Coordinates => "Select a column and a row or press arrows buttons for moving selection square.",
+        _ if matches!(session.position, GamePosition::Qubic(_)) => "Press layer buttons for selecting a layer and arrows buttons for moving selection square.",
+        _ if is_selecting_sub_board => "Press arrows buttons for selecting a highlighted board, then press the select button.",
+        _ if matches!(session.piece, Piece::Spooky(_)) => "Press arrows buttons for moving selection square to the second cell of your spooky mark.",
+        _ => "Press arrows buttons for moving selection square.",
+    };
+
+    let mut embed = your_turn_embed(session, description);
+    let mut components = Vec::new();
+
+    if input_mode == InputMode::Coordinates {
+        components.extend(generate_coordinate_select_menus(rules, cursor_pos));
+    }
+
+    let can_move = cursor_area(session, input_mode).can_move(cursor_pos, rules.width);
+
+    if is_selecting_sub_board {
+        let is_active = session.position.active_sub_boards().contains(&sub_board);
+
+        components.push(generate_game_action_row(CreateButton::new("enter").label("Select"), !is_active, can_move));
+    }
+    else {
+        // Both cells of the spooky mark must be different
+        let send_disabled = !session.position.is_legal(cursor_pos) || session.piece == Piece::Spooky(cursor_pos);
+
+        components.push(generate_game_action_row(CreateButton::new("send").label("Send"), send_disabled, can_move));
+    }
+
+    if let GamePosition::Qubic(_) = session.position {
+        components.push(generate_layer_action_row(qubic::layer_of(cursor_pos)));
+    }
+
+    components.extend(generate_piece_action_row(session));
+
+    // Going back is useless if the move can be made only in one sub-board
+    if session.cursor_in_sub_board && input_mode == InputMode::Arrows && session.position.active_sub_boards().len() > 1 {
+        components.push(CreateActionRow::Buttons(vec![
+            CreateButton::new("back")
+                .label("Back to boards")
+                .style(ButtonStyle::Secondary)
+        ]));
+    }
+
+    let mut cloned = render_board(session);
+
+    if is_selecting_sub_board {
+        draw_sub_board_outline(&mut cloned, sub_board, RED);
+    }
+    else {
+        draw_select_outline(&mut cloned, cursor_pos);
+    }
+
+    if input_mode == InputMode::Coordinates || is_zoomed(&session.position) {
+        embed = embed.field("Selected cell", cell_coordinates(rules, cursor_pos), true);
+    }
+
+    if is_zoomed(&session.position) {
+        let (image, view) = zoom(&cloned, rules, cursor_pos);
+
+        cloned = image;
+        embed = embed.field("View", view, true);
+    }
+
+    EditInteractionResponse::new()
+        .embed(embed)
+        .components(components)
+        .new_attachment(generate_attachment_rgb8(&cloned, "canvas.png"))
+}
+
+fn swap2_choice_response(session: &GameSession) -> EditInteractionResponse {
+    let rules = session.position.rules();
+    let last_move = *session.position.moves().last().unwrap();
+
+    let description = match session.swap2 {
+        Swap2Stage::Choose => "Choose which mark you will play, or place one O and one X and let your opponent choose.",
+        _ => "Choose which mark you will play.",
+    };
+
+    let mut embed = your_turn_embed(session, description);
+    let mut image = session.canvas.clone();
+
+    if is_zoomed(&session.position) {
+        let (zoomed, view) = zoom(&session.canvas, rules, last_move);
+
+        image = zoomed;
+        embed = embed.field("View", view, true);
+    }
+
+    let mut buttons = vec![
+        CreateButton::new("swap2_x")
+            .label("Play X")
+            .style(ButtonStyle::Danger),
+        CreateButton::new("swap2_o")
+            .label("Play O")
+            .style(ButtonStyle::Primary),
+    ];
+
+    if session.swap2 == Swap2Stage::Choose {
+        buttons.push(
+            CreateButton::new("swap2_two")
+                .label("Place two more marks")
+                .style(ButtonStyle::Secondary)
+        );
+    }
+
+    EditInteractionResponse::new()
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(buttons)])
+        .new_attachment(generate_attachment_rgb8(&image, "canvas.png"))
+}
+
+// Quantum tic-tac-toe, the player chooses which cell of the mark which has closed the cycle becomes classical
+fn collapse_choice_response(session: &GameSession) -> EditInteractionResponse {
+    let GamePosition::Quantum(position) = &session.position else {
+        unreachable!();
+    };
+
+    let mark = position.cycle().unwrap();
+    let embed = your_turn_embed(session, "Your opponent has closed a cycle of spooky marks. Choose the cell where the last mark becomes classical, other marks of the cycle will follow.");
+
+    let buttons = position.marks()[mark]
+        .iter()
+        .map(|&cell| {
+            CreateButton::new(format!("collapse_{cell}"))
+                .label(format!("Measure into {}", cell_coordinates(position.board().rules(), cell)))
+                .style(ButtonStyle::Primary)
+        })
+        .collect();
+
+    EditInteractionResponse::new()
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(buttons)])
+        .new_attachment(generate_attachment_rgb8(&render_board(session), "canvas.png"))
+}
 
-                let attachment = self.generate_end_attachment(&mut session, id).await;
-
-                match win_player {
-                    GameCell::First => {
-                        let message = EditMessage::new()
-                            .add_embed(CreateEmbed::new()
-                                .title(
-                                    format!(
-                                        "The game between {} and {} has finished!",
-                                        session.player.2,
-                                        session.player2.2,
-                                    )
-                                )
-                                .description(format!("💥 {} has won! 💥", session.player.2))
-                                .attachment("canvas.png")
-                            )
-                            .attachment(attachment);
-
-                        self.end_game_with_message(&ctx.http, &mut session, &original_session, message).await;
-                    },
-                    GameCell::Second => {
-                        let message = EditMessage::new()
-                            .add_embed(CreateEmbed::new()
-                                .title(
-                                    format!(
-                                        "The game between {} and {} has finished!",
-                                        session.player.2,
-                                        session.player2.2,
-                                    )
-                                )
-                                .description(format!("💥 {} has won! 💥", session.player.2))
-                                .attachment("canvas.png")
-                            )
-                            .attachment(attachment);
+fn your_turn_embed(session: &GameSession, description: &str) -> CreateEmbed {
+    let deadline = session.clock.deadline(session.current_seat());
 
-                        self.end_game_with_message(&ctx.http, &mut session, &original_session, message).await;
-                    },
-                    GameCell::None => unreachable!(),
-                }
-            }
-            _ => unreachable!(),
-        }
-    }
+    let mut embed = CreateEmbed::new()
+        .title(session.title("Your turn"))
+        .description(description)
+        .field("Time left", format!("Your time runs out <t:{}:R>.", deadline), false)
+        .footer(CreateEmbedFooter::new(format!("Time control: {}", session.clock.control().describe())));
 
-    async fn get_current_game(&self, message_component: &ComponentInteraction) -> Option<Arc<Mutex<GameSession>>> {
-        let sessions = self.sessions.lock().await;
+    let placed = session.position.moves().len();
 
-        let mut has_game = None;
-        for session in sessions.iter() {
-            let session_lock = session.lock().await;
-            if session_lock.player.0 == message_component.user.id || 
-                session_lock.player2.0 == message_component.user.id
-            {
-                has_game = Some(Arc::clone(session));
-            }
-        }
+    let opening = match session.swap2 {
+        Swap2Stage::PlaceThree => Some(format!("Place X, O and X, this is the mark {} of 3.", placed + 1)),
+        Swap2Stage::PlaceTwo => Some(format!("Place O and X, this is the mark {} of 2.", placed - 2)),
+        _ => None,
+    };
 
-        has_game
-    }
-    
-    fn draw_x(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell_index: usize) {
-        for y in 0..80 {
-            for x in 0..80 {
-                image.draw_pixel(
-                    CELLS[cell_index].0 as u32 + 10 + x,
-                    CELLS[cell_index].1 as u32 + 10 + y,
-                    *self.x_image.get_pixel(x, y),
-                );
-            }
-        }
-    }
-    
-    fn draw_o(&self, image: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell_index: usize) {
-        for y in 0..80 {
-            for x in 0..80 {
-                image.draw_pixel(
-                    CELLS[cell_index].0 as u32 + 10 + x,
-                    CELLS[cell_index].1 as u32 + 10 + y,
-                    *self.o_image.get_pixel(x, y),
-                );
-            }
-        } 
+    if let Some(opening) = opening {
+        embed = embed.field("Swap2 opening", opening, false);
     }
 
-    async fn generate_end_attachment(&self, session: &mut GameSession, id: u32) -> CreateAttachment {        
-        match id {
-            0..=2 => {
-                for y in 100 * id..100 * (id + 1) {
-                    for x in 0..300 {
-                        fill_pixel(&mut session.canvas, &self.horizontal_scratch, x, y);
-                    }
-                }
-            }
-
-            3..=5 => {
-                for y in 0..300 { 
-                    for x in 100 * (id - 3)..100 * (id - 2) {
-                        fill_pixel(&mut session.canvas, &self.vertical_scratch, x, y);
-                    }
-                }
-            }
+    let goal = match &session.position {
+        GamePosition::Wild(position) if position.is_order_and_chaos() => match position.turn() {
+            Player::First => Some("You are Order, make five same marks in a row. Choose X or O by the buttons below."),
+            _ => Some("You are Chaos, fill the board without five same marks in a row. Choose X or O by the buttons below."),
+        },
+        GamePosition::Wild(_) => Some("Complete a line of X or of O. Choose the mark by the buttons below."),
+        GamePosition::Numerical(position) => match position.turn() {
+            Player::First => Some("Complete a line with the sum of 15 by your odd numbers or by numbers of your opponent. Choose the number below."),
+            _ => Some("Complete a line with the sum of 15 by your even numbers or by numbers of your opponent. Choose the number below."),
+        },
+        GamePosition::Quantum(position) if position.cycle().is_some() => None,
+        GamePosition::Quantum(position) if !position.needs_two_cells() => Some("Only one cell is free, your mark in it is classical."),
+        GamePosition::Quantum(_) => Some("Place a spooky mark in two cells. A cycle of spooky marks is measured by the opponent, only classical marks make lines."),
+        _ => None,
+    };
 
-            6 => {
-                for y in 0..300 { 
-                    for x in 0..300 {
-                        fill_pixel(&mut session.canvas, &self.diagonal_scratch_1, x, y);
-                    }
-                }
-            }
+    if let Some(goal) = goal {
+        embed = embed.field("Goal", goal, false);
+    }
 
-            7 => {
-                for y in 0..300 { 
-                    for x in 0..300 {
-                        fill_pixel(&mut session.canvas, &self.diagonal_scratch_2, x, y);
-                    }
-                }
-            }
+    if let (Some(cell), Some(teammate)) = (session.suggestion, session.teammate(session.current_seat())) {
+        embed = embed.field("Suggestion", format!("{} suggests {}.", session.name(teammate), cell_coordinates(session.position.rules(), cell)), false);
+    }
 
-            _ => unreachable!(),
-        }
+    // Marks of everyone are not obvious in bigger games
+    if session.players.len() > 2 {
+        let players = (0..session.players.len())
+            .map(|seat| format!("{} {}", mark_symbol(session.mark_of(seat)), session.name(seat)))
+            .collect::<Vec<_>>()
+            .join("\n");
 
-        generate_attachment_rgb8(&session.canvas, "canvas.png")
+        embed = embed.field("Players", players, false);
     }
 
-    async fn end_game_with_message(&self, http: &Http, session: &mut GameSession, original_session: &Arc<Mutex<GameSession>>, message: EditMessage) {
-        session.player.1.delete_response(http).await.unwrap();
-        session.player2.1.delete_response(http).await.unwrap();
+    embed
+}
 
-        if let Some(val) = &mut session.player2.3 {
-            val.edit(http, message.clone()).await.unwrap();
-        }
+fn mark_symbol(player: Player) -> &'static str {
+    match player {
+        Player::First => "X",
+        Player::Second => "O",
+        Player::Third => "Δ",
+        Player::Fourth => "□",
+    }
+}
 
-        session.player.3.edit(http, message).await.unwrap();
-        
-        let mut games = self.sessions.lock().await;
-        let pos = games.iter().position(|val| Arc::ptr_eq(val, original_session));
-        games.swap_remove(pos.unwrap());
+// Like "X B2", "7 B2" in numerical tic-tac-toe or "X A1–B2" for a spooky mark, made before the move
+fn move_text(position: &GamePosition, cell: usize, piece: Piece) -> String {
+    let coordinates = cell_coordinates(position.rules(), cell);
+
+    match (position, piece) {
+        // Both players place X
+        (GamePosition::Notakto(_), _) => format!("X {}", coordinates),
+        (_, Piece::Symbol(symbol)) => format!("{} {}", mark_symbol(symbol), coordinates),
+        (_, Piece::Number(number)) => format!("{} {}", number, coordinates),
+        (_, Piece::Spooky(other)) => format!("{} {}–{}", mark_symbol(position.turn()), cell_coordinates(position.rules(), other), coordinates),
+        (_, Piece::Collapse) => format!("{} measures {}", mark_symbol(position.turn()), coordinates),
+        (_, Piece::Mark) => format!("{} {}", mark_symbol(position.turn()), coordinates),
     }
 }
 
-fn draw_new_game_canvas() -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-    let mut canvas = ImageBuffer::new(300, 300);
+// Like "1. X B2 O A1 2. X C3", a number for every round of moves
+fn move_list(session: &GameSession) -> String {
+    let players = session.position.rules().players;
+
+    let list = session.move_log.iter()
+        .enumerate()
+        .map(|(i, text)| match i % players {
+            0 => format!("{}. {}", i / players + 1, text),
+            _ => text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match list.char_indices().rev().nth(MAX_MOVE_LIST_LENGTH) {
+        Some((start, _)) => format!("…{}", &list[start..]),
+        None if list.is_empty() => "No moves yet.".to_string(),
+        None => list,
+    }
+}
 
-    // Background
-    draw_filled_rect_mut(
-        &mut canvas,
-        Rect::at(0, 0).of_size(300, 300),
-        BACKGROUND,
-    );
+// Like H8, columns are letters and rows are numbers from the top
+fn cell_coordinates(rules: Rules, cell: usize) -> String {
+    format!("{}{}", column_name(cell % rules.width), cell / rules.width + 1)
+}
 
-    draw_filled_rect_mut(
-        &mut canvas,
-        Rect::at(98, 0).of_size(4, 300),
-        GRAY,
-    );
+fn column_name(column: usize) -> char {
+    (b'A' + column as u8) as char
+}
 
-    draw_filled_rect_mut(
-        &mut canvas,
-        Rect::at(198, 0).of_size(4, 300),
-        GRAY,
-    );
+// Part of the canvas around the cell and its description
+fn zoom(canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>, rules: Rules, cell: usize) -> (ImageBuffer<Rgb<u8>, Vec<u8>>, String) {
+    let window = |center: usize, size: usize| {
+        let length = size.min(ZOOM_CELLS);
+        let start = center.saturating_sub(length / 2).min(size - length);
 
-    draw_filled_rect_mut(
-        &mut canvas,
-        Rect::at(0, 98).of_size(300, 4),
-        GRAY,
-    );
+        (start, length)
+    };
 
-    draw_filled_rect_mut(
-        &mut canvas,
-        Rect::at(0, 198).of_size(300, 4),
-        GRAY,
+    let (x, width) = window(cell % rules.width, rules.width);
+    let (y, height) = window(cell / rules.width, rules.height);
+
+    let image = image::imageops::crop_imm(
+        canvas,
+        x as u32 * CELL_SIZE,
+        y as u32 * CELL_SIZE,
+        width as u32 * CELL_SIZE,
+        height as u32 * CELL_SIZE,
+    )
+    .to_image();
+
+    let view = format!(
+        "Columns {}–{}, rows {}–{}",
+        column_name(x),
+        column_name(x + width - 1),
+        y + 1,
+        y + height,
     );
 
-    canvas
+    (image, view)
 }
 
-async fn show_wait_and_common_message(
-    http: &Http,
-    interaction: &CommandInteraction,
-    canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>,
-    player_name: &str,
-    player2_name: &str,
-    common_message: &mut Message,
-    common_message2: Option<&mut Message>,
-) {
-    let embed = CreateEmbed::new()
-        .title("Game in process")
-        .description("Waiting for your turn.")
-        .thumbnail("attachment://thumbnail.png");
-
-    let action_row = generate_disabled_action_row();
-    let attachment = generate_attachment_rgb8(canvas, "canvas.png");
+fn draw_select_outline(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell: usize) {
+    let (x, y) = cell_origin(canvas.width(), cell);
+    let (x, y) = (x as i32, y as i32);
+    let size = CELL_SIZE as i32;
 
-    interaction.edit_response(http, EditInteractionResponse::new()
-        .add_embed(embed)
-        .components(vec![action_row])
-        .new_attachment(attachment.clone())
-    ).await.unwrap();
+    // Grid lines around the cell, there are no lines on borders of the canvas
+    if x > 0 {
+        draw_filled_rect_mut(canvas, Rect::at(x - 2, y - 2).of_size(4, CELL_SIZE + 4), RED);
+    }
 
-    let edited_message = EditMessage::new()
-        .embed(CreateEmbed::new()
-            .title(format!(
-                "Game between {} and {} in the progress!",
-                player_name,
-                player2_name,
-            ))
-            .description("You can play this game too by using the `/play` command.")
-            .attachment("canvas.png")
-        )
-        .attachment(attachment);
+    if x + size < canvas.width() as i32 {
+        draw_filled_rect_mut(canvas, Rect::at(x + size - 2, y - 2).of_size(4, CELL_SIZE + 4), RED);
+    }
 
-    if let Some(val) = common_message2 {
-        val.edit(http, edited_message.clone()).await.unwrap();
+    if y > 0 {
+        draw_filled_rect_mut(canvas, Rect::at(x - 2, y - 2).of_size(CELL_SIZE + 4, 4), RED);
     }
 
-    common_message.edit(http, edited_message).await.unwrap();
+    if y + size < canvas.height() as i32 {
+        draw_filled_rect_mut(canvas, Rect::at(x - 2, y + size - 2).of_size(CELL_SIZE + 4, 4), RED);
+    }
 }
 
-async fn show_game_message(
-    http: &Http,
-    interaction: &CommandInteraction,
-    cursor_pos: usize,
-    map: &[GameCell],
-    canvas: &ImageBuffer<Rgb<u8>, Vec<u8>>,
-) {
-    let embed = CreateEmbed::new()
-    .title("Your turn")
-    .description("Press arrows buttons for moving selection square.");
+// Digit of numerical tic-tac-toe, odd numbers of the first player are red
+fn draw_number(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell: usize, number: u8) {
+    let (x, y) = cell_origin(canvas.width(), cell);
+
+    draw_digit(canvas, (x + 30, y + 15), 1, number, player_color(numerical::owner_of(number)));
+}
 
-    let action_row = if map[cursor_pos] != GameCell::None {
-        generate_game_action_row(true, cursor_pos)
+fn player_color(player: Player) -> Rgb<u8> {
+    match player {
+        Player::First => RED,
+        Player::Second => BLUE,
+        Player::Third => GREEN,
+        Player::Fourth => YELLOW,
     }
-    else {
-        generate_game_action_row(false, cursor_pos)
-    };
+}
 
-    let mut cloned = canvas.clone();
+// Δ of the third player, drawn by circles along the sides like the winning line
+fn draw_triangle(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell: usize) {
+    let (x, y) = cell_origin(canvas.width(), cell);
+    let corners = [(x as f32 + 50.0, y as f32 + 15.0), (x as f32 + 85.0, y as f32 + 80.0), (x as f32 + 15.0, y as f32 + 80.0)];
 
-    draw_select_outline(&mut cloned, cursor_pos);
+    for side in 0..3 {
+        let ((start_x, start_y), (end_x, end_y)) = (corners[side], corners[(side + 1) % 3]);
 
-    interaction.edit_response(http, EditInteractionResponse::new()
-        .embed(embed)
-        .components(vec![action_row])
-        .new_attachment(generate_attachment_rgb8(&cloned, "canvas.png"))
-    )
-    .await
-    .unwrap();
+        for i in 0..=70 {
+            let t = i as f32 / 70.0;
+            let x = start_x + (end_x - start_x) * t;
+            let y = start_y + (end_y - start_y) * t;
+
+            draw_filled_circle_mut(canvas, (x as i32, y as i32), 5, GREEN);
+        }
+    }
 }
 
-async fn update_game_message(http: &Http, interaction: &ComponentInteraction, session: &GameSession) {
-    let embed = CreateEmbed::new()
-        .title("Your turn")
-        .description("Press arrows buttons for moving selection square.");
+// □ of the fourth player
+fn draw_square(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell: usize) {
+    let (x, y) = cell_origin(canvas.width(), cell);
+    let (x, y) = (x as i32, y as i32);
+
+    draw_filled_rect_mut(canvas, Rect::at(x + 18, y + 18).of_size(64, 10), YELLOW);
+    draw_filled_rect_mut(canvas, Rect::at(x + 18, y + 72).of_size(64, 10), YELLOW);
+    draw_filled_rect_mut(canvas, Rect::at(x + 18, y + 18).of_size(10, 64), YELLOW);
+    draw_filled_rect_mut(canvas, Rect::at(x + 72, y + 18).of_size(10, 64), YELLOW);
+}
 
-    let action_row = if session.map[session.cursor_pos] != GameCell::None {
-        generate_game_action_row(true, session.cursor_pos)
+// Digit made of seven segments, 40×70 pixels divided by the `divisor`
+fn draw_digit(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, (x, y): (u32, u32), divisor: i32, digit: u8, color: Rgb<u8>) {
+    // Top, top right, bottom right, bottom, bottom left, top left and middle
+    const SEGMENTS: [(i32, i32, u32, u32); 7] = [
+        (0, 0, 40, 10),
+        (30, 0, 10, 40),
+        (30, 30, 10, 40),
+        (0, 60, 40, 10),
+        (0, 30, 10, 40),
+        (0, 0, 10, 40),
+        (0, 30, 40, 10),
+    ];
+
+    // Segments of digits from 0 to 9, the first bit is the top segment
+    const DIGITS: [u8; 10] = [
+        0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110,
+        0b1101101, 0b1111101, 0b0000111, 0b1111111, 0b1101111,
+    ];
+
+    for (i, &(dx, dy, width, height)) in SEGMENTS.iter().enumerate() {
+        if DIGITS[digit as usize] & 1 << i != 0 {
+            let rect = Rect::at(x as i32 + dx / divisor, y as i32 + dy / divisor)
+                .of_size(width / divisor as u32, height / divisor as u32);
+
+            draw_filled_rect_mut(canvas, rect, color);
+        }
     }
-    else {
-        generate_game_action_row(false, session.cursor_pos)
+}
+
+// Boxes of matches by rounds for elimination, the winners bracket above the losers one and the grand final
+// on the right, round robin and Swiss are shown as a crosstable. Players are numbers of their seeds.
+fn draw_bracket(tournament: &Tournament) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    if !tournament.format.is_elimination() {
+        return draw_crosstable(tournament);
+    }
+
+    const BOX_WIDTH: u32 = 80;
+    const ROW_HEIGHT: u32 = 40; // Two rows in the box, one for every player
+    const GAP_X: u32 = 40;
+    const GAP_Y: u32 = 20;
+    const MARGIN: u32 = 20;
+
+    let rounds = |bracket: Bracket| tournament.matches.iter()
+        .filter(|val| val.bracket == bracket)
+        .map(|val| val.round)
+        .max()
+        .unwrap_or(0);
+
+    let round_matches = |bracket: Bracket, round: usize| -> Vec<usize> {
+        (0..tournament.matches.len())
+            .filter(|&index| tournament.matches[index].bracket == bracket && tournament.matches[index].round == round)
+            .collect()
     };
 
-    let mut cloned = session.canvas.clone();
+    let most_matches = |bracket: Bracket| (1..=rounds(bracket)).map(|round| round_matches(bracket, round).len()).max().unwrap_or(0) as u32;
 
-    draw_select_outline(&mut cloned, session.cursor_pos);
+    let box_height = 2 * ROW_HEIGHT;
+    let main_height = most_matches(Bracket::Main) * (box_height + GAP_Y);
+    let losers_height = most_matches(Bracket::Losers) * (box_height + GAP_Y);
+    let columns = rounds(Bracket::Main).max(rounds(Bracket::Losers)) as u32 + rounds(Bracket::Final) as u32;
 
-    interaction.edit_response(http, EditInteractionResponse::new()
-        .embed(embed)
-        .components(vec![action_row])
-        .new_attachment(generate_attachment_rgb8(&cloned, "canvas.png"))
-    )
-    .await
-    .unwrap();
-}
+    let width = 2 * MARGIN + columns * BOX_WIDTH + (columns - 1) * GAP_X;
+    let height = 2 * MARGIN + main_height + losers_height;
 
-fn draw_select_outline(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell: usize) {
-    match cell {
-        0 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 + 98, CELLS[cell].1).of_size(4, 102), 
-                RED,
-            );
-    
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0, CELLS[cell].1 + 98).of_size(98, 4), 
-                RED,
-            );
-        }
+    // Top left corners of boxes, matches in every round are spread evenly over the height of their bracket
+    let mut origins = vec![(0, 0); tournament.matches.len()];
 
-        1 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 + 98, CELLS[cell].1).of_size(4, 102), 
-                RED,
-            );
-    
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 98).of_size(100, 4), 
-                RED,
-            );
+    for (bracket, top, bracket_height) in [(Bracket::Main, MARGIN, main_height), (Bracket::Losers, MARGIN + main_height, losers_height)] {
+        for round in 1..=rounds(bracket) {
+            let matches = round_matches(bracket, round);
+            let slot_height = bracket_height / matches.len() as u32;
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1).of_size(4, 98), 
-                RED,
-            );
+            for (i, &index) in matches.iter().enumerate() {
+                let x = MARGIN + (round as u32 - 1) * (BOX_WIDTH + GAP_X);
+                let y = top + i as u32 * slot_height + (slot_height - box_height) / 2;
+
+                origins[index] = (x, y);
+            }
         }
+    }
 
-        2 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 98).of_size(102, 4), 
-                RED,
-            );
+    for index in round_matches(Bracket::Final, 1) {
+        origins[index] = (width - MARGIN - BOX_WIDTH, (height - box_height) / 2);
+    }
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1).of_size(4, 98), 
-                RED,
-            );
-        }
+    let mut canvas = ImageBuffer::from_pixel(width, height, BACKGROUND);
 
-        3 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0, CELLS[cell].1 - 2).of_size(102, 4), 
-                RED,
-            );
+    for (index, val) in tournament.matches.iter().enumerate() {
+        let (x, y) = origins[index];
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 + 98, CELLS[cell].1 + 2).of_size(4, 100), 
-                RED,
-            );
-    
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0, CELLS[cell].1 + 98).of_size(98, 4), 
-                RED,
-            );
+        // Players who have dropped into the losers bracket are not connected
+        for (row, source) in val.sources.iter().enumerate() {
+            if let Source::Winner(previous) = *source {
+                if tournament.matches[previous].bracket == val.bracket || val.bracket == Bracket::Final {
+                    let (from_x, from_y) = origins[previous];
+                    let from = (from_x + BOX_WIDTH, from_y + ROW_HEIGHT);
+                    let to = (x, y + row as u32 * ROW_HEIGHT + ROW_HEIGHT / 2);
+
+                    draw_connector(&mut canvas, from, to, x - GAP_X / 2);
+                }
+            }
         }
 
-        4 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 - 2).of_size(104, 4), 
-                RED,
-            );
+        draw_filled_rect_mut(&mut canvas, Rect::at(x as i32, y as i32).of_size(BOX_WIDTH, box_height), PANEL);
+        draw_filled_rect_mut(&mut canvas, Rect::at(x as i32, (y + ROW_HEIGHT) as i32).of_size(BOX_WIDTH, 2), BACKGROUND);
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 + 98, CELLS[cell].1 + 2).of_size(4, 100), 
-                RED,
-            );
-    
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 98).of_size(100, 4), 
-                RED,
-            );
+        for (row, slot) in tournament.slots(index).into_iter().enumerate() {
+            let winner = match val.result {
+                Some(MatchResult::First) => Some(0),
+                Some(MatchResult::Second) => Some(1),
+                _ => None,
+            };
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 2).of_size(4, 96), 
-                RED,
-            );
+            let color = match winner {
+                Some(winner) if winner == row => GREEN,
+                Some(_) => RED,
+                None => GRAY,
+            };
+
+            let (x, y) = (x + 10, y + row as u32 * ROW_HEIGHT);
+
+            match slot {
+                Slot::Player(player) => draw_seed(&mut canvas, (x, y + 3), player + 1, color),
+                Slot::Bye => draw_filled_rect_mut(&mut canvas, Rect::at(x as i32, (y + ROW_HEIGHT / 2 - 2) as i32).of_size(20, 4), GRAY),
+                Slot::Pending => (),
+            }
         }
+    }
 
-        5 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 - 2).of_size(102, 4), 
-                RED,
-            );
+    canvas
+}
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 98).of_size(102, 4), 
-                RED,
-            );
+// Results of the player in the row against the player in the column
+fn draw_crosstable(tournament: &Tournament) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    const CELL: u32 = 50;
+    const MARGIN: u32 = 20;
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 2).of_size(4, 96), 
-                RED,
-            );
-        }
+    let players = tournament.players.len() as u32;
+    let size = 2 * MARGIN + (players + 1) * CELL;
+    let mut canvas = ImageBuffer::from_pixel(size, size, BACKGROUND);
 
-        6 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0, CELLS[cell].1 - 2).of_size(102, 4), 
-                RED,
-            );
+    let origin = |row: u32, column: u32| (MARGIN + column * CELL, MARGIN + row * CELL);
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 + 98, CELLS[cell].1 + 2).of_size(4, 98), 
-                RED,
-            );
-        }
+    for player in 0..players {
+        let (x, y) = origin(0, player + 1);
+        draw_seed(&mut canvas, (x + 3, y + 7), player as usize + 1, GRAY);
 
-        7 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 - 2).of_size(104, 4), 
-                RED,
-            );
+        let (x, y) = origin(player + 1, 0);
+        draw_seed(&mut canvas, (x + 3, y + 7), player as usize + 1, GRAY);
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 + 98, CELLS[cell].1 + 2).of_size(4, 98), 
-                RED,
-            );
-    
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 2).of_size(4, 98), 
-                RED,
-            );
+        for other in (0..players).filter(|&other| other != player) {
+            let (x, y) = origin(player + 1, other + 1);
+            draw_filled_rect_mut(&mut canvas, Rect::at(x as i32 + 3, y as i32 + 3).of_size(CELL - 6, CELL - 6), PANEL);
         }
+    }
 
-        8 => {
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 - 2).of_size(102, 4), 
-                RED,
-            );
+    for (index, val) in tournament.matches.iter().enumerate() {
+        let [Slot::Player(first), Slot::Player(second)] = tournament.slots(index) else {
+            continue;
+        };
 
-            draw_filled_rect_mut(
-                canvas,
-                Rect::at(CELLS[cell].0 - 2, CELLS[cell].1 + 2).of_size(4, 98), 
-                RED,
-            );
+        let colors = match val.result {
+            Some(MatchResult::First) => [GREEN, RED],
+            Some(MatchResult::Second) => [RED, GREEN],
+            Some(MatchResult::Draw) => [GRAY, GRAY],
+            None => continue,
+        };
+
+        for (player, other, color) in [(first, second, colors[0]), (second, first, colors[1])] {
+            let (x, y) = origin(player as u32 + 1, other as u32 + 1);
+            draw_filled_rect_mut(&mut canvas, Rect::at(x as i32 + 3, y as i32 + 3).of_size(CELL - 6, CELL - 6), color);
         }
+    }
 
-        _ => unreachable!(),
+    canvas
+}
+
+// Horizontal line to the `middle_x`, vertical one to the height of the end and horizontal one to the end
+fn draw_connector(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, (from_x, from_y): (u32, u32), (to_x, to_y): (u32, u32), middle_x: u32) {
+    let (top, bottom) = (from_y.min(to_y), from_y.max(to_y));
+
+    draw_filled_rect_mut(canvas, Rect::at(from_x as i32, from_y as i32 - 1).of_size(middle_x - from_x + 2, 3), GRAY);
+    draw_filled_rect_mut(canvas, Rect::at(middle_x as i32 - 1, top as i32 - 1).of_size(3, bottom - top + 3), GRAY);
+    draw_filled_rect_mut(canvas, Rect::at(middle_x as i32, to_y as i32 - 1).of_size(to_x - middle_x, 3), GRAY);
+}
+
+// Number of one or two digits, 45×35 pixels at most
+fn draw_seed(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, (x, y): (u32, u32), seed: usize, color: Rgb<u8>) {
+    let digits: Vec<_> = seed.to_string().bytes().map(|val| val - b'0').collect();
+
+    for (i, &digit) in digits.iter().enumerate() {
+        draw_digit(canvas, (x + i as u32 * 25, y), 2, digit, color);
     }
 }
 
+// Frame inside of the cell, used for winning lines which are not straight on the canvas
+// and for cells of quantum tic-tac-toe which are being chosen
+fn draw_cell_highlight(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, cell: usize, color: Rgb<u8>) {
+    let (x, y) = cell_origin(canvas.width(), cell);
+    let (x, y) = (x as i32 + 4, y as i32 + 4);
+    let size = CELL_SIZE - 8;
+
+    draw_filled_rect_mut(canvas, Rect::at(x, y).of_size(size, 6), color);
+    draw_filled_rect_mut(canvas, Rect::at(x, y + size as i32 - 6).of_size(size, 6), color);
+    draw_filled_rect_mut(canvas, Rect::at(x, y).of_size(6, size), color);
+    draw_filled_rect_mut(canvas, Rect::at(x + size as i32 - 6, y).of_size(6, size), color);
+}
+
+// Frame on the grid lines around the sub-board of ultimate tic-tac-toe
+fn draw_sub_board_outline(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, sub_board: usize, color: Rgb<u8>) {
+    let (x, y) = cell_origin(canvas.width(), ultimate::sub_board_cells(sub_board)[0]);
+    let (x, y) = (x as i32, y as i32);
+    let size = 3 * CELL_SIZE;
+
+    // Parts outside of the canvas are not drawn
+    draw_filled_rect_mut(canvas, Rect::at(x - 4, y - 4).of_size(size + 8, 8), color);
+    draw_filled_rect_mut(canvas, Rect::at(x - 4, y + size as i32 - 4).of_size(size + 8, 8), color);
+    draw_filled_rect_mut(canvas, Rect::at(x - 4, y - 4).of_size(8, size + 8), color);
+    draw_filled_rect_mut(canvas, Rect::at(x + size as i32 - 4, y - 4).of_size(8, size + 8), color);
+}
+
 fn generate_disabled_action_row() -> CreateActionRow {
     let left = CreateButton::new("left")
         .label("←")
@@ -930,41 +4969,32 @@ fn generate_disabled_action_row() -> CreateActionRow {
     action_row
 }
 
-fn generate_game_action_row(send_disabled: bool, cursor_position: usize) -> CreateActionRow {
-    let mut left = CreateButton::new("left")
+// `can_move` is from the `CursorArea::can_move`, `send` is the button for making
+// a move, or for selecting a sub-board in ultimate tic-tac-toe
+fn generate_game_action_row(send: CreateButton, send_disabled: bool, can_move: [bool; 4]) -> CreateActionRow {
+    let [can_left, can_down, can_up, can_right] = can_move;
+
+    let left = CreateButton::new("left")
         .label("←")
-        .style(ButtonStyle::Secondary);
-    
-    if [0, 3, 6].contains(&cursor_position) {
-        left = left.disabled(true);
-    }
-    
-    let mut down = CreateButton::new("down")
-        .label("↓")
-        .style(ButtonStyle::Secondary); 
+        .style(ButtonStyle::Secondary)
+        .disabled(!can_left);
 
-    if cursor_position >= 6 {
-        down = down.disabled(true);
-    }
+    let down = CreateButton::new("down")
+        .label("↓")
+        .style(ButtonStyle::Secondary)
+        .disabled(!can_down);
 
-    let mut up = CreateButton::new("up")
+    let up = CreateButton::new("up")
         .label("↑")
-        .style(ButtonStyle::Secondary);
-
-    if cursor_position <= 2 {
-        up = up.disabled(true);
-    }
+        .style(ButtonStyle::Secondary)
+        .disabled(!can_up);
 
-    let mut right = CreateButton::new("right")
+    let right = CreateButton::new("right")
         .label("→")
-        .style(ButtonStyle::Secondary); 
-
-    if [2, 5, 8].contains(&cursor_position) {
-        right = right.disabled(true);
-    }
+        .style(ButtonStyle::Secondary)
+        .disabled(!can_right);
 
-    let send = CreateButton::new("send")
-        .label("Send")
+    let send = send
         .style(ButtonStyle::Primary)
         .disabled(send_disabled);
 
@@ -979,6 +5009,149 @@ fn generate_game_action_row(send_disabled: bool, cursor_position: usize) -> Crea
     action_row
 }
 
+// Layers of the cube from the top to the bottom, the current one is disabled
+fn generate_layer_action_row(current_layer: usize) -> CreateActionRow {
+    let buttons = (0..qubic::SIZE)
+        .map(|layer| {
+            CreateButton::new(format!("layer_{layer}"))
+                .label(format!("Layer {}", layer + 1))
+                .style(if layer == current_layer { ButtonStyle::Primary } else { ButtonStyle::Secondary })
+                .disabled(layer == current_layer)
+        })
+        .collect();
+
+    CreateActionRow::Buttons(buttons)
+}
+
+// Choice of the piece for modes where players choose it
+fn generate_piece_action_row(session: &GameSession) -> Option<CreateActionRow> {
+    match (&session.position, session.piece) {
+        (_, Piece::Symbol(symbol)) => Some(generate_symbol_action_row(symbol)),
+        (GamePosition::Quantum(position), Piece::Spooky(cell)) => Some(generate_spooky_action_row(position.board().rules(), cell)),
+        (GamePosition::Numerical(position), Piece::Number(number)) => {
+            Some(generate_number_select_menu(&position.remaining_numbers(position.turn()), number))
+        }
+        _ => None,
+    }
+}
+
+// Marks of wild tic-tac-toe and Order & Chaos, the chosen one is disabled
+fn generate_symbol_action_row(symbol: Player) -> CreateActionRow {
+    let x = CreateButton::new("symbol_x")
+        .label("Place X")
+        .style(if symbol == Player::First { ButtonStyle::Danger } else { ButtonStyle::Secondary })
+        .disabled(symbol == Player::First);
+
+    let o = CreateButton::new("symbol_o")
+        .label("Place O")
+        .style(if symbol == Player::Second { ButtonStyle::Primary } else { ButtonStyle::Secondary })
+        .disabled(symbol == Player::Second);
+
+    CreateActionRow::Buttons(vec![x, o])
+}
+
+// The first cell of the spooky mark can be chosen again
+fn generate_spooky_action_row(rules: Rules, cell: usize) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new("spooky_cancel")
+            .label(format!("Change the first cell ({})", cell_coordinates(rules, cell)))
+            .style(ButtonStyle::Secondary)
+    ])
+}
+
+// Remaining numbers of the player in numerical tic-tac-toe
+fn generate_number_select_menu(numbers: &[u8], selected: u8) -> CreateActionRow {
+    let options = numbers.iter()
+        .map(|&number| {
+            CreateSelectMenuOption::new(format!("Number {}", number), number.to_string())
+                .default_selection(number == selected)
+        })
+        .collect();
+
+    CreateActionRow::SelectMenu(CreateSelectMenu::new("number", CreateSelectMenuKind::String { options }))
+}
+
+fn generate_coordinate_select_menus(rules: Rules, cursor_position: usize) -> Vec<CreateActionRow> {
+    let columns = (0..rules.width)
+        .map(|column| {
+            CreateSelectMenuOption::new(format!("Column {}", column_name(column)), column.to_string())
+                .default_selection(column == cursor_position % rules.width)
+        })
+        .collect();
+
+    let rows = (0..rules.height)
+        .map(|row| {
+            CreateSelectMenuOption::new(format!("Row {}", row + 1), row.to_string())
+                .default_selection(row == cursor_position / rules.width)
+        })
+        .collect();
+
+    vec![
+        CreateActionRow::SelectMenu(CreateSelectMenu::new("column", CreateSelectMenuKind::String { options: columns })),
+        CreateActionRow::SelectMenu(CreateSelectMenu::new("row", CreateSelectMenuKind::String { options: rows })),
+    ]
+}
+
+// Legal cells closest to the last move, a select menu has at most 25 options
+fn generate_suggestion_action_row(session: &GameSession) -> CreateActionRow {
+    let rules = session.position.rules();
+    let last_move = session.position.moves().last().copied().unwrap_or_else(|| center_cell(rules));
+
+    let distance = |cell: usize| {
+        let dx = (cell % rules.width).abs_diff(last_move % rules.width);
+        let dy = (cell / rules.width).abs_diff(last_move / rules.width);
+        dx.max(dy)
+    };
+
+    let mut cells: Vec<_> = (0..rules.width * rules.height).filter(|&cell| session.position.is_legal(cell)).collect();
+    cells.sort_by_key(|&cell| distance(cell));
+    cells.truncate(25);
+    cells.sort();
+
+    let options = cells.into_iter()
+        .map(|cell| {
+            CreateSelectMenuOption::new(cell_coordinates(rules, cell), cell.to_string())
+                .default_selection(session.suggestion == Some(cell))
+        })
+        .collect();
+
+    CreateActionRow::SelectMenu(
+        CreateSelectMenu::new("suggest", CreateSelectMenuKind::String { options })
+            .placeholder("Suggest a move to your teammate")
+    )
+}
+
+// Cells where the move can not be made are disabled, like cells of dead boards in Notakto
+fn generate_cell_action_rows(position: &GamePosition, disabled: bool) -> Vec<CreateActionRow> {
+    let board = position.board();
+
+    (0..board.height()).map(|row| {
+        let buttons = (0..board.width()).map(|column| {
+            let cell = row * board.width() + column;
+
+            let (label, style) = match board.get(cell) {
+                GameCell::None => ("\u{200b}".to_string(), ButtonStyle::Secondary), // Labels can not be empty
+                GameCell::First => ("X".to_string(), ButtonStyle::Danger),
+                GameCell::Second => ("O".to_string(), ButtonStyle::Primary),
+                GameCell::Third => ("Δ".to_string(), ButtonStyle::Success),
+                GameCell::Fourth => ("□".to_string(), ButtonStyle::Secondary),
+            };
+
+            // Numbers are shown instead of marks of their players
+            let label = position.number(cell).map_or(label, |number| number.to_string());
+
+            CreateButton::new(format!("cell_{cell}"))
+                .label(label)
+                .style(style)
+                .disabled(disabled || !position.is_legal(cell))
+        })
+        .collect();
+
+        CreateActionRow::Buttons(buttons)
+    })
+    .collect()
+}
+
 fn generate_attachment(image: &[u8], width: u32, height: u32, name: &'static str, color_type: ColorType) -> CreateAttachment {
     let buffer = Vec::new();
     let cursor = Cursor::new(buffer);
@@ -1018,4 +5191,43 @@ fn fill_pixel(canvas: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, scratch: &ImageBuffer<
     }
 
     canvas.draw_pixel(x, y, output);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forfeits_lose_to_everyone_else() {
+        assert_eq!(forfeit_result(2, false, 0), GameResult::SecondWon);
+        assert_eq!(forfeit_result(2, false, 1), GameResult::FirstWon);
+
+        // Teammates share the seat of their mark
+        assert_eq!(forfeit_result(4, true, 2), GameResult::SecondWon);
+        assert_eq!(forfeit_result(4, true, 3), GameResult::FirstWon);
+
+        let result = forfeit_result(3, false, 1);
+        assert_eq!(result, GameResult::Lost(Player::Second));
+        assert_eq!(result.winner(), None);
+        assert_eq!(result.loser(), Some(Player::Second));
+    }
+
+    #[test]
+    fn queue_matches_the_same_options() {
+        let key = QueueKey::new(QueueScope::Guild, Some(GuildId::new(1)), ChannelId::new(2));
+        let classic = GameOptions::for_tournament(Mode::Classic);
+        let teams = GameOptions { teams: true, ..classic };
+        let mut queue = Queue::default();
+
+        for (user_id, options) in [(1, teams), (2, GameOptions::for_tournament(Mode::Gomoku)), (3, classic), (4, classic)] {
+            queue.push(key, QueueEntry { user_id: UserId::new(user_id), joined_at: 0, value: options });
+        }
+
+        // Time controls are not compared, the longest waiting players come first
+        let blitz = GameOptions { time_control: TimeControl::PerMove { seconds: 10 }, ..classic };
+        let lobby = queue.pop(key, 2, |val| val.is_matching(&blitz)).unwrap();
+
+        assert_eq!(lobby.iter().map(|entry| entry.user_id.get()).collect::<Vec<_>>(), [3, 4]);
+        assert!(queue.pop(key, 1, |val| val.is_matching(&classic)).is_none());
+        assert_eq!(queue.pop(key, 1, |val| val.is_matching(&teams)).unwrap()[0].user_id.get(), 1);
+    }
+}
@@ -1,5 +1,25 @@
+mod ai;
+mod board;
+mod clock;
 mod game;
+mod notakto;
+mod notation;
+mod numerical;
 mod ping;
+mod quantum;
+mod qubic;
+mod queue;
+mod rating;
+mod stats;
+mod storage;
+mod tournament;
+mod ultimate;
+mod variant;
+mod wild;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use serenity::all::Interaction;
 use serenity::async_trait;
@@ -8,15 +28,24 @@ use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMess
 use serenity::prelude::*;
 
 use game::Game;
+use storage::Storage;
 
 struct Handler {
-    game: Game,
+    game: Arc<Game>,
+    storage: Arc<Storage>,
+
+    timers_started: AtomicBool, // `ready` is called again after reconnections
 }
 
 impl Handler {
     fn new() -> Self {
+        let storage = Arc::new(Storage::open("./games.sqlite3").expect("failed to open the database"));
+
         Self {
-            game: Game::new(),
+            game: Arc::new(Game::new(Arc::clone(&storage))),
+            storage,
+
+            timers_started: AtomicBool::new(false),
         }
     }
 }
@@ -28,7 +57,10 @@ impl EventHandler for Handler {
             Interaction::Command(command) => {
                 match command.data.name.as_str() {
                     "ping" => ping::command(ctx, command).await,
-                    "play" => self.game.command(ctx, command).await,
+                    "play" | "stop" | "queue" | "tournament" | "replay" | "export" | "import" => self.game.command(ctx, command).await,
+                    "stats" => stats::command(ctx, command, &self.storage).await,
+                    "leaderboard" => rating::leaderboard_command(ctx, command, &self.storage).await,
+                    "rating" => rating::rating_command(ctx, command, &self.storage).await,
                     _ => {
                         command.create_response(&ctx.http, CreateInteractionResponse::Message(
                             CreateInteractionResponseMessage::new()
@@ -42,7 +74,12 @@ impl EventHandler for Handler {
             }
 
             Interaction::Component(component) => {
-                self.game.component(ctx, component).await;
+                if component.data.custom_id.starts_with("leaderboard_") {
+                    rating::leaderboard_component(ctx, component, &self.storage).await;
+                }
+                else {
+                    self.game.component(ctx, component).await;
+                }
             }
 
             _ => (), // Now other variants are not important
@@ -52,6 +89,26 @@ impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
         println!("{} has connected!", ready.user.name);
 
+        if !self.timers_started.swap(true, Ordering::SeqCst) {
+            let game = Arc::clone(&self.game);
+            let http = Arc::clone(&ctx.http);
+
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+                loop {
+                    interval.tick().await;
+
+                    // A panic in one tick must not stop the timers for good
+                    let (game, http) = (Arc::clone(&game), Arc::clone(&http));
+
+                    if let Err(err) = tokio::spawn(async move { game.tick(&http).await }).await {
+                        eprintln!("The timer tick has failed: {err:?}");
+                    }
+                }
+            });
+        }
+
         // Trying to get our guild
         let guild = ready.guilds[0];
         assert_eq!(guild.unavailable, true);
@@ -63,7 +120,15 @@ impl EventHandler for Handler {
         guild_id.set_application_commands(&ctx.http, vec![
             Game::register_play(),
             Game::register_stop(),
-            ping::register(), 
+            Game::register_queue(),
+            Game::register_tournament(),
+            Game::register_replay(),
+            Game::register_export(),
+            Game::register_import(),
+            stats::register(),
+            rating::register_leaderboard(),
+            rating::register_rating(),
+            ping::register(),
         ])
         .await
         .expect("failed to create application command");
@@ -0,0 +1,183 @@
+// Notakto: both players place X on one or more 3×3 boards. A board with
+// three in a row is dead and the player who kills the last board loses.
+//
+// Boards are placed side by side, so cells are numbered row by row
+// on the 3×(3·boards) board, like cells of other boards.
+use crate::board::{Board, GameCell, MoveError, Outcome, Player, Rules, LINES};
+
+pub const MAX_BOARDS: usize = 3;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NotaktoPosition {
+    board: Board,
+    dead: Vec<Option<usize>>, // The killing line of every board, an index in the `LINES`
+    moves: Vec<usize>,
+    outcome: Outcome, // `line` of the loss is `board * LINES.len() + line`
+}
+
+impl NotaktoPosition {
+    pub fn new(boards: usize) -> Self {
+        Self {
+            board: Board::new(Self::rules(boards)),
+            dead: vec![None; boards],
+            moves: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    pub fn rules(boards: usize) -> Rules {
+        Rules::new(3 * boards, 3, 3).unwrap()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn boards(&self) -> usize {
+        self.dead.len()
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    // Players take turns, but both of them place X
+    pub fn turn(&self) -> Player {
+        match self.moves.len() % 2 {
+            0 => Player::First,
+            _ => Player::Second,
+        }
+    }
+
+    pub fn is_dead(&self, board: usize) -> bool {
+        self.dead[board].is_some()
+    }
+
+    // Killing line of the board as cells of the whole board
+    pub fn dead_line(&self, board: usize) -> Option<[usize; 3]> {
+        let line = self.dead[board]?;
+
+        Some(LINES[line].map(|i| self.cell(board, i)))
+    }
+
+    // Cell of the whole board from the index of the cell inside of the board
+    pub fn cell(&self, board: usize, index: usize) -> usize {
+        index / 3 * self.board.width() + board * 3 + index % 3
+    }
+
+    pub fn board_of(&self, cell: usize) -> usize {
+        cell % self.board.width() / 3
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_move(cell).is_ok()
+    }
+
+    pub fn legal_moves(&self) -> Vec<usize> {
+        (0..self.board.cells().len())
+            .filter(|&cell| self.is_legal(cell))
+            .collect()
+    }
+
+    pub fn apply_move(&mut self, cell: usize) -> Result<Outcome, MoveError> {
+        self.check_move(cell)?;
+
+        let board = self.board_of(cell);
+
+        self.board.set(cell, GameCell::First);
+        self.moves.push(cell);
+
+        self.dead[board] = LINES.iter()
+            .position(|line| line.iter().all(|&i| self.board.get(self.cell(board, i)) == GameCell::First));
+
+        // Boards are killed only by complete lines, so there are no draws
+        self.outcome = match self.dead[board] {
+            Some(line) if self.dead.iter().all(Option::is_some) => Outcome::Loss {
+                player: self.turn().other(),
+                line: board * LINES.len() + line,
+            },
+            _ => Outcome::Ongoing,
+        };
+
+        Ok(self.outcome)
+    }
+
+    // Takes back the last move, used by the search of the computer player
+    pub fn undo_move(&mut self) -> Option<usize> {
+        let cell = self.moves.pop()?;
+
+        // Moves are made only on alive boards
+        let board = self.board_of(cell);
+
+        self.board.set(cell, GameCell::None);
+        self.dead[board] = None;
+        self.outcome = Outcome::Ongoing;
+
+        Some(cell)
+    }
+
+    fn check_move(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= self.board.cells().len() {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        if self.is_dead(self.board_of(cell)) {
+            return Err(MoveError::DeadBoard);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dead_boards_take_no_moves() {
+        let mut position = NotaktoPosition::new(2);
+
+        // The left column of the first board
+        for cell in [0, 6, 12] {
+            assert_eq!(position.apply_move(cell), Ok(Outcome::Ongoing));
+        }
+
+        assert!(position.is_dead(0));
+        assert!(!position.is_dead(1));
+        assert_eq!(position.dead_line(0), Some([0, 6, 12]));
+        assert_eq!(position.apply_move(1), Err(MoveError::DeadBoard));
+        assert_eq!(position.legal_moves(), [3, 4, 5, 9, 10, 11, 15, 16, 17]);
+
+        // Taking back the killing move revives the board
+        position.undo_move();
+        assert!(!position.is_dead(0));
+        assert!(position.is_legal(1));
+    }
+
+    #[test]
+    fn killing_the_last_board_loses() {
+        let mut position = NotaktoPosition::new(2);
+
+        for cell in [0, 6, 12] {
+            position.apply_move(cell).unwrap();
+        }
+
+        // The second player kills the second board by its main diagonal
+        position.apply_move(3).unwrap();
+        position.apply_move(10).unwrap();
+
+        let outcome = position.apply_move(17).unwrap();
+
+        assert_eq!(outcome, Outcome::Loss { player: Player::Second, line: LINES.len() + 6 });
+        assert_eq!(position.dead_line(1), Some([3, 10, 17]));
+        assert_eq!(position.apply_move(4), Err(MoveError::GameOver));
+    }
+}
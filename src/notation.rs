@@ -0,0 +1,577 @@
+// Text records of games, like PGN of chess: tags in square brackets and moves by coordinates
+//
+// [Variant "classic"]
+// [Board "3x3"]
+// [WinLength "3"]
+// [Date "2026.10.17"]
+// [Player1 "Alice"]
+// [Player2 "Bob"]
+// [Result "1-0"]
+// [Termination "line"]
+//
+// 1. b2 a1 2. c3 b1 3. a3 c1 4. c2 1-0
+//
+// Columns are letters and rows are numbers from the top. Wild moves start with the mark (Xb2),
+// numerical ones with the number (7b2), spooky marks join two cells (a1-b2) and measurements
+// of quantum tic-tac-toe start with "!" (!b2).
+use crate::board::{MoveError, Outcome, Player, Rules};
+use crate::game;
+use crate::storage::{EndReason, GameRecord, GameResult};
+use crate::variant::{GamePosition, Mode, Piece};
+
+// Moves are wrapped like in PGN
+const LINE_LENGTH: usize = 80;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NotationError {
+    Tag(&'static str), // The tag is missing or its value is invalid
+    Token(String), // The text can not be read as a move
+    Illegal(String, MoveError), // The move can not be made in the position
+    Unfinished, // The game has not ended by the moves, and neither by a resignation or a timeout
+    Result, // The result or the termination does not match the moves
+}
+
+impl NotationError {
+    pub fn describe(&self) -> String {
+        match self {
+            NotationError::Tag(tag) => format!("The tag {} is missing or invalid.", tag),
+            NotationError::Token(token) => format!("\"{}\" is not a move.", token),
+            NotationError::Illegal(token, _) => format!("The move {} is not legal.", token),
+            NotationError::Unfinished => "The game is not finished, the termination must be a resignation or a timeout.".to_string(),
+            NotationError::Result => "The result does not match the moves.".to_string(),
+        }
+    }
+}
+
+// The game read from the text, in the same form as in the storage
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParsedGame {
+    pub players: Vec<String>, // In the order of marks, then teammates of X and O
+    pub bot: Option<String>,
+
+    pub mode: Mode,
+    pub rules: Rules,
+    pub moves: Vec<usize>,
+    pub symbols: Vec<Player>,
+    pub numbers: Vec<u8>,
+    pub result: GameResult,
+    pub reason: EndReason,
+
+    pub date: Option<i64>, // Unix time of the start of the day
+}
+
+pub fn write(record: &GameRecord) -> Result<String, MoveError> {
+    let moves = GamePosition::stored_moves(record.mode, record.rules, &record.moves, &record.symbols, &record.numbers)?;
+    let rules = record.rules;
+
+    let mut tags = vec![
+        ("Variant", record.mode.as_str().to_string()),
+        ("Board", format!("{}x{}", rules.width, rules.height)),
+        ("WinLength", rules.win_length.to_string()),
+    ];
+
+    if rules.exact {
+        tags.push(("Exact", "true".to_string()));
+    }
+
+    if rules.misere {
+        tags.push(("Misere", "true".to_string()));
+    }
+
+    if rules.players > 2 {
+        tags.push(("Marks", rules.players.to_string()));
+    }
+
+    tags.push(("Date", format_date(record.started_at)));
+
+    let players = [&record.player, &record.player2].into_iter().chain(&record.others);
+
+    for (tag, player) in ["Player1", "Player2", "Player3", "Player4"].into_iter().zip(players) {
+        tags.push((tag, player.1.clone()));
+    }
+
+    if let Some(bot) = &record.bot {
+        tags.push(("Bot", bot.clone()));
+    }
+
+    let result = format_result(record.result, rules.players);
+
+    tags.push(("Result", result.clone()));
+    tags.push(("Termination", record.reason.as_str().to_string()));
+
+    let mut text: String = tags.iter()
+        .map(|(tag, value)| format!("[{} \"{}\"]\n", tag, value.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect();
+
+    text.push('\n');
+
+    let tokens = moves.iter()
+        .enumerate()
+        .map(|(i, &(cell, piece))| {
+            let token = format_move(rules, cell, piece);
+
+            match i % rules.players {
+                0 => format!("{}. {}", i / rules.players + 1, token),
+                _ => token,
+            }
+        })
+        .chain([result]);
+
+    let mut line = String::new();
+
+    for token in tokens {
+        if !line.is_empty() && line.len() + token.len() + 1 > LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+
+        line.push_str(&token);
+    }
+
+    text.push_str(&line);
+    text.push('\n');
+
+    Ok(text)
+}
+
+// Moves are checked by the rules, the result and the termination of finished games are taken from the moves
+pub fn parse(text: &str) -> Result<ParsedGame, NotationError> {
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+
+    for line in text.lines().map(str::trim) {
+        match line.strip_prefix('[').and_then(|val| val.strip_suffix(']')) {
+            Some(tag) => tags.push(parse_tag(tag).ok_or_else(|| NotationError::Token(line.to_string()))?),
+            None => {
+                movetext.push_str(line);
+                movetext.push(' ');
+            }
+        }
+    }
+
+    let tag = |name: &str| tags.iter().find(|val| val.0 == name).map(|val| val.1.as_str());
+
+    let mode = tag("Variant").and_then(Mode::from_str).ok_or(NotationError::Tag("Variant"))?;
+
+    let (width, height) = tag("Board")
+        .and_then(|val| val.split_once('x'))
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or(NotationError::Tag("Board"))?;
+
+    let win_length = tag("WinLength").and_then(|val| val.parse().ok()).ok_or(NotationError::Tag("WinLength"))?;
+    let exact = tag("Exact") == Some("true");
+    let misere = tag("Misere") == Some("true");
+    let marks = match tag("Marks") {
+        Some(val) => val.parse().ok().filter(|val| (2..=4).contains(val)).ok_or(NotationError::Tag("Marks"))?,
+        None => 2,
+    };
+
+    // Only boards which could be played here, so imported games can be replayed and rematched
+    let rules = Rules::new(width, height, win_length)
+        .map(|rules| Rules { exact, misere, players: marks, ..rules })
+        .filter(|&rules| game::is_playable_board(mode, rules))
+        .ok_or(NotationError::Tag("Board"))?;
+
+    if misere && (mode != Mode::Classic || marks > 2) {
+        return Err(NotationError::Tag("Misere"));
+    }
+
+    // Fixed boards of modes are created by the mode, so a different board would not match the moves
+    let mut position = GamePosition::new(mode, rules);
+
+    if position.rules() != rules || (marks > 2 && mode != Mode::Classic) {
+        return Err(NotationError::Tag("Board"));
+    }
+
+    let players: Vec<String> = ["Player1", "Player2", "Player3", "Player4"].into_iter()
+        .map_while(|name| tag(name).map(str::to_string))
+        .collect();
+
+    // Teams are only of two players on each side
+    if players.len() != marks && (marks, players.len()) != (2, 4) {
+        return Err(NotationError::Tag("Player"));
+    }
+
+    let mut outcome = Outcome::Ongoing;
+
+    for token in movetext.split_whitespace() {
+        // Move numbers and the result after the moves
+        if token.ends_with('.') || parse_result(token, marks).is_some() {
+            continue;
+        }
+
+        let (cell, piece) = parse_move(rules, token).ok_or_else(|| NotationError::Token(token.to_string()))?;
+
+        outcome = position.apply_move(cell, piece).map_err(|err| NotationError::Illegal(token.to_string(), err))?;
+    }
+
+    let tagged_result = tag("Result").map(|val| parse_result(val, marks).ok_or(NotationError::Tag("Result"))).transpose()?;
+    let tagged_reason = tag("Termination");
+
+    let (result, reason) = match outcome {
+        Outcome::Ongoing => match (tagged_result, tagged_reason.and_then(|val| EndReason::from_str(val, None))) {
+            (Some(result), Some(reason @ (EndReason::Resignation | EndReason::Timeout))) => (result, reason),
+            _ => return Err(NotationError::Unfinished),
+        },
+        Outcome::Win { line, .. } => (GameResult::won_by(outcome.winner().unwrap()), EndReason::Line(line)),
+        Outcome::Loss { line, .. } => (GameResult::won_by(outcome.winner().unwrap()), EndReason::LosingLine(line)),
+        Outcome::Blocked { .. } | Outcome::Draw => (outcome.winner().map_or(GameResult::Draw, GameResult::won_by), EndReason::BoardFull),
+    };
+
+    // Tags of finished games are optional, lines are not written in them
+    if tagged_result.is_some_and(|val| val != result) || tagged_reason.is_some_and(|val| val != reason.as_str()) {
+        return Err(NotationError::Result);
+    }
+
+    Ok(ParsedGame {
+        players,
+        bot: tag("Bot").map(str::to_string),
+
+        mode,
+        rules,
+        moves: position.moves().to_vec(),
+        symbols: position.symbols().to_vec(),
+        numbers: position.numbers().to_vec(),
+        result,
+        reason,
+
+        date: tag("Date").map(|val| parse_date(val).ok_or(NotationError::Tag("Date"))).transpose()?,
+    })
+}
+
+// Like a1, the row is counted from the top
+fn format_cell(rules: Rules, cell: usize) -> String {
+    format!("{}{}", (b'a' + (cell % rules.width) as u8) as char, cell / rules.width + 1)
+}
+
+// `None` outside of the board
+fn parse_cell(rules: Rules, val: &str) -> Option<usize> {
+    let mut chars = val.chars();
+    let column = chars.next().filter(|val| val.is_ascii_lowercase())? as usize - 'a' as usize;
+    let row: usize = chars.as_str().parse().ok()?;
+
+    (column < rules.width && (1..=rules.height).contains(&row)).then(|| (row - 1) * rules.width + column)
+}
+
+fn format_move(rules: Rules, cell: usize, piece: Piece) -> String {
+    let coordinates = format_cell(rules, cell);
+
+    match piece {
+        Piece::Mark => coordinates,
+        Piece::Symbol(Player::First) => format!("X{}", coordinates),
+        Piece::Symbol(_) => format!("O{}", coordinates),
+        Piece::Number(number) => format!("{}{}", number, coordinates),
+        Piece::Spooky(other) => format!("{}-{}", coordinates, format_cell(rules, other)),
+        Piece::Collapse => format!("!{}", coordinates),
+    }
+}
+
+fn parse_move(rules: Rules, token: &str) -> Option<(usize, Piece)> {
+    if let Some(cell) = token.strip_prefix('!') {
+        return Some((parse_cell(rules, cell)?, Piece::Collapse));
+    }
+
+    if let Some((cell, other)) = token.split_once('-') {
+        return Some((parse_cell(rules, cell)?, Piece::Spooky(parse_cell(rules, other)?)));
+    }
+
+    let start = token.find(|val: char| val.is_ascii_lowercase())?;
+    let cell = parse_cell(rules, &token[start..])?;
+
+    let piece = match &token[..start] {
+        "" => Piece::Mark,
+        "X" => Piece::Symbol(Player::First),
+        "O" => Piece::Symbol(Player::Second),
+        number => Piece::Number(number.parse().ok()?),
+    };
+
+    Some((cell, piece))
+}
+
+// Points of marks like "1-0" or "0-0-1", "1/2" for everyone in a draw
+fn format_result(result: GameResult, marks: usize) -> String {
+    (0..marks)
+        .map(|mark| match (result.winner(), result.loser()) {
+            (Some(winner), _) if winner.index() == mark => "1",
+            (Some(_), _) => "0",
+            (None, Some(loser)) if loser.index() == mark => "0",
+            (None, Some(_)) => "1",
+            (None, None) => "1/2",
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn parse_result(val: &str, marks: usize) -> Option<GameResult> {
+    let players = (0..marks).map(Player::from_index);
+
+    // In games of two players the one who has left has lost to the other one
+    let lost = players.clone().filter(|_| marks > 2).map(GameResult::Lost);
+
+    players.map(GameResult::won_by)
+        .chain(lost)
+        .chain([GameResult::Draw])
+        .find(|&result| format_result(result, marks) == val)
+}
+
+// Like Tag "Value", quotes and backslashes in the value are escaped
+fn parse_tag(val: &str) -> Option<(String, String)> {
+    let (name, value) = val.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => unescaped.push(chars.next()?),
+            char => unescaped.push(char),
+        }
+    }
+
+    Some((name.to_string(), unescaped))
+}
+
+// Like 2026.10.17, days of the proleptic Gregorian calendar in UTC
+fn format_date(time: i64) -> String {
+    let (year, month, day) = civil_from_days(time.div_euclid(86400));
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn parse_date(val: &str) -> Option<i64> {
+    let mut parts = val.split('.').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+
+    if parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    Some(days_from_civil(year, month, day) * 86400)
+}
+
+// Algorithms of Howard Hinnant, years start from March so leap days are the last ones
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::prelude::{ChannelId, UserId};
+
+    use super::*;
+    use crate::ai::Difficulty;
+    use crate::notakto::NotaktoPosition;
+    use crate::numerical::NumericalPosition;
+    use crate::quantum::QuantumPosition;
+    use crate::qubic::QubicPosition;
+    use crate::ultimate::UltimatePosition;
+    use crate::wild::WildPosition;
+
+    // Boards of every mode, with some bigger and multi-player boards of tic-tac-toe
+    fn boards() -> Vec<(Mode, Rules)> {
+        vec![
+            (Mode::Classic, Rules::default()),
+            (Mode::Classic, Rules { misere: true, ..Rules::default() }),
+            (Mode::Classic, Rules { players: 3, ..Rules::new(5, 5, 4).unwrap() }),
+            (Mode::Classic, Rules { players: 4, ..Rules::new(6, 6, 4).unwrap() }),
+            (Mode::Gomoku, Rules::gomoku(false)),
+            (Mode::Gomoku, Rules::gomoku(true)),
+            (Mode::Ultimate, UltimatePosition::rules()),
+            (Mode::Qubic, QubicPosition::rules()),
+            (Mode::Notakto, NotaktoPosition::rules(1)),
+            (Mode::Notakto, NotaktoPosition::rules(3)),
+            (Mode::Wild, Rules::new(4, 4, 3).unwrap()),
+            (Mode::OrderChaos, WildPosition::order_and_chaos_rules()),
+            (Mode::Numerical, NumericalPosition::rules()),
+            (Mode::Quantum, QuantumPosition::rules()),
+        ]
+    }
+
+    // The game is played by the computer until its end
+    fn play(mode: Mode, rules: Rules) -> GameRecord {
+        let mut position = GamePosition::new(mode, rules);
+
+        let outcome = loop {
+            let (cell, piece) = position.choose_move(Difficulty::Easy);
+
+            match position.apply_move(cell, piece).unwrap() {
+                Outcome::Ongoing => (),
+                outcome => break outcome,
+            }
+        };
+
+        let reason = match outcome {
+            Outcome::Win { line, .. } => EndReason::Line(line),
+            Outcome::Loss { line, .. } => EndReason::LosingLine(line),
+            _ => EndReason::BoardFull,
+        };
+
+        let players = ["Alice", "Bob \"the builder\"", "Carol", "Dave"];
+
+        GameRecord {
+            guild_id: None,
+            channel_id: ChannelId::new(1),
+
+            player: (UserId::new(1), players[0].to_string()),
+            player2: (UserId::new(2), players[1].to_string()),
+            others: (2..rules.players).map(|i| (UserId::new(i as u64 + 1), players[i].to_string())).collect(),
+            bot: None,
+
+            mode,
+            rules,
+            moves: position.moves().to_vec(),
+            symbols: position.symbols().to_vec(),
+            numbers: position.numbers().to_vec(),
+            result: outcome.winner().map_or(GameResult::Draw, GameResult::won_by),
+            reason,
+
+            started_at: 1_792_108_800, // 2026.10.16
+            ended_at: 1_792_109_100,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for (mode, rules) in boards() {
+            for _ in 0..10 {
+                let record = play(mode, rules);
+                let text = write(&record).unwrap();
+                let parsed = parse(&text).unwrap_or_else(|err| panic!("{:?}: {}\n{}", err, err.describe(), text));
+
+                assert_eq!(parsed.mode, record.mode);
+                assert_eq!(parsed.rules, record.rules);
+                assert_eq!(parsed.moves, record.moves, "{}", text);
+                assert_eq!(parsed.symbols, record.symbols);
+                assert_eq!(parsed.numbers, record.numbers);
+                assert_eq!(parsed.result, record.result);
+                assert_eq!(parsed.reason, record.reason);
+                assert_eq!(parsed.date, Some(1_792_108_800));
+
+                let names: Vec<_> = [&record.player, &record.player2].into_iter().chain(&record.others).map(|val| val.1.clone()).collect();
+                assert_eq!(parsed.players, names);
+            }
+        }
+    }
+
+    #[test]
+    fn writes_moves_by_coordinates() {
+        let mut record = play(Mode::Classic, Rules::default());
+        record.moves = vec![4, 0, 8, 2, 1, 6, 3];
+        record.result = GameResult::FirstWon;
+        record.reason = EndReason::Resignation;
+
+        let text = write(&record).unwrap();
+
+        assert!(text.contains("[Board \"3x3\"]\n"));
+        assert!(text.contains("[Player2 \"Bob \\\"the builder\\\"\"]\n"));
+        assert!(text.contains("[Date \"2026.10.16\"]\n"));
+        assert!(text.ends_with("\n\n1. b2 a1 2. c3 c1 3. b1 a3 4. a2 1-0\n"));
+    }
+
+    #[test]
+    fn writes_pieces() {
+        let rules = Rules::default();
+
+        assert_eq!(format_move(rules, 5, Piece::Symbol(Player::Second)), "Oc2");
+        assert_eq!(format_move(rules, 8, Piece::Number(7)), "7c3");
+        assert_eq!(format_move(rules, 0, Piece::Spooky(8)), "a1-c3");
+        assert_eq!(format_move(rules, 3, Piece::Collapse), "!a2");
+
+        assert_eq!(parse_move(rules, "12c3"), Some((8, Piece::Number(12))));
+        assert_eq!(parse_move(rules, "d1"), None);
+        assert_eq!(parse_move(rules, "a4"), None);
+    }
+
+    #[test]
+    fn writes_losses_of_players_who_have_left() {
+        assert_eq!(format_result(GameResult::Lost(Player::Second), 3), "1-0-1");
+        assert_eq!(parse_result("1-0-1", 3), Some(GameResult::Lost(Player::Second)));
+        assert_eq!(parse_result("0-1-1-1", 4), Some(GameResult::Lost(Player::First)));
+
+        // Two players can only win against each other
+        assert_eq!(parse_result("0-1", 2), Some(GameResult::SecondWon));
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let text = "[Variant \"classic\"]\n[Board \"3x3\"]\n[WinLength \"3\"]\n[Player1 \"A\"]\n[Player2 \"B\"]\n\n1. b2 b2";
+
+        assert_eq!(parse(text), Err(NotationError::Illegal("b2".to_string(), MoveError::Occupied)));
+    }
+
+    #[test]
+    fn rejects_wrong_results() {
+        let game = "[Variant \"classic\"]\n[Board \"3x3\"]\n[WinLength \"3\"]\n[Player1 \"A\"]\n[Player2 \"B\"]\n";
+
+        // The first player has completed the top row
+        let won = format!("{}[Result \"0-1\"]\n\n1. a1 a2 2. b1 b2 3. c1", game);
+        assert_eq!(parse(&won), Err(NotationError::Result));
+
+        let unfinished = format!("{}\n1. a1 a2", game);
+        assert_eq!(parse(&unfinished), Err(NotationError::Unfinished));
+
+        let resigned = format!("{}[Result \"0-1\"]\n[Termination \"resignation\"]\n\n1. a1 a2 0-1", game);
+        let parsed = parse(&resigned).unwrap();
+        assert_eq!((parsed.result, parsed.reason), (GameResult::SecondWon, EndReason::Resignation));
+    }
+
+    #[test]
+    fn rejects_boards_of_other_modes() {
+        let text = "[Variant \"ultimate\"]\n[Board \"3x3\"]\n[WinLength \"3\"]\n[Player1 \"A\"]\n[Player2 \"B\"]\n\n1. b2";
+
+        assert_eq!(parse(text), Err(NotationError::Tag("Board")));
+    }
+
+    #[test]
+    fn rejects_boards_bigger_than_games() {
+        let game = "[Player1 \"A\"]\n[Player2 \"B\"]\n\n1. b2";
+
+        let big = format!("[Variant \"classic\"]\n[Board \"8x8\"]\n[WinLength \"5\"]\n{}", game);
+        assert_eq!(parse(&big), Err(NotationError::Tag("Board")));
+
+        let gomoku = format!("[Variant \"gomoku\"]\n[Board \"7x7\"]\n[WinLength \"5\"]\n{}", game);
+        assert_eq!(parse(&gomoku), Err(NotationError::Tag("Board")));
+
+        let misere = format!("[Variant \"wild\"]\n[Board \"3x3\"]\n[WinLength \"3\"]\n[Misere \"true\"]\n{}", game);
+        assert_eq!(parse(&misere), Err(NotationError::Tag("Misere")));
+    }
+
+    #[test]
+    fn converts_dates() {
+        for days in [-800_000, -1, 0, 59, 11_016, 20_742, 800_000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+
+        assert_eq!(format_date(0), "1970.01.01");
+        assert_eq!(format_date(951_782_400), "2000.02.29");
+        assert_eq!(parse_date("2000.03.01"), Some(951_868_800));
+        assert_eq!(parse_date("2000.13.01"), None);
+    }
+}
@@ -0,0 +1,214 @@
+// Numerical tic-tac-toe: the first player places odd numbers from 1 to 9
+// and the second player even ones, every number only once. Whoever
+// completes a line of three numbers with the sum of 15 wins, no matter
+// who has placed other numbers of the line.
+use crate::board::{Board, GameCell, MoveError, Outcome, Player, Rules, LINES};
+
+pub const MAGIC_SUM: u8 = 15;
+pub const MAX_NUMBER: u8 = 9;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NumericalPosition {
+    board: Board, // Cells of the player who has placed the number
+    numbers: [u8; 9], // Zero for empty cells
+    moves: Vec<usize>,
+    played: Vec<u8>, // Numbers of the moves
+    outcome: Outcome,
+}
+
+impl Default for NumericalPosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Odd numbers for the first player and even ones for the second
+pub fn numbers_of(player: Player) -> impl Iterator<Item = u8> {
+    (1 + player.index() as u8..=MAX_NUMBER).step_by(2)
+}
+
+pub fn owner_of(number: u8) -> Player {
+    match number % 2 {
+        1 => Player::First,
+        _ => Player::Second,
+    }
+}
+
+impl NumericalPosition {
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(Self::rules()),
+            numbers: [0; 9],
+            moves: Vec::new(),
+            played: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    pub fn rules() -> Rules {
+        Rules::default()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    pub fn played(&self) -> &[u8] {
+        &self.played
+    }
+
+    pub fn number(&self, cell: usize) -> Option<u8> {
+        self.numbers.get(cell).copied().filter(|&val| val != 0)
+    }
+
+    pub fn turn(&self) -> Player {
+        match self.moves.len() % 2 {
+            0 => Player::First,
+            _ => Player::Second,
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    // Numbers of the player which are not placed yet, from the smallest one
+    pub fn remaining_numbers(&self, player: Player) -> Vec<u8> {
+        numbers_of(player)
+            .filter(|number| !self.played.contains(number))
+            .collect()
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_cell(cell).is_ok()
+    }
+
+    pub fn legal_moves(&self) -> Vec<usize> {
+        if self.outcome != Outcome::Ongoing {
+            return Vec::new();
+        }
+
+        (0..self.board.cells().len())
+            .filter(|&cell| self.board.is_empty(cell))
+            .collect()
+    }
+
+    pub fn apply_move(&mut self, cell: usize, number: u8) -> Result<Outcome, MoveError> {
+        self.check_cell(cell)?;
+
+        let player = self.turn();
+
+        if !numbers_of(player).any(|val| val == number) || self.played.contains(&number) {
+            return Err(MoveError::UnavailableNumber);
+        }
+
+        self.board.set(cell, player.cell());
+        self.numbers[cell] = number;
+        self.moves.push(cell);
+        self.played.push(number);
+
+        let line = LINES.iter().position(|line| {
+            line.contains(&cell)
+                && line.iter().all(|&val| self.numbers[val] != 0)
+                && line.iter().map(|&val| self.numbers[val]).sum::<u8>() == MAGIC_SUM
+        });
+
+        self.outcome = if let Some(line) = line {
+            Outcome::Win { player, line }
+        }
+        else if self.board.is_full() {
+            Outcome::Draw
+        }
+        else {
+            Outcome::Ongoing
+        };
+
+        Ok(self.outcome)
+    }
+
+    // Takes back the last move, used by the search of the computer player
+    pub fn undo_move(&mut self) -> Option<(usize, u8)> {
+        let cell = self.moves.pop()?;
+        let number = self.played.pop()?;
+
+        self.board.set(cell, GameCell::None);
+        self.numbers[cell] = 0;
+        self.outcome = Outcome::Ongoing;
+
+        Some((cell, number))
+    }
+
+    fn check_cell(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= self.board.cells().len() {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_own_odd_and_even_numbers() {
+        assert_eq!(numbers_of(Player::First).collect::<Vec<_>>(), [1, 3, 5, 7, 9]);
+        assert_eq!(numbers_of(Player::Second).collect::<Vec<_>>(), [2, 4, 6, 8]);
+        assert_eq!(owner_of(7), Player::First);
+        assert_eq!(owner_of(8), Player::Second);
+
+        let mut position = NumericalPosition::new();
+
+        assert_eq!(position.apply_move(0, 2), Err(MoveError::UnavailableNumber));
+        assert_eq!(position.apply_move(0, 1), Ok(Outcome::Ongoing));
+        assert_eq!(position.apply_move(1, 3), Err(MoveError::UnavailableNumber));
+        assert_eq!(position.remaining_numbers(Player::First), [3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn rejects_reused_numbers() {
+        let mut position = NumericalPosition::new();
+
+        position.apply_move(0, 5).unwrap();
+        position.apply_move(1, 4).unwrap();
+
+        assert_eq!(position.apply_move(2, 5), Err(MoveError::UnavailableNumber));
+        assert_eq!(position.apply_move(0, 7), Err(MoveError::Occupied));
+
+        // Taken back numbers are available again
+        position.undo_move();
+        position.undo_move();
+        assert_eq!(position.apply_move(2, 5), Ok(Outcome::Ongoing));
+    }
+
+    #[test]
+    fn sum_of_fifteen_wins_for_the_mover() {
+        let mut position = NumericalPosition::new();
+
+        // The first row is 8 + 1 + 6, completed by the even player
+        assert_eq!(position.apply_move(1, 1), Ok(Outcome::Ongoing));
+        assert_eq!(position.apply_move(0, 8), Ok(Outcome::Ongoing));
+        assert_eq!(position.apply_move(4, 9), Ok(Outcome::Ongoing));
+        assert_eq!(position.apply_move(2, 6), Ok(Outcome::Win { player: Player::Second, line: 0 }));
+
+        // Full lines with other sums do not win
+        let mut position = NumericalPosition::new();
+
+        for (cell, number) in [(0, 1), (1, 2), (2, 3)] {
+            assert_eq!(position.apply_move(cell, number), Ok(Outcome::Ongoing));
+        }
+    }
+}
@@ -0,0 +1,351 @@
+// Quantum tic-tac-toe: every move places a spooky mark in two cells.
+// Spooky marks are edges of the graph of cells, and the move which closes
+// a cycle is measured: the other player chooses which of its two cells
+// gets the classical mark, then other marks of the cycle follow. Only
+// classical marks make lines. When both players get lines by one
+// measurement, the line whose newest mark is older wins.
+use crate::board::{Board, MoveError, Outcome, Player, Rules, LINES};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QuantumPosition {
+    board: Board, // Only classical marks
+    marks: Vec<[usize; 2]>, // Cells of every spooky mark, the last move into the last free cell is classical at once
+    collapsed: Vec<Option<usize>>, // Cell of every mark after the measurement
+    cycle: Option<usize>, // Mark which has closed a cycle and waits for the measurement
+    actions: Vec<usize>, // Cells of all moves and measurements, in the order they were made
+    outcome: Outcome,
+}
+
+impl Default for QuantumPosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Marks of the first player have even indexes
+pub fn player_of(mark: usize) -> Player {
+    match mark % 2 {
+        0 => Player::First,
+        _ => Player::Second,
+    }
+}
+
+impl QuantumPosition {
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(Self::rules()),
+            marks: Vec::new(),
+            collapsed: Vec::new(),
+            cycle: None,
+            actions: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    pub fn rules() -> Rules {
+        Rules::default()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn marks(&self) -> &[[usize; 2]] {
+        &self.marks
+    }
+
+    #[cfg(test)]
+    pub fn collapsed(&self, mark: usize) -> Option<usize> {
+        self.collapsed[mark]
+    }
+
+    // Mark whose cells have to be chosen from by the player whose turn it is
+    pub fn cycle(&self) -> Option<usize> {
+        self.cycle
+    }
+
+    pub fn actions(&self) -> &[usize] {
+        &self.actions
+    }
+
+    // The measurement of a cycle is made by the player whose turn is next
+    pub fn turn(&self) -> Player {
+        player_of(self.marks.len())
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    // Mark which has become classical in the cell
+    pub fn classical_mark(&self, cell: usize) -> Option<usize> {
+        self.collapsed.iter().position(|&val| val == Some(cell))
+    }
+
+    // Spooky marks which are still in the cell
+    pub fn spooky_marks(&self, cell: usize) -> Vec<usize> {
+        (0..self.marks.len())
+            .filter(|&mark| self.collapsed[mark].is_none() && self.marks[mark].contains(&cell))
+            .collect()
+    }
+
+    pub fn free_cells(&self) -> Vec<usize> {
+        (0..self.board.cells().len())
+            .filter(|&cell| self.board.is_empty(cell))
+            .collect()
+    }
+
+    // A move in the last free cell is classical, otherwise two cells are needed
+    pub fn needs_two_cells(&self) -> bool {
+        self.free_cells().len() > 1
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_cell(cell).is_ok()
+    }
+
+    // Pairs of cells, or the last free cell twice
+    pub fn legal_moves(&self) -> Vec<[usize; 2]> {
+        if self.outcome != Outcome::Ongoing || self.cycle.is_some() {
+            return Vec::new();
+        }
+
+        let cells = self.free_cells();
+
+        if let [cell] = cells[..] {
+            return vec![[cell, cell]];
+        }
+
+        cells.iter()
+            .enumerate()
+            .flat_map(|(i, &first)| cells[i + 1..].iter().map(move |&second| [first, second]))
+            .collect()
+    }
+
+    // The spooky mark of the player whose turn it is in the `cell` and the `other` cell,
+    // both cells are the same for the classical move into the last free cell
+    pub fn apply_move(&mut self, cell: usize, other: usize) -> Result<Outcome, MoveError> {
+        self.check_cell(cell)?;
+        self.check_cell(other)?;
+
+        if (cell == other) == self.needs_two_cells() {
+            return Err(MoveError::SameCell);
+        }
+
+        let mark = self.marks.len();
+        let is_cycle = cell != other && self.is_connected(cell, other);
+
+        self.marks.push([cell, other]);
+        self.collapsed.push(None);
+        self.actions.extend(if cell == other { vec![cell] } else { vec![cell, other] });
+
+        if cell == other {
+            self.collapse_mark(mark, cell);
+            self.outcome = self.check_outcome();
+        }
+        else if is_cycle {
+            self.cycle = Some(mark);
+        }
+
+        Ok(self.outcome)
+    }
+
+    // Measurement of the mark which has closed the cycle into one of its cells
+    pub fn collapse(&mut self, cell: usize) -> Result<Outcome, MoveError> {
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        let Some(mark) = self.cycle.filter(|&mark| self.marks[mark].contains(&cell)) else {
+            return Err(MoveError::InvalidCollapse);
+        };
+
+        self.cycle = None;
+        self.actions.push(cell);
+        self.collapse_mark(mark, cell);
+        self.outcome = self.check_outcome();
+
+        Ok(self.outcome)
+    }
+
+    // Marks which share a cell with a classical mark collapse into their other cells
+    fn collapse_mark(&mut self, mark: usize, cell: usize) {
+        let mut queue = vec![(mark, cell)];
+
+        while let Some((mark, cell)) = queue.pop() {
+            if self.collapsed[mark].is_some() {
+                continue;
+            }
+
+            self.collapsed[mark] = Some(cell);
+            self.board.set(cell, player_of(mark).cell());
+
+            for other in self.spooky_marks(cell) {
+                let [first, second] = self.marks[other];
+                queue.push((other, if first == cell { second } else { first }));
+            }
+        }
+    }
+
+    // Cells are connected by a chain of spooky marks
+    fn is_connected(&self, from: usize, to: usize) -> bool {
+        let mut visited = vec![from];
+        let mut queue = vec![from];
+
+        while let Some(cell) = queue.pop() {
+            if cell == to {
+                return true;
+            }
+
+            for mark in self.spooky_marks(cell) {
+                for next in self.marks[mark] {
+                    if !visited.contains(&next) {
+                        visited.push(next);
+                        queue.push(next);
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    // The line with the oldest newest mark wins
+    fn check_outcome(&self) -> Outcome {
+        let winner = LINES.iter()
+            .enumerate()
+            .filter_map(|(line, cells)| {
+                let marks = cells.map(|cell| self.classical_mark(cell));
+
+                if marks.iter().any(Option::is_none) {
+                    return None;
+                }
+
+                let marks = marks.map(Option::unwrap);
+                let player = player_of(marks[0]);
+
+                marks.iter()
+                    .all(|&mark| player_of(mark) == player)
+                    .then(|| (marks.iter().copied().max().unwrap(), line))
+            })
+            .min();
+
+        // The newest mark of the line tells the player
+        match winner {
+            Some((mark, line)) => Outcome::Win { player: player_of(mark), line },
+            None if self.board.is_full() => Outcome::Draw,
+            None => Outcome::Ongoing,
+        }
+    }
+
+    fn check_cell(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= self.board.cells().len() {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if self.cycle.is_some() {
+            return Err(MoveError::CollapsePending);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::GameCell;
+
+    #[test]
+    fn detects_cycles() {
+        let mut position = QuantumPosition::new();
+
+        position.apply_move(0, 1).unwrap();
+        position.apply_move(1, 2).unwrap();
+        assert_eq!(position.cycle(), None);
+        assert_eq!(position.apply_move(3, 3), Err(MoveError::SameCell));
+
+        // The third mark closes the triangle of cells
+        position.apply_move(2, 0).unwrap();
+        assert_eq!(position.cycle(), Some(2));
+        assert_eq!(position.turn(), Player::Second);
+        assert_eq!(position.apply_move(4, 5), Err(MoveError::CollapsePending));
+        assert!(position.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn collapses_cycles() {
+        let mut position = QuantumPosition::new();
+
+        position.apply_move(0, 1).unwrap();
+        position.apply_move(1, 2).unwrap();
+        position.apply_move(2, 0).unwrap();
+
+        assert_eq!(position.collapse(4), Err(MoveError::InvalidCollapse));
+        assert_eq!(position.collapse(0), Ok(Outcome::Ongoing));
+
+        // Other marks of the cycle follow into their other cells
+        assert_eq!(position.collapsed(2), Some(0));
+        assert_eq!(position.collapsed(0), Some(1));
+        assert_eq!(position.collapsed(1), Some(2));
+        assert_eq!(&position.board().cells()[..3], &[GameCell::First, GameCell::First, GameCell::Second]);
+        assert_eq!(position.cycle(), None);
+        assert_eq!(position.actions(), &[0, 1, 1, 2, 2, 0, 0]);
+    }
+
+    #[test]
+    fn detects_wins() {
+        let mut position = QuantumPosition::new();
+
+        for (first, second, collapse) in [
+            (0, 1, None), // X
+            (3, 4, None), // O
+            (1, 0, Some(1)), // X, measured by O
+            (3, 4, Some(3)), // O, measured by X
+            (2, 6, None), // X
+            (5, 7, None), // O
+            (2, 6, Some(2)), // X, measured by O
+        ] {
+            let mut outcome = position.apply_move(first, second).unwrap();
+
+            if let Some(cell) = collapse {
+                outcome = position.collapse(cell).unwrap();
+            }
+
+            if position.marks().len() < 7 {
+                assert_eq!(outcome, Outcome::Ongoing);
+            }
+            else {
+                assert_eq!(outcome, Outcome::Win { player: Player::First, line: 0 });
+            }
+        }
+
+        assert_eq!(position.apply_move(8, 7), Err(MoveError::GameOver));
+    }
+
+    #[test]
+    fn last_move_is_classical() {
+        let mut position = QuantumPosition::new();
+
+        // Cells are filled by cycles of two marks, without any line
+        for [first, second] in [[1, 0], [4, 2], [5, 3], [6, 8]] {
+            position.apply_move(first, second).unwrap();
+            position.apply_move(first, second).unwrap();
+            position.collapse(first).unwrap();
+        }
+
+        assert_eq!(position.free_cells(), vec![7]);
+        assert!(!position.needs_two_cells());
+        assert_eq!(position.legal_moves(), vec![[7, 7]]);
+        assert_eq!(position.apply_move(7, 7), Ok(Outcome::Draw));
+        assert_eq!(position.classical_mark(7), Some(8));
+    }
+}
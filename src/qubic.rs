@@ -0,0 +1,256 @@
+// 3D tic-tac-toe on the 4×4×4 cube, four marks in a row win in any direction.
+//
+// Cells are numbered layer by layer and row by row inside of a layer, so the
+// cube is the 4×16 board of four 4×4 layers stacked from the top to the bottom.
+use crate::board::{Board, GameCell, MoveError, Outcome, Player, Rules};
+
+// Cells in a row, rows in a layer and layers in the cube
+pub const SIZE: usize = 4;
+
+const LAYER_CELLS: usize = SIZE * SIZE;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QubicPosition {
+    board: Board, // Layers stacked from the top to the bottom
+    lines: Vec<[usize; SIZE]>,
+    moves: Vec<usize>,
+    outcome: Outcome, // `line` of the win is an index in the `lines`
+}
+
+impl Default for QubicPosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn layer_of(cell: usize) -> usize {
+    cell / LAYER_CELLS
+}
+
+// The same cell in another layer
+pub fn move_to_layer(cell: usize, layer: usize) -> usize {
+    layer * LAYER_CELLS + cell % LAYER_CELLS
+}
+
+// All 76 lines: 48 rows, columns and pillars, 24 diagonals of planes
+// and 4 diagonals of the cube. Directions are ordered by their steps.
+pub fn lines() -> Vec<[usize; SIZE]> {
+    let mut lines = Vec::new();
+    let size = SIZE as i32;
+
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                // Every line once, in one of the two opposite directions
+                let is_forward = (dz, dy, dx) > (0, 0, 0);
+
+                if !is_forward {
+                    continue;
+                }
+
+                for z in 0..size {
+                    for y in 0..size {
+                        for x in 0..size {
+                            let end = (z + dz * (size - 1), y + dy * (size - 1), x + dx * (size - 1));
+                            let fits = [end.0, end.1, end.2].iter().all(|val| (0..size).contains(val));
+
+                            if !fits {
+                                continue;
+                            }
+
+                            lines.push(std::array::from_fn(|i| {
+                                let i = i as i32;
+                                ((z + dz * i) * size * size + (y + dy * i) * size + x + dx * i) as usize
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    lines
+}
+
+impl QubicPosition {
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(Self::rules()),
+            lines: lines(),
+            moves: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    // Size of the board of stacked layers
+    pub fn rules() -> Rules {
+        Rules::new(SIZE, SIZE * SIZE, SIZE).unwrap()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn lines(&self) -> &[[usize; SIZE]] {
+        &self.lines
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    pub fn turn(&self) -> Player {
+        match self.moves.len() % 2 {
+            0 => Player::First,
+            _ => Player::Second,
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_move(cell).is_ok()
+    }
+
+    pub fn legal_moves(&self) -> Vec<usize> {
+        if self.outcome != Outcome::Ongoing {
+            return Vec::new();
+        }
+
+        (0..self.board.cells().len())
+            .filter(|&cell| self.board.is_empty(cell))
+            .collect()
+    }
+
+    pub fn apply_move(&mut self, cell: usize) -> Result<Outcome, MoveError> {
+        self.check_move(cell)?;
+
+        let player = self.turn();
+
+        self.board.set(cell, player.cell());
+        self.moves.push(cell);
+
+        let line = self.lines.iter()
+            .position(|line| line.contains(&cell) && line.iter().all(|&val| self.board.get(val) == player.cell()));
+
+        self.outcome = if let Some(line) = line {
+            Outcome::Win { player, line }
+        }
+        else if self.board.is_full() {
+            Outcome::Draw
+        }
+        else {
+            Outcome::Ongoing
+        };
+
+        Ok(self.outcome)
+    }
+
+    // Takes back the last move, used by the search of the computer player
+    pub fn undo_move(&mut self) -> Option<usize> {
+        let cell = self.moves.pop()?;
+
+        self.board.set(cell, GameCell::None);
+        self.outcome = Outcome::Ongoing;
+
+        Some(cell)
+    }
+
+    fn check_move(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= self.board.cells().len() {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn has_76_lines() {
+        let lines = lines();
+        assert_eq!(lines.len(), 76);
+
+        let mut sorted: Vec<_> = lines.iter()
+            .map(|line| {
+                let mut line = *line;
+                line.sort();
+                line
+            })
+            .collect();
+
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 76);
+
+        // Corners and the inner cube are on seven lines, other cells on four
+        for cell in 0..SIZE * LAYER_CELLS {
+            let count = lines.iter().filter(|line| line.contains(&cell)).count();
+            let is_edge = |val: usize| val == 0 || val == SIZE - 1;
+            let coords = [cell / LAYER_CELLS, cell / SIZE % SIZE, cell % SIZE];
+
+            let expected = if coords.iter().all(|&val| is_edge(val)) || coords.iter().all(|&val| !is_edge(val)) {
+                7
+            }
+            else {
+                4
+            };
+
+            assert_eq!(count, expected, "cell {}", cell);
+        }
+    }
+
+    // X plays the `line`, O answers in the free cells of the first layer
+    fn play_line(line: [usize; SIZE]) -> (QubicPosition, Outcome) {
+        let mut position = QubicPosition::new();
+        let mut replies = (0..LAYER_CELLS).filter(|cell| !line.contains(cell));
+        let mut outcome = Outcome::Ongoing;
+
+        for (i, &cell) in line.iter().enumerate() {
+            outcome = position.apply_move(cell).unwrap();
+
+            if i + 1 < SIZE {
+                position.apply_move(replies.next().unwrap()).unwrap();
+            }
+        }
+
+        (position, outcome)
+    }
+
+    #[test]
+    fn detects_wins() {
+        for line in [[1, 17, 33, 49], [0, 21, 42, 63], [3, 18, 33, 48], [12, 25, 38, 51]] {
+            let (position, outcome) = play_line(line);
+            let id = position.lines().iter().position(|val| val == &line).unwrap();
+
+            assert_eq!(outcome, Outcome::Win { player: Player::First, line: id }, "{:?}", line);
+            assert!(position.legal_moves().is_empty());
+        }
+    }
+
+    #[test]
+    fn undoes_moves() {
+        let (mut position, _) = play_line([0, 21, 42, 63]);
+
+        assert_eq!(position.undo_move(), Some(63));
+        assert_eq!(position.outcome(), Outcome::Ongoing);
+        assert_eq!(position.turn(), Player::First);
+        assert_eq!(position.apply_move(0), Err(MoveError::Occupied));
+        assert_eq!(position.apply_move(64), Err(MoveError::OutOfBounds));
+        assert_eq!(layer_of(63), 3);
+        assert_eq!(move_to_layer(63, 0), 15);
+    }
+}
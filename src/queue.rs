@@ -0,0 +1,197 @@
+use std::collections::{HashMap, VecDeque};
+
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+// Where players are matched with each other
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum QueueScope {
+    #[default]
+    Guild, // With anyone on the server
+    Channel, // Only with players in the same channel
+}
+
+impl QueueScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueueScope::Guild => "guild",
+            QueueScope::Channel => "channel",
+        }
+    }
+
+    pub fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "guild" => Some(QueueScope::Guild),
+            "channel" => Some(QueueScope::Channel),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct QueueKey {
+    guild_id: Option<GuildId>,
+    channel_id: Option<ChannelId>,
+}
+
+impl QueueKey {
+    pub fn new(scope: QueueScope, guild_id: Option<GuildId>, channel_id: ChannelId) -> Self {
+        match (scope, guild_id) {
+            (QueueScope::Guild, Some(guild_id)) => Self {
+                guild_id: Some(guild_id),
+                channel_id: None,
+            },
+
+            // Direct messages have no guild, so they are always matched by channel
+            _ => Self {
+                guild_id,
+                channel_id: Some(channel_id),
+            },
+        }
+    }
+}
+
+pub struct QueueEntry<T> {
+    pub user_id: UserId,
+    pub joined_at: i64, // Unix time in seconds
+    pub value: T,
+}
+
+// Separate FIFO queue for every key
+pub struct Queue<T> {
+    queues: HashMap<QueueKey, VecDeque<QueueEntry<T>>>,
+}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Self {
+            queues: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    pub fn push(&mut self, key: QueueKey, entry: QueueEntry<T>) {
+        self.queues.entry(key).or_default().push_back(entry);
+    }
+
+    // Takes `count` players who are waiting for the longest time among matching ones,
+    // nothing if there are not enough of them, like in a lobby of a bigger game
+    pub fn pop(&mut self, key: QueueKey, count: usize, is_matching: impl Fn(&T) -> bool) -> Option<Vec<QueueEntry<T>>> {
+        let queue = self.queues.get_mut(&key)?;
+        let positions: Vec<_> = (0..queue.len())
+            .filter(|&pos| is_matching(&queue[pos].value))
+            .take(count)
+            .collect();
+
+        if positions.len() < count {
+            return None;
+        }
+
+        // Removing from the end keeps positions of other entries
+        let mut entries: Vec<_> = positions.iter()
+            .rev()
+            .filter_map(|&pos| queue.remove(pos))
+            .collect();
+
+        entries.reverse();
+
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+
+        Some(entries)
+    }
+
+    // Matching players who are waiting, from the oldest to the newest
+    pub fn matching(&self, key: QueueKey, is_matching: impl Fn(&T) -> bool) -> Vec<&QueueEntry<T>> {
+        self.queues.get(&key)
+            .map(|queue| queue.iter().filter(|entry| is_matching(&entry.value)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn remove(&mut self, user_id: UserId) -> Option<QueueEntry<T>> {
+        let (&key, queue) = self.queues.iter_mut()
+            .find(|(_, queue)| queue.iter().any(|entry| entry.user_id == user_id))?;
+
+        let pos = queue.iter().position(|entry| entry.user_id == user_id)?;
+        let entry = queue.remove(pos);
+
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+
+        entry
+    }
+
+    pub fn contains(&self, user_id: UserId) -> bool {
+        self.queues.values()
+            .flatten()
+            .any(|entry| entry.user_id == user_id)
+    }
+
+    // Entries of all queues of the guild or of the channel for direct messages,
+    // sorted from the oldest to the newest
+    pub fn entries(&self, guild_id: Option<GuildId>, channel_id: ChannelId) -> Vec<&QueueEntry<T>> {
+        let mut entries: Vec<_> = self.queues.iter()
+            .filter(|(key, _)| match guild_id {
+                Some(_) => key.guild_id == guild_id,
+                None => key.guild_id.is_none() && key.channel_id == Some(channel_id),
+            })
+            .flat_map(|(_, queue)| queue)
+            .collect();
+
+        entries.sort_by_key(|entry| entry.joined_at);
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user_id: u64, value: usize) -> QueueEntry<usize> {
+        QueueEntry { user_id: UserId::new(user_id), joined_at: user_id as i64, value }
+    }
+
+    fn user_ids(entries: &[QueueEntry<usize>]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.user_id.get()).collect()
+    }
+
+    #[test]
+    fn pops_matching_players_in_order() {
+        let key = QueueKey::new(QueueScope::Guild, Some(GuildId::new(1)), ChannelId::new(2));
+        let mut queue = Queue::default();
+
+        // Values are sizes of boards, only the same ones match
+        for (user_id, value) in [(1, 3), (2, 4), (3, 3), (4, 3), (5, 4)] {
+            queue.push(key, entry(user_id, value));
+        }
+
+        assert!(queue.pop(key, 3, |&val| val == 4).is_none());
+        assert_eq!(user_ids(&queue.pop(key, 2, |&val| val == 3).unwrap()), [1, 3]);
+        assert_eq!(user_ids(&queue.pop(key, 1, |&val| val == 4).unwrap()), [2]);
+
+        let rest: Vec<u64> = queue.matching(key, |_| true).iter().map(|entry| entry.user_id.get()).collect();
+        assert_eq!(rest, [4, 5]);
+    }
+
+    #[test]
+    fn separates_guilds_and_channels() {
+        let guild = QueueKey::new(QueueScope::Guild, Some(GuildId::new(1)), ChannelId::new(2));
+        let channel = QueueKey::new(QueueScope::Channel, Some(GuildId::new(1)), ChannelId::new(2));
+        let mut queue = Queue::default();
+
+        assert_eq!(guild, QueueKey::new(QueueScope::Guild, Some(GuildId::new(1)), ChannelId::new(3)));
+        assert_ne!(guild, channel);
+
+        queue.push(guild, entry(1, 3));
+        queue.push(channel, entry(2, 3));
+
+        assert!(queue.pop(channel, 2, |_| true).is_none());
+        assert_eq!(queue.entries(Some(GuildId::new(1)), ChannelId::new(2)).len(), 2);
+
+        assert!(queue.remove(UserId::new(1)).is_some());
+        assert!(!queue.contains(UserId::new(1)));
+        assert!(queue.contains(UserId::new(2)));
+    }
+}
@@ -0,0 +1,333 @@
+use serenity::all::{ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction, ResolvedValue};
+use serenity::builder::{CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage};
+use serenity::model::mention::Mentionable;
+use serenity::model::prelude::GuildId;
+use serenity::prelude::Context;
+
+use crate::storage::Storage;
+
+// Increase it after any change of the formula,
+// so ratings are recomputed from the game history on the next start
+pub const FORMULA_VERSION: i64 = 1;
+
+pub const INITIAL_RATING: f64 = 1500.0;
+
+// Ratings of players with less games are changing faster
+pub const PROVISIONAL_GAMES: i64 = 10;
+
+const PROVISIONAL_K_FACTOR: f64 = 40.0;
+const K_FACTOR: f64 = 20.0;
+
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+const HISTORY_SIZE: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rating {
+    pub rating: f64,
+    pub games: i64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+impl Rating {
+    pub fn is_provisional(&self) -> bool {
+        self.games < PROVISIONAL_GAMES
+    }
+
+    // Rating with a question mark for provisional ones
+    pub fn display(&self) -> String {
+        if self.is_provisional() {
+            format!("{:.0}?", self.rating)
+        }
+        else {
+            format!("{:.0}", self.rating)
+        }
+    }
+
+    fn k_factor(&self) -> f64 {
+        if self.is_provisional() {
+            PROVISIONAL_K_FACTOR
+        }
+        else {
+            K_FACTOR
+        }
+    }
+}
+
+// Elo update, `score` is 1.0 for a win of the first player,
+// 0.5 for a draw and 0.0 for a win of the second player
+pub fn update(first: Rating, second: Rating, score: f64) -> (Rating, Rating) {
+    let expected = 1.0 / (1.0 + 10f64.powf((second.rating - first.rating) / 400.0));
+
+    let first = Rating {
+        rating: first.rating + first.k_factor() * (score - expected),
+        games: first.games + 1,
+    };
+
+    let second = Rating {
+        rating: second.rating + second.k_factor() * (expected - score),
+        games: second.games + 1,
+    };
+
+    (first, second)
+}
+
+pub fn register_leaderboard() -> CreateCommand {
+    CreateCommand::new("leaderboard")
+        .description("Shows the best players of the server")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "page", "Page of the leaderboard")
+                .min_int_value(1)
+        )
+}
+
+pub fn register_rating() -> CreateCommand {
+    CreateCommand::new("rating")
+        .description("Shows the rating and its history of the player")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::User, "user", "Whose rating to show, yours by default")
+        )
+}
+
+pub async fn leaderboard_command(ctx: Context, interaction: CommandInteraction, storage: &Storage) {
+    let Some(guild_id) = interaction.guild_id else {
+        return;
+    };
+
+    let page = interaction.data.options().into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Integer(val) => Some(val as usize - 1),
+            _ => None,
+        })
+        .unwrap_or(0);
+
+    let message = match leaderboard_message(storage, guild_id, page) {
+        Some(val) => val,
+        None => CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .content("Failed to load the leaderboard!"),
+    };
+
+    interaction.create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await
+        .unwrap();
+}
+
+// Page buttons of the leaderboard
+pub async fn leaderboard_component(ctx: Context, component: ComponentInteraction, storage: &Storage) {
+    let Some(guild_id) = component.guild_id else {
+        return;
+    };
+
+    let page = component.data.custom_id.strip_prefix("leaderboard_")
+        .and_then(|val| val.parse().ok())
+        .unwrap();
+
+    let response = match leaderboard_message(storage, guild_id, page) {
+        Some(val) => CreateInteractionResponse::UpdateMessage(val),
+        None => CreateInteractionResponse::Acknowledge,
+    };
+
+    component.create_response(&ctx.http, response).await.unwrap();
+}
+
+fn leaderboard_message(storage: &Storage, guild_id: GuildId, page: usize) -> Option<CreateInteractionResponseMessage> {
+    let loaded = storage.leaderboard_size(guild_id).and_then(|count| {
+        let pages = count.div_ceil(LEADERBOARD_PAGE_SIZE).max(1);
+        let page = page.min(pages - 1);
+
+        let ratings = storage.leaderboard(guild_id, page * LEADERBOARD_PAGE_SIZE, LEADERBOARD_PAGE_SIZE)?;
+        Ok((ratings, pages, page))
+    });
+
+    let (ratings, pages, page) = match loaded {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Failed to load the leaderboard: {err:?}");
+            return None;
+        }
+    };
+
+    let description = if ratings.is_empty() {
+        "No one has played rated games yet.".to_string()
+    }
+    else {
+        ratings.iter()
+            .enumerate()
+            .map(|(i, (user_id, rating))| format!(
+                "**{}.** {} — {} ({} games)",
+                page * LEADERBOARD_PAGE_SIZE + i + 1,
+                user_id.mention(),
+                rating.display(),
+                rating.games,
+            ))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let previous = CreateButton::new(format!("leaderboard_{}", page.saturating_sub(1)))
+        .label("←")
+        .style(ButtonStyle::Secondary)
+        .disabled(page == 0);
+
+    let next = CreateButton::new(format!("leaderboard_{}", page + 1))
+        .label("→")
+        .style(ButtonStyle::Secondary)
+        .disabled(page + 1 >= pages);
+
+    Some(CreateInteractionResponseMessage::new()
+        .embed(
+            CreateEmbed::new()
+                .title("Leaderboard")
+                .description(description)
+                .footer(CreateEmbedFooter::new(format!(
+                    "Page {} of {}. Ratings marked with ? are provisional.",
+                    page + 1,
+                    pages,
+                )))
+        )
+        .components(vec![CreateActionRow::Buttons(vec![previous, next])])
+    )
+}
+
+pub async fn rating_command(ctx: Context, interaction: CommandInteraction, storage: &Storage) {
+    let Some(guild_id) = interaction.guild_id else {
+        return;
+    };
+
+    let (user, nick) = interaction.data.options().into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::User(user, member) => Some((user.clone(), member.and_then(|val| val.nick.clone()))),
+            _ => None,
+        })
+        .unwrap_or_else(|| (
+            interaction.user.clone(),
+            interaction.member.as_ref().and_then(|val| val.nick.clone()),
+        ));
+
+    let name = nick.unwrap_or_else(|| user.name.clone());
+
+    let loaded = storage.rating(guild_id, user.id)
+        .and_then(|rating| Ok((rating, storage.rating_history(guild_id, user.id, HISTORY_SIZE)?)));
+
+    let (rating, history) = match loaded {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Failed to load the rating: {err:?}");
+
+            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content("Failed to load the rating!")
+            ))
+            .await
+            .unwrap();
+
+            return;
+        }
+    };
+
+    let icon_url = user.avatar_url().unwrap_or_else(|| user.default_avatar_url());
+
+    let mut embed = CreateEmbed::new()
+        .author(
+            CreateEmbedAuthor::new(name.clone())
+                .icon_url(icon_url)
+        )
+        .title(format!("Rating of {}", name));
+
+    match rating {
+        Some(rating) => {
+            let history = history.iter()
+                .map(|change| format!(
+                    "<t:{}:d> vs {}: **{:+.0}** → {:.0}",
+                    change.ended_at,
+                    change.opponent_name,
+                    change.change,
+                    change.rating,
+                ))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            embed = embed
+                .field("Rating", rating.display(), true)
+                .field("Rated games", rating.games.to_string(), true)
+                .field("Recent changes", history, false);
+
+            if rating.is_provisional() {
+                embed = embed.footer(CreateEmbedFooter::new(format!(
+                    "The rating is provisional until {} rated games are played.",
+                    PROVISIONAL_GAMES,
+                )));
+            }
+        }
+        None => {
+            embed = embed.description(format!("{} has not played rated games yet.", name));
+        }
+    }
+
+    interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+    ))
+    .await
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rating(rating: Rating, expected: f64, games: i64) {
+        assert!((rating.rating - expected).abs() < 1e-6, "{} is not {}", rating.rating, expected);
+        assert_eq!(rating.games, games);
+    }
+
+    #[test]
+    fn provisional_ratings_change_faster() {
+        let new = Rating::default();
+        let established = Rating { rating: INITIAL_RATING, games: PROVISIONAL_GAMES };
+
+        assert!(new.is_provisional());
+        assert!(!established.is_provisional());
+        assert_eq!(new.display(), "1500?");
+        assert_eq!(established.display(), "1500");
+
+        // Equal ratings expect 0.5, so a win moves them by a half of K
+        let (first, second) = update(new, new, 1.0);
+        assert_rating(first, 1520.0, 1);
+        assert_rating(second, 1480.0, 1);
+
+        let (first, second) = update(established, established, 1.0);
+        assert_rating(first, 1510.0, 11);
+        assert_rating(second, 1490.0, 11);
+
+        let (first, second) = update(new, established, 0.0);
+        assert_rating(first, 1480.0, 1);
+        assert_rating(second, 1510.0, 11);
+    }
+
+    #[test]
+    fn stronger_players_expect_more() {
+        let stronger = Rating { rating: 1600.0, games: 20 };
+        let weaker = Rating { rating: 1400.0, games: 20 };
+
+        // The expected score is 1 / (1 + 10^(-200 / 400)) = 0.7597469
+        let (first, second) = update(stronger, weaker, 0.5);
+        assert_rating(first, 1594.805061, 21);
+        assert_rating(second, 1405.194939, 21);
+
+        let (first, second) = update(weaker, stronger, 1.0);
+        assert_rating(first, 1415.194939, 21);
+        assert_rating(second, 1584.805061, 21);
+    }
+}
@@ -0,0 +1,285 @@
+use serenity::all::{CommandInteraction, CommandOptionType, ResolvedValue};
+use serenity::builder::{CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage};
+use serenity::model::prelude::UserId;
+use serenity::prelude::Context;
+
+use crate::board::{Player, CELL_NAMES, LINE_NAMES};
+use crate::storage::{GameRecord, GameResult, Storage};
+use crate::variant::Mode;
+
+#[derive(Default)]
+pub struct PlayerStats {
+    pub wins: usize,
+    pub losses: usize,
+    pub draws: usize,
+
+    pub games_as_x: usize,
+    pub games_as_o: usize,
+
+    pub longest_win_streak: usize,
+    pub favourite_win_line: Option<usize>, // Index in the board::LINES
+    pub favourite_first_cell: Option<usize>,
+}
+
+impl PlayerStats {
+    // Games must be sorted from the oldest to the newest.
+    // Games against the computer are skipped.
+    pub fn compute(user_id: UserId, games: &[GameRecord]) -> Self {
+        let mut stats = Self::default();
+
+        let mut win_streak = 0;
+        let mut win_lines = [0; LINE_NAMES.len()];
+        let mut first_cells = [0; CELL_NAMES.len()];
+
+        for game in games.iter().filter(|game| game.bot.is_none()) {
+            // Players of the record are in the order of their moves, teammates play the same mark
+            let seat = [&game.player, &game.player2].into_iter()
+                .chain(&game.others)
+                .position(|val| val.0 == user_id)
+                .unwrap_or(1);
+            let mark = Player::from_index(seat % game.rules.players);
+
+            match mark {
+                Player::First => stats.games_as_x += 1,
+                Player::Second => stats.games_as_o += 1,
+                _ => (),
+            }
+
+            // Cells and lines have names only on the classic board
+            let is_classic = game.mode == Mode::Classic && game.rules.is_classic();
+
+            // Moves of the players are taking turns, X moves first
+            let first_move = game.moves.get(seat);
+            if let (Some(&cell), true) = (first_move, is_classic) {
+                first_cells[cell] += 1;
+            }
+
+            match game.result {
+                // Everyone but the player who has left a game of more than two players has won
+                result if result.winner() == Some(mark) || result.loser().is_some_and(|val| val != mark) => {
+                    stats.wins += 1;
+                    win_streak += 1;
+                    stats.longest_win_streak = stats.longest_win_streak.max(win_streak);
+
+                    if let (Some(line), true) = (game.reason.win_line(), is_classic) {
+                        win_lines[line] += 1;
+                    }
+                }
+                GameResult::Draw => {
+                    stats.draws += 1;
+                    win_streak = 0;
+                }
+                _ => {
+                    stats.losses += 1;
+                    win_streak = 0;
+                }
+            }
+        }
+
+        stats.favourite_win_line = most_common(&win_lines);
+        stats.favourite_first_cell = most_common(&first_cells);
+
+        stats
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.losses + self.draws
+    }
+
+    // In percents
+    pub fn win_rate(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+
+        self.wins as f64 * 100.0 / self.games() as f64
+    }
+}
+
+// Index of the biggest non zero count, the first one for equal counts
+fn most_common(counts: &[usize]) -> Option<usize> {
+    counts.iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(&a.0)))
+        .map(|(i, _)| i)
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("stats")
+        .description("Shows statistics of the player")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::User, "user", "Whose statistics to show, yours by default")
+        )
+}
+
+pub async fn command(ctx: Context, interaction: CommandInteraction, storage: &Storage) {
+    let (user, member) = interaction.data.options().into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::User(user, member) => Some((user.clone(), member.and_then(|val| val.nick.clone()))),
+            _ => None,
+        })
+        .unwrap_or_else(|| (
+            interaction.user.clone(),
+            interaction.member.as_ref().and_then(|val| val.nick.clone()),
+        ));
+
+    let name = member.unwrap_or_else(|| user.name.clone());
+
+    let games = match storage.games_of_user(user.id) {
+        Ok(val) => val,
+        Err(err) => {
+            eprintln!("Failed to load games: {err:?}");
+
+            interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content("Failed to load the statistics!")
+            ))
+            .await
+            .unwrap();
+
+            return;
+        }
+    };
+
+    let stats = PlayerStats::compute(user.id, &games);
+
+    let icon_url = user.avatar_url().unwrap_or_else(|| user.default_avatar_url());
+
+    let mut embed = CreateEmbed::new()
+        .author(
+            CreateEmbedAuthor::new(name.clone())
+                .icon_url(icon_url)
+        )
+        .title(format!("Statistics of {}", name))
+        .footer(CreateEmbedFooter::new("Games against the computer are not counted. Favourite cells and lines are only for 3×3 games."));
+
+    if stats.games() == 0 {
+        embed = embed.description(format!("{} has not played any games yet.", name));
+    }
+    else {
+        embed = embed
+            .field("Wins", stats.wins.to_string(), true)
+            .field("Losses", stats.losses.to_string(), true)
+            .field("Draws", stats.draws.to_string(), true)
+            .field("Win rate", format!("{:.1}%", stats.win_rate()), true)
+            .field("Games as X", stats.games_as_x.to_string(), true)
+            .field("Games as O", stats.games_as_o.to_string(), true)
+            .field("Longest win streak", stats.longest_win_streak.to_string(), true)
+            .field("Favourite winning line", stats.favourite_win_line.map_or("-", |val| LINE_NAMES[val]), true)
+            .field("Favourite first cell", stats.favourite_first_cell.map_or("-", |val| CELL_NAMES[val]), true);
+    }
+
+    interaction.create_response(&ctx.http, CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .embed(embed)
+    ))
+    .await
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::prelude::ChannelId;
+
+    use super::*;
+    use crate::board::Rules;
+    use crate::storage::EndReason;
+
+    const USER_ID: UserId = UserId::new(10);
+
+    // The user plays X against the other player, or O when `player2` is the user
+    fn game(player: u64, player2: u64, result: GameResult, moves: Vec<usize>) -> GameRecord {
+        GameRecord {
+            guild_id: None,
+            channel_id: ChannelId::new(1),
+
+            player: (UserId::new(player), "X".to_string()),
+            player2: (UserId::new(player2), "O".to_string()),
+            others: Vec::new(),
+            bot: None,
+
+            mode: Mode::Classic,
+            rules: Rules::default(),
+            moves,
+            symbols: Vec::new(),
+            numbers: Vec::new(),
+            result,
+            reason: EndReason::BoardFull,
+
+            started_at: 0,
+            ended_at: 0,
+        }
+    }
+
+    fn won_by_line(mut game: GameRecord, line: usize) -> GameRecord {
+        game.reason = EndReason::Line(line);
+        game
+    }
+
+    #[test]
+    fn counts_results() {
+        let mut bot = game(10, 20, GameResult::FirstWon, vec![4]);
+        bot.bot = Some("easy".to_string());
+
+        let games = [
+            won_by_line(game(10, 20, GameResult::FirstWon, vec![0, 3, 1, 4, 2]), 0),
+            game(20, 10, GameResult::FirstWon, vec![0, 4, 1, 3, 2]),
+            game(10, 20, GameResult::Draw, vec![4, 0, 2, 6, 3, 5, 1, 7, 8]),
+            bot,
+            won_by_line(game(20, 10, GameResult::SecondWon, vec![0, 4, 1, 3, 8, 5]), 1),
+            won_by_line(game(10, 20, GameResult::FirstWon, vec![0, 4, 1, 5, 2]), 0),
+        ];
+
+        let stats = PlayerStats::compute(USER_ID, &games);
+
+        assert_eq!((stats.wins, stats.losses, stats.draws), (3, 1, 1));
+        assert_eq!((stats.games_as_x, stats.games_as_o), (3, 2));
+        assert_eq!(stats.longest_win_streak, 2);
+        assert_eq!(stats.win_rate(), 60.0);
+        assert_eq!(stats.favourite_win_line, Some(0));
+        assert_eq!(stats.favourite_first_cell, Some(4));
+    }
+
+    #[test]
+    fn names_cells_and_lines_only_on_the_classic_board() {
+        let mut games = vec![won_by_line(game(10, 20, GameResult::FirstWon, vec![0, 3, 1, 4, 2]), 0)];
+
+        // Indexes of other boards would be counted as cells and lines of the classic one
+        for _ in 0..2 {
+            let mut big = won_by_line(game(10, 20, GameResult::FirstWon, vec![8, 0, 9, 1, 10, 2, 11, 3, 12]), 5);
+
+            big.mode = Mode::Gomoku;
+            big.rules = Rules::gomoku(false);
+            games.push(big);
+        }
+
+        let stats = PlayerStats::compute(USER_ID, &games);
+
+        assert_eq!(stats.wins, 3);
+        assert_eq!(stats.longest_win_streak, 3);
+        assert_eq!(stats.favourite_win_line, Some(0));
+        assert_eq!(stats.favourite_first_cell, Some(0));
+    }
+
+    #[test]
+    fn players_who_leave_lose_to_everyone_else() {
+        // The user plays Δ after players 20 and 30
+        let games = [GameResult::Lost(Player::Second), GameResult::ThirdWon, GameResult::Lost(Player::Third)].map(|result| {
+            let mut game = game(20, 30, result, vec![0, 1, 2]);
+
+            game.rules.players = 3;
+            game.others = vec![(USER_ID, "Δ".to_string())];
+            game
+        });
+
+        let stats = PlayerStats::compute(USER_ID, &games);
+
+        assert_eq!((stats.wins, stats.losses, stats.draws), (2, 1, 0));
+        assert_eq!((stats.games_as_x, stats.games_as_o), (0, 0));
+
+        let stats = PlayerStats::compute(UserId::new(30), &games);
+        assert_eq!((stats.wins, stats.losses), (1, 2));
+    }
+}
@@ -0,0 +1,972 @@
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+use crate::board::{Player, Rules};
+use crate::variant::Mode;
+use crate::queue::QueueScope;
+use crate::rating::{self, Rating};
+use crate::tournament::{Bracket, Format, Match, MatchResult, Source, Status, Tournament};
+
+// Every migration is applied only once, the number of applied
+// migrations is stored in the `user_version` pragma of the database.
+// Never change already released migrations, add new ones instead.
+const MIGRATIONS: &[&str] = &[
+    // 1
+    "CREATE TABLE games (
+        id INTEGER PRIMARY KEY,
+        guild_id INTEGER, -- NULL for direct messages
+        channel_id INTEGER NOT NULL,
+        player_id INTEGER NOT NULL, -- Plays X
+        player_name TEXT NOT NULL,
+        player2_id INTEGER NOT NULL, -- Plays O
+        player2_name TEXT NOT NULL,
+        bot TEXT, -- Difficulty if the second player is a computer
+        moves TEXT NOT NULL, -- Cell indexes separated by spaces
+        result TEXT NOT NULL, -- 'first', 'second' or 'draw'
+        reason TEXT NOT NULL, -- 'line', 'board_full' or 'resignation'
+        win_line INTEGER, -- Index in the board::LINES
+        started_at INTEGER NOT NULL, -- Unix time in seconds
+        ended_at INTEGER NOT NULL
+    );
+
+    CREATE INDEX games_player_id ON games (player_id);
+    CREATE INDEX games_player2_id ON games (player2_id);",
+
+    // 2
+    "CREATE TABLE ratings (
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        rating REAL NOT NULL,
+        games INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, user_id)
+    );
+
+    CREATE INDEX ratings_rating ON ratings (guild_id, rating);
+
+    -- Ratings after every rated game
+    CREATE TABLE rating_history (
+        game_id INTEGER NOT NULL REFERENCES games (id),
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        rating REAL NOT NULL,
+        change REAL NOT NULL,
+        PRIMARY KEY (game_id, user_id)
+    );
+
+    CREATE INDEX rating_history_user_id ON rating_history (guild_id, user_id);
+
+    CREATE TABLE meta (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );",
+
+    // 3
+    "CREATE TABLE guild_settings (
+        guild_id INTEGER PRIMARY KEY,
+        queue_scope TEXT NOT NULL -- 'guild' or 'channel'
+    );",
+
+    // 4
+    "ALTER TABLE games ADD COLUMN width INTEGER NOT NULL DEFAULT 3;
+    ALTER TABLE games ADD COLUMN height INTEGER NOT NULL DEFAULT 3;
+    ALTER TABLE games ADD COLUMN win_length INTEGER NOT NULL DEFAULT 3; -- Marks in a row needed for a win",
+
+    // 5
+    "ALTER TABLE games ADD COLUMN exact_length INTEGER NOT NULL DEFAULT 0; -- 1 if longer lines do not win",
+
+    // 6
+    "ALTER TABLE games ADD COLUMN mode TEXT NOT NULL DEFAULT 'classic'; -- 'classic', 'gomoku' or 'ultimate'",
+
+    // 7
+    "ALTER TABLE games ADD COLUMN misere INTEGER NOT NULL DEFAULT 0; -- 1 if the player who completes a line loses",
+
+    // 8
+    "ALTER TABLE games ADD COLUMN symbols TEXT; -- 'x' or 'o' of every move separated by spaces, NULL if players do not choose marks",
+
+    // 9
+    "ALTER TABLE games ADD COLUMN numbers TEXT; -- Numbers of every move separated by spaces, NULL if it is not numerical tic-tac-toe",
+
+    // 10
+    "ALTER TABLE games ADD COLUMN players INTEGER NOT NULL DEFAULT 2; -- Marks which take turns
+    ALTER TABLE games ADD COLUMN player3_id INTEGER; -- Plays Δ in games of three or four players
+    ALTER TABLE games ADD COLUMN player3_name TEXT;
+    ALTER TABLE games ADD COLUMN player4_id INTEGER; -- Plays □ in games of four players
+    ALTER TABLE games ADD COLUMN player4_name TEXT;
+
+    CREATE INDEX games_player3_id ON games (player3_id);
+    CREATE INDEX games_player4_id ON games (player4_id);",
+
+    // 11
+    "CREATE TABLE tournaments (
+        id INTEGER PRIMARY KEY,
+        guild_id INTEGER, -- NULL for direct messages
+        channel_id INTEGER NOT NULL,
+        creator_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        format TEXT NOT NULL, -- 'single_elimination', 'double_elimination', 'round_robin' or 'swiss'
+        mode TEXT NOT NULL,
+        status TEXT NOT NULL, -- 'registration', 'running' or 'finished'
+        created_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE tournament_players (
+        tournament_id INTEGER NOT NULL REFERENCES tournaments (id),
+        seed INTEGER NOT NULL, -- From 0 in the order of joining
+        user_id INTEGER NOT NULL,
+        user_name TEXT NOT NULL,
+        PRIMARY KEY (tournament_id, seed)
+    );
+
+    CREATE TABLE tournament_matches (
+        tournament_id INTEGER NOT NULL REFERENCES tournaments (id),
+        number INTEGER NOT NULL, -- Index of the match, sources refer to other matches by it
+        bracket TEXT NOT NULL, -- 'main', 'losers' or 'final'
+        round INTEGER NOT NULL,
+        source1 TEXT NOT NULL, -- 'seed 3', 'winner 5', 'loser 5' or 'bye'
+        source2 TEXT NOT NULL,
+        result TEXT, -- 'first', 'second' or 'draw', NULL until the match is finished
+        PRIMARY KEY (tournament_id, number)
+    );
+
+    CREATE INDEX tournaments_status ON tournaments (status);",
+
+    // 12
+    "ALTER TABLE games ADD COLUMN teams INTEGER NOT NULL DEFAULT 0; -- 1 if player3 and player4 are teammates of X and O instead of players of Δ and □
+    UPDATE games SET teams = 1 WHERE players = 2 AND player3_id IS NOT NULL;",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameResult {
+    FirstWon,
+    SecondWon,
+    ThirdWon, // Only in games of more than two players
+    FourthWon,
+    Draw,
+    Lost(Player), // The player has left a game of more than two players, the others have won
+}
+
+impl GameResult {
+    // The player of the record who has won, by the order of marks
+    pub fn won_by(player: Player) -> Self {
+        match player {
+            Player::First => GameResult::FirstWon,
+            Player::Second => GameResult::SecondWon,
+            Player::Third => GameResult::ThirdWon,
+            Player::Fourth => GameResult::FourthWon,
+        }
+    }
+
+    pub fn winner(self) -> Option<Player> {
+        match self {
+            GameResult::FirstWon => Some(Player::First),
+            GameResult::SecondWon => Some(Player::Second),
+            GameResult::ThirdWon => Some(Player::Third),
+            GameResult::FourthWon => Some(Player::Fourth),
+            GameResult::Draw | GameResult::Lost(_) => None,
+        }
+    }
+
+    pub fn loser(self) -> Option<Player> {
+        match self {
+            GameResult::Lost(player) => Some(player),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            GameResult::FirstWon => "first",
+            GameResult::SecondWon => "second",
+            GameResult::ThirdWon => "third",
+            GameResult::FourthWon => "fourth",
+            GameResult::Draw => "draw",
+            GameResult::Lost(Player::First) => "first_lost",
+            GameResult::Lost(Player::Second) => "second_lost",
+            GameResult::Lost(Player::Third) => "third_lost",
+            GameResult::Lost(Player::Fourth) => "fourth_lost",
+        }
+    }
+
+    fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "first" => Some(GameResult::FirstWon),
+            "second" => Some(GameResult::SecondWon),
+            "third" => Some(GameResult::ThirdWon),
+            "fourth" => Some(GameResult::FourthWon),
+            "draw" => Some(GameResult::Draw),
+            "first_lost" => Some(GameResult::Lost(Player::First)),
+            "second_lost" => Some(GameResult::Lost(Player::Second)),
+            "third_lost" => Some(GameResult::Lost(Player::Third)),
+            "fourth_lost" => Some(GameResult::Lost(Player::Fourth)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EndReason {
+    Line(usize), // Index in the board::Rules::lines, or in the board::LINES of sub-boards for ultimate tic-tac-toe
+    LosingLine(usize), // The loser has completed the line, in misère games and Notakto
+    BoardFull, // A draw, or a win of Chaos in Order & Chaos
+    Resignation,
+    Timeout, // The loser has run out of time
+}
+
+impl EndReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EndReason::Line(_) => "line",
+            EndReason::LosingLine(_) => "losing_line",
+            EndReason::BoardFull => "board_full",
+            EndReason::Resignation => "resignation",
+            EndReason::Timeout => "timeout",
+        }
+    }
+
+    pub fn from_str(val: &str, win_line: Option<usize>) -> Option<Self> {
+        match (val, win_line) {
+            ("line", Some(line)) => Some(EndReason::Line(line)),
+            ("losing_line", Some(line)) => Some(EndReason::LosingLine(line)),
+            ("board_full", _) => Some(EndReason::BoardFull),
+            ("resignation", _) => Some(EndReason::Resignation),
+            ("timeout", _) => Some(EndReason::Timeout),
+            _ => None,
+        }
+    }
+
+    pub fn win_line(self) -> Option<usize> {
+        match self {
+            EndReason::Line(line) => Some(line),
+            _ => None,
+        }
+    }
+
+    // Stored in the same column as the winning line
+    pub fn line(self) -> Option<usize> {
+        match self {
+            EndReason::Line(line) | EndReason::LosingLine(line) => Some(line),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GameRecord {
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+
+    pub player: (UserId, String), // Second element is a name of player
+    pub player2: (UserId, String),
+    pub others: Vec<(UserId, String)>, // Players of Δ and □ in games of more than two players, teammates of X and O in team games
+    pub bot: Option<String>,
+
+    pub mode: Mode,
+    pub rules: Rules, // The whole 9×9 board for ultimate tic-tac-toe, `players` is the count of marks
+    pub moves: Vec<usize>,
+    pub symbols: Vec<Player>, // Marks of the moves in wild tic-tac-toe and Order & Chaos, otherwise empty
+    pub numbers: Vec<u8>, // Numbers of the moves in numerical tic-tac-toe, otherwise empty
+    pub result: GameResult,
+    pub reason: EndReason,
+
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+impl GameRecord {
+    // Other players of games of two marks are teammates of X and O
+    pub fn is_team_game(&self) -> bool {
+        self.rules.players == 2 && !self.others.is_empty()
+    }
+}
+
+pub struct RatingChange {
+    pub rating: f64, // After the game
+    pub change: f64,
+    pub opponent_name: String,
+    pub ended_at: i64,
+}
+
+pub struct Storage {
+    connection: Mutex<Connection>,
+}
+
+impl Storage {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let mut connection = Connection::open(path)?;
+        migrate(&mut connection)?;
+
+        let formula_version: Option<i64> = connection.query_row(
+            "SELECT value FROM meta WHERE key = 'rating_formula'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+        if formula_version != Some(rating::FORMULA_VERSION) {
+            recompute_ratings(&mut connection)?;
+        }
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    // Saves the game and updates ratings of the players.
+    // Returns an id of the saved game.
+    pub fn save_game(&self, record: &GameRecord) -> rusqlite::Result<i64> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        let moves = record.moves.iter()
+            .map(|cell| cell.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let symbols = record.symbols.iter()
+            .map(|&symbol| symbol_name(symbol))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let numbers = record.numbers.iter()
+            .map(|number| number.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        transaction.execute(
+            "INSERT INTO games (
+                guild_id, channel_id,
+                player_id, player_name, player2_id, player2_name, bot,
+                moves, result, reason, win_line,
+                started_at, ended_at,
+                width, height, win_length, exact_length, mode, misere, symbols, numbers,
+                players, player3_id, player3_name, player4_id, player4_name, teams
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)",
+            params![
+                record.guild_id.map(|val| val.get() as i64),
+                record.channel_id.get() as i64,
+                record.player.0.get() as i64,
+                record.player.1,
+                record.player2.0.get() as i64,
+                record.player2.1,
+                record.bot,
+                moves,
+                record.result.as_str(),
+                record.reason.as_str(),
+                record.reason.line().map(|val| val as i64),
+                record.started_at,
+                record.ended_at,
+                record.rules.width as i64,
+                record.rules.height as i64,
+                record.rules.win_length as i64,
+                record.rules.exact,
+                record.mode.as_str(),
+                record.rules.misere,
+                (!symbols.is_empty()).then_some(symbols),
+                (!numbers.is_empty()).then_some(numbers),
+                record.rules.players as i64,
+                record.others.first().map(|val| val.0.get() as i64),
+                record.others.first().map(|val| &val.1),
+                record.others.get(1).map(|val| val.0.get() as i64),
+                record.others.get(1).map(|val| &val.1),
+                record.is_team_game(),
+            ],
+        )?;
+
+        let id = transaction.last_insert_rowid();
+        rate_game(&transaction, id, record)?;
+
+        transaction.commit()?;
+
+        Ok(id)
+    }
+
+    // All games of the user from the oldest to the newest
+    pub fn games_of_user(&self, user_id: UserId) -> rusqlite::Result<Vec<GameRecord>> {
+        let connection = self.connection.lock().unwrap();
+
+        let mut statement = connection.prepare(
+            "SELECT * FROM games
+            WHERE player_id = ?1 OR player2_id = ?1 OR player3_id = ?1 OR player4_id = ?1
+            ORDER BY ended_at, id"
+        )?;
+
+        let games = statement.query_map([user_id.get() as i64], read_game)?
+            .collect();
+
+        games
+    }
+
+    pub fn game(&self, id: i64) -> rusqlite::Result<Option<GameRecord>> {
+        let connection = self.connection.lock().unwrap();
+
+        connection.query_row("SELECT * FROM games WHERE id = ?1", [id], read_game)
+            .optional()
+    }
+
+    pub fn rating(&self, guild_id: GuildId, user_id: UserId) -> rusqlite::Result<Option<Rating>> {
+        let connection = self.connection.lock().unwrap();
+        read_rating(&connection, guild_id, user_id)
+    }
+
+    // The newest changes are first
+    pub fn rating_history(&self, guild_id: GuildId, user_id: UserId, limit: usize) -> rusqlite::Result<Vec<RatingChange>> {
+        let connection = self.connection.lock().unwrap();
+
+        let mut statement = connection.prepare(
+            "SELECT history.rating, history.change, games.ended_at,
+                CASE WHEN games.player_id = history.user_id
+                    THEN games.player2_name
+                    ELSE games.player_name
+                END AS opponent_name
+            FROM rating_history AS history
+            JOIN games ON games.id = history.game_id
+            WHERE history.guild_id = ?1 AND history.user_id = ?2
+            ORDER BY games.ended_at DESC, games.id DESC
+            LIMIT ?3"
+        )?;
+
+        let params = params![guild_id.get() as i64, user_id.get() as i64, limit as i64];
+        let history = statement.query_map(params, |row| Ok(RatingChange {
+            rating: row.get("rating")?,
+            change: row.get("change")?,
+            opponent_name: row.get("opponent_name")?,
+            ended_at: row.get("ended_at")?,
+        }))?
+        .collect();
+
+        history
+    }
+
+    pub fn queue_scope(&self, guild_id: GuildId) -> rusqlite::Result<QueueScope> {
+        let connection = self.connection.lock().unwrap();
+
+        let scope: Option<String> = connection.query_row(
+            "SELECT queue_scope FROM guild_settings WHERE guild_id = ?1",
+            [guild_id.get() as i64],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+        Ok(scope.and_then(|val| QueueScope::from_str(&val)).unwrap_or_default())
+    }
+
+    pub fn set_queue_scope(&self, guild_id: GuildId, scope: QueueScope) -> rusqlite::Result<()> {
+        let connection = self.connection.lock().unwrap();
+
+        connection.execute(
+            "INSERT OR REPLACE INTO guild_settings (guild_id, queue_scope) VALUES (?1, ?2)",
+            params![guild_id.get() as i64, scope.as_str()],
+        )?;
+
+        Ok(())
+    }
+
+    // Creates the tournament when its id is 0, players and matches are replaced.
+    // Returns an id of the tournament.
+    pub fn save_tournament(&self, tournament: &Tournament) -> rusqlite::Result<i64> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        let id = if tournament.id == 0 {
+            transaction.execute(
+                "INSERT INTO tournaments (guild_id, channel_id, creator_id, name, format, mode, status, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    tournament.guild_id.map(|val| val.get() as i64),
+                    tournament.channel_id.get() as i64,
+                    tournament.creator_id.get() as i64,
+                    tournament.name,
+                    tournament.format.as_str(),
+                    tournament.mode.as_str(),
+                    tournament.status.as_str(),
+                    tournament.created_at,
+                ],
+            )?;
+
+            transaction.last_insert_rowid()
+        }
+        else {
+            transaction.execute(
+                "UPDATE tournaments SET status = ?2 WHERE id = ?1",
+                params![tournament.id, tournament.status.as_str()],
+            )?;
+
+            tournament.id
+        };
+
+        transaction.execute("DELETE FROM tournament_players WHERE tournament_id = ?1", [id])?;
+        transaction.execute("DELETE FROM tournament_matches WHERE tournament_id = ?1", [id])?;
+
+        for (seed, (user_id, name)) in tournament.players.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO tournament_players (tournament_id, seed, user_id, user_name) VALUES (?1, ?2, ?3, ?4)",
+                params![id, seed as i64, user_id.get() as i64, name],
+            )?;
+        }
+
+        for (number, val) in tournament.matches.iter().enumerate() {
+            transaction.execute(
+                "INSERT INTO tournament_matches (tournament_id, number, bracket, round, source1, source2, result)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    id,
+                    number as i64,
+                    val.bracket.as_str(),
+                    val.round as i64,
+                    val.sources[0].to_text(),
+                    val.sources[1].to_text(),
+                    val.result.map(|result| result.as_str()),
+                ],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(id)
+    }
+
+    // Tournaments which are not finished or cancelled, they are kept in memory while the bot is running
+    pub fn active_tournaments(&self) -> rusqlite::Result<Vec<Tournament>> {
+        let connection = self.connection.lock().unwrap();
+
+        let mut statement = connection.prepare("SELECT * FROM tournaments WHERE status IN ('registration', 'running') ORDER BY id")?;
+        let mut tournaments = statement.query_map([], read_tournament)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for tournament in &mut tournaments {
+            let mut statement = connection.prepare("SELECT * FROM tournament_players WHERE tournament_id = ?1 ORDER BY seed")?;
+            tournament.players = statement.query_map([tournament.id], |row| Ok((
+                UserId::new(row.get::<_, i64>("user_id")? as u64),
+                row.get("user_name")?,
+            )))?
+            .collect::<rusqlite::Result<_>>()?;
+
+            let mut statement = connection.prepare("SELECT * FROM tournament_matches WHERE tournament_id = ?1 ORDER BY number")?;
+            tournament.matches = statement.query_map([tournament.id], read_match)?
+                .collect::<rusqlite::Result<_>>()?;
+        }
+
+        Ok(tournaments)
+    }
+
+    // Count of rated players on the server
+    pub fn leaderboard_size(&self, guild_id: GuildId) -> rusqlite::Result<usize> {
+        let connection = self.connection.lock().unwrap();
+
+        let count: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM ratings WHERE guild_id = ?1",
+            [guild_id.get() as i64],
+            |row| row.get(0),
+        )?;
+
+        Ok(count as usize)
+    }
+
+    // Players sorted by rating
+    pub fn leaderboard(&self, guild_id: GuildId, offset: usize, limit: usize) -> rusqlite::Result<Vec<(UserId, Rating)>> {
+        let connection = self.connection.lock().unwrap();
+
+        let mut statement = connection.prepare(
+            "SELECT user_id, rating, games FROM ratings
+            WHERE guild_id = ?1
+            ORDER BY rating DESC, games DESC
+            LIMIT ?2 OFFSET ?3"
+        )?;
+
+        let params = params![guild_id.get() as i64, limit as i64, offset as i64];
+        let ratings = statement.query_map(params, |row| Ok((
+            UserId::new(row.get::<_, i64>("user_id")? as u64),
+            Rating {
+                rating: row.get("rating")?,
+                games: row.get("games")?,
+            },
+        )))?
+        .collect();
+
+        ratings
+    }
+}
+
+fn read_rating(connection: &Connection, guild_id: GuildId, user_id: UserId) -> rusqlite::Result<Option<Rating>> {
+    connection.query_row(
+        "SELECT rating, games FROM ratings WHERE guild_id = ?1 AND user_id = ?2",
+        [guild_id.get() as i64, user_id.get() as i64],
+        |row| Ok(Rating {
+            rating: row.get("rating")?,
+            games: row.get("games")?,
+        }),
+    )
+    .optional()
+}
+
+fn write_rating(transaction: &Transaction, game_id: i64, guild_id: GuildId, user_id: UserId, old: Rating, new: Rating) -> rusqlite::Result<()> {
+    transaction.execute(
+        "INSERT OR REPLACE INTO ratings (guild_id, user_id, rating, games) VALUES (?1, ?2, ?3, ?4)",
+        params![guild_id.get() as i64, user_id.get() as i64, new.rating, new.games],
+    )?;
+
+    transaction.execute(
+        "INSERT INTO rating_history (game_id, guild_id, user_id, rating, change) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![game_id, guild_id.get() as i64, user_id.get() as i64, new.rating, new.rating - old.rating],
+    )?;
+
+    Ok(())
+}
+
+fn rate_game(transaction: &Transaction, game_id: i64, record: &GameRecord) -> rusqlite::Result<()> {
+    // Only games between two humans on a server are rated, not games of teams
+    let (Some(guild_id), None, 2, false) = (record.guild_id, &record.bot, record.rules.players, record.is_team_game()) else {
+        return Ok(());
+    };
+
+    let first = read_rating(transaction, guild_id, record.player.0)?.unwrap_or_default();
+    let second = read_rating(transaction, guild_id, record.player2.0)?.unwrap_or_default();
+
+    let score = match record.result {
+        GameResult::FirstWon => 1.0,
+        GameResult::Draw => 0.5,
+        _ => 0.0,
+    };
+
+    let (new_first, new_second) = rating::update(first, second, score);
+
+    write_rating(transaction, game_id, guild_id, record.player.0, first, new_first)?;
+    write_rating(transaction, game_id, guild_id, record.player2.0, second, new_second)?;
+
+    Ok(())
+}
+
+// Replays all saved games with the current rating formula
+fn recompute_ratings(connection: &mut Connection) -> rusqlite::Result<()> {
+    let transaction = connection.transaction()?;
+
+    transaction.execute("DELETE FROM rating_history", [])?;
+    transaction.execute("DELETE FROM ratings", [])?;
+
+    {
+        let mut statement = transaction.prepare("SELECT * FROM games ORDER BY ended_at, id")?;
+        let mut rows = statement.query([])?;
+
+        while let Some(row) = rows.next()? {
+            rate_game(&transaction, row.get("id")?, &read_game(row)?)?;
+        }
+    }
+
+    transaction.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES ('rating_formula', ?1)",
+        [rating::FORMULA_VERSION],
+    )?;
+
+    transaction.commit()
+}
+
+fn read_game(row: &Row) -> rusqlite::Result<GameRecord> {
+    let invalid = |column: &str| rusqlite::Error::InvalidColumnName(column.to_string());
+
+    let moves: String = row.get("moves")?;
+    let moves = moves.split_whitespace()
+        .map(|cell| cell.parse().map_err(|_| invalid("moves")))
+        .collect::<rusqlite::Result<_>>()?;
+
+    let symbols: Option<String> = row.get("symbols")?;
+    let symbols = symbols.unwrap_or_default()
+        .split_whitespace()
+        .map(|val| symbol_from_name(val).ok_or_else(|| invalid("symbols")))
+        .collect::<rusqlite::Result<_>>()?;
+
+    let numbers: Option<String> = row.get("numbers")?;
+    let numbers = numbers.unwrap_or_default()
+        .split_whitespace()
+        .map(|val| val.parse().map_err(|_| invalid("numbers")))
+        .collect::<rusqlite::Result<_>>()?;
+
+    let result: String = row.get("result")?;
+    let result = GameResult::from_str(&result).ok_or_else(|| invalid("result"))?;
+
+    let reason: String = row.get("reason")?;
+    let win_line = row.get::<_, Option<i64>>("win_line")?.map(|val| val as usize);
+    let reason = EndReason::from_str(&reason, win_line).ok_or_else(|| invalid("reason"))?;
+
+    let mode: String = row.get("mode")?;
+    let mode = Mode::from_str(&mode).ok_or_else(|| invalid("mode"))?;
+
+    let exact = row.get("exact_length")?;
+    let misere = row.get("misere")?;
+    let players = row.get::<_, i64>("players")? as usize;
+    let rules = Rules::new(
+        row.get::<_, i64>("width")? as usize,
+        row.get::<_, i64>("height")? as usize,
+        row.get::<_, i64>("win_length")? as usize,
+    )
+    .map(|rules| Rules { exact, misere, players, ..rules })
+    .ok_or_else(|| invalid("win_length"))?;
+
+    let mut others = Vec::new();
+
+    for column in ["player3", "player4"] {
+        if let Some(id) = row.get::<_, Option<i64>>(format!("{column}_id").as_str())? {
+            others.push((UserId::new(id as u64), row.get(format!("{column}_name").as_str())?));
+        }
+    }
+
+    Ok(GameRecord {
+        guild_id: row.get::<_, Option<i64>>("guild_id")?.map(|val| GuildId::new(val as u64)),
+        channel_id: ChannelId::new(row.get::<_, i64>("channel_id")? as u64),
+
+        player: (UserId::new(row.get::<_, i64>("player_id")? as u64), row.get("player_name")?),
+        player2: (UserId::new(row.get::<_, i64>("player2_id")? as u64), row.get("player2_name")?),
+        others,
+        bot: row.get("bot")?,
+
+        mode,
+        rules,
+        moves,
+        symbols,
+        numbers,
+        result,
+        reason,
+
+        started_at: row.get("started_at")?,
+        ended_at: row.get("ended_at")?,
+    })
+}
+
+// Players and matches are read separately
+fn read_tournament(row: &Row) -> rusqlite::Result<Tournament> {
+    let invalid = |column: &str| rusqlite::Error::InvalidColumnName(column.to_string());
+
+    let format: String = row.get("format")?;
+    let mode: String = row.get("mode")?;
+    let status: String = row.get("status")?;
+
+    Ok(Tournament {
+        id: row.get("id")?,
+        guild_id: row.get::<_, Option<i64>>("guild_id")?.map(|val| GuildId::new(val as u64)),
+        channel_id: ChannelId::new(row.get::<_, i64>("channel_id")? as u64),
+        creator_id: UserId::new(row.get::<_, i64>("creator_id")? as u64),
+
+        name: row.get("name")?,
+        format: Format::from_str(&format).ok_or_else(|| invalid("format"))?,
+        mode: Mode::from_str(&mode).ok_or_else(|| invalid("mode"))?,
+        status: Status::from_str(&status).ok_or_else(|| invalid("status"))?,
+
+        players: Vec::new(),
+        matches: Vec::new(),
+
+        created_at: row.get("created_at")?,
+    })
+}
+
+fn read_match(row: &Row) -> rusqlite::Result<Match> {
+    let invalid = |column: &str| rusqlite::Error::InvalidColumnName(column.to_string());
+
+    let bracket: String = row.get("bracket")?;
+    let source1: String = row.get("source1")?;
+    let source2: String = row.get("source2")?;
+    let result: Option<String> = row.get("result")?;
+
+    Ok(Match {
+        bracket: Bracket::from_str(&bracket).ok_or_else(|| invalid("bracket"))?,
+        round: row.get::<_, i64>("round")? as usize,
+        sources: [
+            Source::from_text(&source1).ok_or_else(|| invalid("source1"))?,
+            Source::from_text(&source2).ok_or_else(|| invalid("source2"))?,
+        ],
+        result: match result {
+            Some(val) => Some(MatchResult::from_str(&val).ok_or_else(|| invalid("result"))?),
+            None => None,
+        },
+    })
+}
+
+fn symbol_name(symbol: Player) -> &'static str {
+    match symbol {
+        Player::First => "x",
+        Player::Second => "o",
+        Player::Third => "triangle",
+        Player::Fourth => "square",
+    }
+}
+
+fn symbol_from_name(val: &str) -> Option<Player> {
+    match val {
+        "x" => Some(Player::First),
+        "o" => Some(Player::Second),
+        "triangle" => Some(Player::Third),
+        "square" => Some(Player::Fourth),
+        _ => None,
+    }
+}
+
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", i + 1)?;
+
+        transaction.commit()?;
+    }
+
+    Ok(())
+}
+
+// Current Unix time in seconds
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time went backwards")
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD_ID: GuildId = GuildId::new(1);
+
+    fn game(player: u64, player2: u64, result: GameResult, ended_at: i64) -> GameRecord {
+        GameRecord {
+            guild_id: Some(GUILD_ID),
+            channel_id: ChannelId::new(2),
+
+            player: (UserId::new(player), format!("Player {player}")),
+            player2: (UserId::new(player2), format!("Player {player2}")),
+            others: Vec::new(),
+            bot: None,
+
+            mode: Mode::Classic,
+            rules: Rules::default(),
+            moves: vec![4, 0, 2, 6, 3, 5, 1, 7, 8],
+            symbols: Vec::new(),
+            numbers: Vec::new(),
+            result,
+            reason: EndReason::BoardFull,
+
+            started_at: ended_at - 60,
+            ended_at,
+        }
+    }
+
+    fn ratings(storage: &Storage) -> Vec<(UserId, Rating)> {
+        storage.leaderboard(GUILD_ID, 0, 100).unwrap()
+    }
+
+    #[test]
+    fn applies_all_migrations() {
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        // Applying them again does nothing
+        for _ in 0..2 {
+            migrate(&mut connection).unwrap();
+
+            let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+            assert_eq!(version, MIGRATIONS.len());
+        }
+
+        let teams: i64 = connection.query_row("SELECT COUNT(*) FROM games WHERE teams = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(teams, 0);
+    }
+
+    #[test]
+    fn reads_saved_games() {
+        let storage = Storage::open(":memory:").unwrap();
+        let mut record = game(10, 20, GameResult::FirstWon, 1000);
+
+        record.moves = vec![4, 0, 2, 6, 3, 5];
+        record.reason = EndReason::Line(1);
+        storage.save_game(&record).unwrap();
+
+        // Losses of players who have left are stored too
+        let mut three = game(10, 20, GameResult::Lost(Player::Third), 2000);
+
+        three.rules.players = 3;
+        three.others = vec![(UserId::new(30), "Player 30".to_string())];
+        three.reason = EndReason::Resignation;
+        storage.save_game(&three).unwrap();
+
+        let games = storage.games_of_user(UserId::new(10)).unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].player, record.player);
+        assert_eq!(games[0].player2, record.player2);
+        assert_eq!(games[0].moves, record.moves);
+        assert_eq!(games[0].result, GameResult::FirstWon);
+        assert_eq!(games[0].reason, EndReason::Line(1));
+        assert_eq!(games[0].rules, Rules::default());
+        assert_eq!((games[0].started_at, games[0].ended_at), (940, 1000));
+
+        assert_eq!(games[1].result, GameResult::Lost(Player::Third));
+        assert_eq!(games[1].others, three.others);
+        assert_eq!(storage.games_of_user(UserId::new(30)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rates_only_games_of_two_humans_on_servers() {
+        let storage = Storage::open(":memory:").unwrap();
+
+        let mut bot = game(10, 20, GameResult::FirstWon, 1000);
+        bot.bot = Some("easy".to_string());
+
+        let mut direct = game(10, 20, GameResult::FirstWon, 1000);
+        direct.guild_id = None;
+
+        let mut teams = game(10, 20, GameResult::FirstWon, 1000);
+        teams.others = vec![(UserId::new(30), "Player 30".to_string()), (UserId::new(40), "Player 40".to_string())];
+
+        for record in [bot, direct, teams] {
+            storage.save_game(&record).unwrap();
+        }
+
+        assert!(ratings(&storage).is_empty());
+
+        let teams = storage.connection.lock().unwrap()
+            .query_row("SELECT COUNT(*) FROM games WHERE teams = 1", [], |row| row.get::<_, i64>(0))
+            .unwrap();
+        assert_eq!(teams, 1);
+
+        storage.save_game(&game(10, 20, GameResult::FirstWon, 2000)).unwrap();
+
+        let ratings = ratings(&storage);
+        assert_eq!(ratings.len(), 2);
+        assert_eq!(ratings[0].0, UserId::new(10));
+        assert_eq!(ratings[0].1.games, 1);
+        assert_eq!(storage.rating_history(GUILD_ID, UserId::new(20), 10).unwrap()[0].opponent_name, "Player 10");
+    }
+
+    #[test]
+    fn recomputed_ratings_match_saved_ones() {
+        let storage = Storage::open(":memory:").unwrap();
+
+        // Games are saved in the order of their ends, like they are replayed
+        for (player, player2, result, ended_at) in [
+            (10, 20, GameResult::FirstWon, 1000),
+            (20, 30, GameResult::Draw, 2000),
+            (30, 10, GameResult::SecondWon, 3000),
+            (20, 10, GameResult::FirstWon, 4000),
+        ] {
+            storage.save_game(&game(player, player2, result, ended_at)).unwrap();
+        }
+
+        let saved = ratings(&storage);
+
+        {
+            let mut connection = storage.connection.lock().unwrap();
+
+            connection.execute("UPDATE meta SET value = value - 1 WHERE key = 'rating_formula'", []).unwrap();
+            recompute_ratings(&mut connection).unwrap();
+
+            let version: i64 = connection.query_row("SELECT value FROM meta WHERE key = 'rating_formula'", [], |row| row.get(0)).unwrap();
+            assert_eq!(version, rating::FORMULA_VERSION);
+        }
+
+        assert_eq!(ratings(&storage), saved);
+    }
+}
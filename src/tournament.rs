@@ -0,0 +1,671 @@
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+use crate::variant::Mode;
+
+pub const MAX_PLAYERS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    SingleElimination,
+    DoubleElimination, // Without a reset of the grand final
+    RoundRobin,
+    Swiss,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::SingleElimination => "Single elimination",
+            Format::DoubleElimination => "Double elimination",
+            Format::RoundRobin => "Round robin",
+            Format::Swiss => "Swiss",
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::SingleElimination => "single_elimination",
+            Format::DoubleElimination => "double_elimination",
+            Format::RoundRobin => "round_robin",
+            Format::Swiss => "swiss",
+        }
+    }
+
+    pub fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "single_elimination" => Some(Format::SingleElimination),
+            "double_elimination" => Some(Format::DoubleElimination),
+            "round_robin" => Some(Format::RoundRobin),
+            "swiss" => Some(Format::Swiss),
+            _ => None,
+        }
+    }
+
+    // The losers bracket needs at least two rounds of the winners bracket
+    pub fn min_players(self) -> usize {
+        match self {
+            Format::DoubleElimination => 3,
+            _ => 2,
+        }
+    }
+
+    pub fn is_elimination(self) -> bool {
+        matches!(self, Format::SingleElimination | Format::DoubleElimination)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    Registration,
+    Running,
+    Finished,
+    Cancelled, // By the creator, or the registration has expired
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Registration => "registration",
+            Status::Running => "running",
+            Status::Finished => "finished",
+            Status::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "registration" => Some(Status::Registration),
+            "running" => Some(Status::Running),
+            "finished" => Some(Status::Finished),
+            "cancelled" => Some(Status::Cancelled),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Bracket {
+    Main, // The winners bracket, or all rounds of round robin and Swiss
+    Losers,
+    Final, // The grand final of double elimination
+}
+
+impl Bracket {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Bracket::Main => "main",
+            Bracket::Losers => "losers",
+            Bracket::Final => "final",
+        }
+    }
+
+    pub fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "main" => Some(Bracket::Main),
+            "losers" => Some(Bracket::Losers),
+            "final" => Some(Bracket::Final),
+            _ => None,
+        }
+    }
+}
+
+// Where the player of the match comes from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Source {
+    Seed(usize), // Index in the players of the tournament
+    Winner(usize), // Index in the matches of the tournament
+    Loser(usize),
+    Bye,
+}
+
+impl Source {
+    // Like "seed 3" or "winner 5"
+    pub fn to_text(self) -> String {
+        match self {
+            Source::Seed(val) => format!("seed {}", val),
+            Source::Winner(val) => format!("winner {}", val),
+            Source::Loser(val) => format!("loser {}", val),
+            Source::Bye => "bye".to_string(),
+        }
+    }
+
+    pub fn from_text(val: &str) -> Option<Self> {
+        let (kind, index) = match val.split_once(' ') {
+            Some((kind, index)) => (kind, index.parse().ok()?),
+            None => (val, 0),
+        };
+
+        match kind {
+            "seed" => Some(Source::Seed(index)),
+            "winner" => Some(Source::Winner(index)),
+            "loser" => Some(Source::Loser(index)),
+            "bye" => Some(Source::Bye),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Slot {
+    Pending, // The previous match is not finished yet
+    Bye,
+    Player(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchResult {
+    First,
+    Second,
+    Draw, // Only in round robin and Swiss, drawn elimination matches are played again
+}
+
+impl MatchResult {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MatchResult::First => "first",
+            MatchResult::Second => "second",
+            MatchResult::Draw => "draw",
+        }
+    }
+
+    pub fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "first" => Some(MatchResult::First),
+            "second" => Some(MatchResult::Second),
+            "draw" => Some(MatchResult::Draw),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Match {
+    pub bracket: Bracket,
+    pub round: usize, // From 1 in every bracket
+    pub sources: [Source; 2],
+    pub result: Option<MatchResult>,
+}
+
+impl Match {
+    fn new(bracket: Bracket, round: usize, sources: [Source; 2]) -> Self {
+        Self {
+            bracket,
+            round,
+            sources,
+            result: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tournament {
+    pub id: i64,
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub creator_id: UserId,
+
+    pub name: String,
+    pub format: Format,
+    pub mode: Mode, // Played with the default board of the mode
+    pub status: Status,
+
+    pub players: Vec<(UserId, String)>, // In the order of seeds, second element is a name of player
+    pub matches: Vec<Match>,
+
+    pub created_at: i64,
+}
+
+impl Tournament {
+    pub fn start(&mut self) {
+        self.matches = match self.format {
+            Format::SingleElimination => elimination_bracket(self.players.len(), false),
+            Format::DoubleElimination => elimination_bracket(self.players.len(), true),
+            Format::RoundRobin => round_robin_rounds(self.players.len()),
+            Format::Swiss => Vec::new(),
+        };
+
+        self.status = Status::Running;
+        self.advance();
+    }
+
+    pub fn slot(&self, source: Source) -> Slot {
+        match source {
+            Source::Seed(seed) => Slot::Player(seed),
+            Source::Bye => Slot::Bye,
+            Source::Winner(index) | Source::Loser(index) => {
+                let slots = self.slots(index);
+
+                let winner = match self.matches[index].result {
+                    Some(MatchResult::First) => 0,
+                    Some(MatchResult::Second) => 1,
+                    _ => return Slot::Pending,
+                };
+
+                match source {
+                    Source::Winner(_) => slots[winner],
+                    _ => slots[1 - winner],
+                }
+            }
+        }
+    }
+
+    pub fn slots(&self, index: usize) -> [Slot; 2] {
+        self.matches[index].sources.map(|source| self.slot(source))
+    }
+
+    // Matches which can be played now, in the order of the bracket
+    pub fn ready_matches(&self) -> Vec<usize> {
+        (0..self.matches.len())
+            .filter(|&index| self.matches[index].result.is_none())
+            .filter(|&index| matches!(self.slots(index), [Slot::Player(_), Slot::Player(_)]))
+            .collect()
+    }
+
+    // Drawn elimination matches stay unfinished, so they are played again
+    pub fn record(&mut self, index: usize, result: MatchResult) {
+        if result == MatchResult::Draw && self.format.is_elimination() {
+            return;
+        }
+
+        self.matches[index].result = Some(result);
+        self.advance();
+    }
+
+    // Matches against byes are won without playing, the next Swiss round is paired after the previous one
+    fn advance(&mut self) {
+        loop {
+            let bye = (0..self.matches.len()).find_map(|index| {
+                let result = match (self.matches[index].result, self.slots(index)) {
+                    (None, [Slot::Player(_) | Slot::Bye, Slot::Bye]) => MatchResult::First,
+                    (None, [Slot::Bye, Slot::Player(_)]) => MatchResult::Second,
+                    _ => return None,
+                };
+
+                Some((index, result))
+            });
+
+            if let Some((index, result)) = bye {
+                self.matches[index].result = Some(result);
+                continue;
+            }
+
+            let round = self.matches.last().map_or(0, |val| val.round);
+            let is_round_finished = self.matches.iter().all(|val| val.result.is_some());
+
+            if self.format == Format::Swiss && is_round_finished && round < swiss_rounds(self.players.len()) {
+                self.pair_swiss_round(round + 1);
+                continue;
+            }
+
+            break;
+        }
+
+        if self.matches.iter().all(|val| val.result.is_some()) {
+            self.status = Status::Finished;
+        }
+    }
+
+    // Players with points from the best, byes are counted as wins
+    pub fn standings(&self) -> Vec<(usize, f64)> {
+        let mut points = vec![0.0_f64; self.players.len()];
+
+        for index in 0..self.matches.len() {
+            let slots = self.slots(index);
+
+            let scores = match self.matches[index].result {
+                Some(MatchResult::First) => [1.0, 0.0],
+                Some(MatchResult::Second) => [0.0, 1.0],
+                Some(MatchResult::Draw) => [0.5, 0.5],
+                None => continue,
+            };
+
+            for (slot, score) in slots.into_iter().zip(scores) {
+                if let Slot::Player(player) = slot {
+                    points[player] += score;
+                }
+            }
+        }
+
+        // Equal points are ordered by seeds
+        let mut standings: Vec<_> = points.into_iter().enumerate().collect();
+        standings.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        standings
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        if self.status != Status::Finished {
+            return None;
+        }
+
+        match self.format {
+            // The last match is the final or the grand final
+            Format::SingleElimination | Format::DoubleElimination => match self.slot(Source::Winner(self.matches.len() - 1)) {
+                Slot::Player(player) => Some(player),
+                _ => None,
+            },
+            Format::RoundRobin | Format::Swiss => self.standings().first().map(|val| val.0),
+        }
+    }
+
+    pub fn have_played(&self, first: usize, second: usize) -> bool {
+        (0..self.matches.len()).any(|index| {
+            let slots = self.slots(index);
+            slots == [Slot::Player(first), Slot::Player(second)] || slots == [Slot::Player(second), Slot::Player(first)]
+        })
+    }
+
+    fn pair_swiss_round(&mut self, round: usize) {
+        let mut order: Vec<_> = self.standings().into_iter().map(|val| val.0).collect();
+
+        // The lowest player who has not had a bye yet
+        if order.len() % 2 == 1 {
+            let had_bye = |player: usize| self.matches.iter().any(|val| val.sources == [Source::Seed(player), Source::Bye]);
+            let pos = order.iter().rposition(|&player| !had_bye(player)).unwrap_or(order.len() - 1);
+            let player = order.remove(pos);
+
+            self.matches.push(Match::new(Bracket::Main, round, [Source::Seed(player), Source::Bye]));
+        }
+
+        // Rematches are avoided when it is possible
+        let pairs = pair_without_rematches(&order, &|first, second| self.have_played(first, second))
+            .unwrap_or_else(|| order.chunks(2).map(|val| (val[0], val[1])).collect());
+
+        for (first, second) in pairs {
+            self.matches.push(Match::new(Bracket::Main, round, [Source::Seed(first), Source::Seed(second)]));
+        }
+    }
+}
+
+// Enough rounds to have a single player with all wins
+pub fn swiss_rounds(players: usize) -> usize {
+    players.next_power_of_two().trailing_zeros().max(1) as usize
+}
+
+// Better players are paired first, each with the best possible opponent
+fn pair_without_rematches(order: &[usize], have_played: &dyn Fn(usize, usize) -> bool) -> Option<Vec<(usize, usize)>> {
+    let Some((&first, rest)) = order.split_first() else {
+        return Some(Vec::new());
+    };
+
+    for (i, &second) in rest.iter().enumerate() {
+        if have_played(first, second) {
+            continue;
+        }
+
+        let mut others = rest.to_vec();
+        others.remove(i);
+
+        if let Some(mut pairs) = pair_without_rematches(&others, have_played) {
+            pairs.insert(0, (first, second));
+            return Some(pairs);
+        }
+    }
+
+    None
+}
+
+// Positions of seeds in the first round, so the best seeds meet in the last rounds
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+
+    while order.len() < size {
+        let length = order.len();
+        order = order.into_iter().flat_map(|seed| [seed, 2 * length - 1 - seed]).collect();
+    }
+
+    order
+}
+
+fn elimination_bracket(players: usize, double: bool) -> Vec<Match> {
+    let size = players.next_power_of_two().max(2);
+    let seed = |seed: usize| if seed < players { Source::Seed(seed) } else { Source::Bye };
+
+    let mut matches: Vec<Match> = Vec::new();
+    let mut winners_rounds: Vec<Vec<usize>> = Vec::new();
+
+    let first_round = seed_order(size).chunks(2)
+        .map(|pair| Match::new(Bracket::Main, 1, [seed(pair[0]), seed(pair[1])]))
+        .collect();
+
+    let mut round: Vec<usize> = push_matches(&mut matches, first_round);
+    winners_rounds.push(round.clone());
+
+    while round.len() > 1 {
+        let number = winners_rounds.len() + 1;
+        let next = round.chunks(2)
+            .map(|pair| Match::new(Bracket::Main, number, [Source::Winner(pair[0]), Source::Winner(pair[1])]))
+            .collect();
+
+        round = push_matches(&mut matches, next);
+        winners_rounds.push(round.clone());
+    }
+
+    if !double {
+        return matches;
+    }
+
+    // Losers of the first round play each other, then winners of the losers bracket
+    // meet losers of the next winners round and play each other between those rounds
+    let first_losers = winners_rounds[0].chunks(2)
+        .map(|pair| Match::new(Bracket::Losers, 1, [Source::Loser(pair[0]), Source::Loser(pair[1])]))
+        .collect();
+
+    let mut losers_round = push_matches(&mut matches, first_losers);
+    let mut number = 1;
+
+    for (i, winners_round) in winners_rounds.iter().enumerate().skip(1) {
+        number += 1;
+        let major = losers_round.iter().zip(winners_round)
+            .map(|(&winner, &loser)| Match::new(Bracket::Losers, number, [Source::Winner(winner), Source::Loser(loser)]))
+            .collect();
+
+        losers_round = push_matches(&mut matches, major);
+
+        if i + 1 < winners_rounds.len() {
+            number += 1;
+            let minor = losers_round.chunks(2)
+                .map(|pair| Match::new(Bracket::Losers, number, [Source::Winner(pair[0]), Source::Winner(pair[1])]))
+                .collect();
+
+            losers_round = push_matches(&mut matches, minor);
+        }
+    }
+
+    let winners_final = round[0];
+    let losers_final = losers_round[0];
+
+    matches.push(Match::new(Bracket::Final, 1, [Source::Winner(winners_final), Source::Winner(losers_final)]));
+
+    matches
+}
+
+// Indexes of the added matches
+fn push_matches(matches: &mut Vec<Match>, new: Vec<Match>) -> Vec<usize> {
+    let start = matches.len();
+    matches.extend(new);
+
+    (start..matches.len()).collect()
+}
+
+// The circle method, the first player stays and others rotate
+fn round_robin_rounds(players: usize) -> Vec<Match> {
+    let mut circle: Vec<_> = (0..players).map(Source::Seed).collect();
+    if players % 2 == 1 {
+        circle.push(Source::Bye);
+    }
+
+    let length = circle.len();
+    let mut matches = Vec::new();
+
+    for round in 1..length {
+        for i in 0..length / 2 {
+            let pair = [circle[i], circle[length - 1 - i]];
+
+            // Byes are always the second
+            let sources = if pair[0] == Source::Bye { [pair[1], pair[0]] } else { pair };
+            matches.push(Match::new(Bracket::Main, round, sources));
+        }
+
+        circle[1..].rotate_right(1);
+    }
+
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tournament(format: Format, players: usize) -> Tournament {
+        let mut tournament = Tournament {
+            id: 1,
+            guild_id: None,
+            channel_id: ChannelId::new(1),
+            creator_id: UserId::new(1),
+
+            name: "Test".to_string(),
+            format,
+            mode: Mode::Classic,
+            status: Status::Registration,
+
+            players: (0..players).map(|i| (UserId::new(i as u64 + 1), format!("Player {}", i + 1))).collect(),
+            matches: Vec::new(),
+
+            created_at: 0,
+        };
+
+        tournament.start();
+        tournament
+    }
+
+    // Plays ready matches until the end, `result` decides every match by the seeds of its players
+    fn play(tournament: &mut Tournament, result: impl Fn(usize, usize) -> MatchResult) {
+        while let Some(&index) = tournament.ready_matches().first() {
+            let [Slot::Player(first), Slot::Player(second)] = tournament.slots(index) else {
+                unreachable!();
+            };
+
+            tournament.record(index, result(first, second));
+        }
+    }
+
+    fn better_seed(first: usize, second: usize) -> MatchResult {
+        if first < second { MatchResult::First } else { MatchResult::Second }
+    }
+
+    #[test]
+    fn seeds_best_players_apart() {
+        assert_eq!(seed_order(2), vec![0, 1]);
+        assert_eq!(seed_order(4), vec![0, 3, 1, 2]);
+        assert_eq!(seed_order(8), vec![0, 7, 3, 4, 1, 6, 2, 5]);
+    }
+
+    #[test]
+    fn byes_advance_top_seeds() {
+        let mut tournament = tournament(Format::SingleElimination, 5);
+
+        assert_eq!(tournament.matches.len(), 7);
+        // Two matches against byes already meet in the second round
+        assert_eq!(tournament.ready_matches(), vec![1, 5]);
+        assert_eq!(tournament.slots(1), [Slot::Player(3), Slot::Player(4)]);
+        assert_eq!(tournament.slot(Source::Winner(0)), Slot::Player(0));
+        assert_eq!(tournament.slot(Source::Winner(2)), Slot::Player(1));
+
+        play(&mut tournament, better_seed);
+
+        assert_eq!(tournament.status, Status::Finished);
+        assert_eq!(tournament.winner(), Some(0));
+    }
+
+    #[test]
+    fn drawn_elimination_matches_are_replayed() {
+        let mut tournament = tournament(Format::SingleElimination, 2);
+
+        tournament.record(0, MatchResult::Draw);
+        assert_eq!(tournament.ready_matches(), vec![0]);
+
+        tournament.record(0, MatchResult::Second);
+        assert_eq!(tournament.winner(), Some(1));
+    }
+
+    #[test]
+    fn double_elimination_survives_one_loss() {
+        let mut tournament = tournament(Format::DoubleElimination, 4);
+
+        let brackets: Vec<_> = tournament.matches.iter().map(|val| val.bracket).collect();
+        assert_eq!(brackets, [Bracket::Main, Bracket::Main, Bracket::Main, Bracket::Losers, Bracket::Losers, Bracket::Final]);
+
+        // The top seed loses the first match, then wins the losers bracket and the grand final
+        let has_lost = std::cell::Cell::new(false);
+
+        play(&mut tournament, |first, second| {
+            if first == 0 && !has_lost.replace(true) {
+                MatchResult::Second
+            }
+            else {
+                better_seed(first, second)
+            }
+        });
+
+        assert_eq!(tournament.slots(5), [Slot::Player(1), Slot::Player(0)]);
+        assert_eq!(tournament.winner(), Some(0));
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        let mut tournament = tournament(Format::RoundRobin, 5);
+
+        // Five rounds of two matches and a bye
+        assert_eq!(tournament.matches.len(), 15);
+
+        for first in 0..5 {
+            for second in first + 1..5 {
+                assert!(tournament.have_played(first, second), "{} and {}", first, second);
+            }
+        }
+
+        play(&mut tournament, |_, _| MatchResult::Draw);
+
+        assert_eq!(tournament.status, Status::Finished);
+        assert!(tournament.standings().iter().all(|val| val.1 == 3.0)); // Four draws and a bye
+        assert_eq!(tournament.winner(), Some(0));
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        let mut tournament = tournament(Format::Swiss, 6);
+
+        assert_eq!(swiss_rounds(6), 3);
+        assert_eq!(tournament.slots(0), [Slot::Player(0), Slot::Player(1)]);
+
+        play(&mut tournament, better_seed);
+
+        assert_eq!(tournament.status, Status::Finished);
+        assert_eq!(tournament.matches.len(), 9);
+        assert_eq!(tournament.winner(), Some(0));
+
+        for (i, first) in tournament.matches.iter().enumerate() {
+            for second in &tournament.matches[i + 1..] {
+                let mut pair = first.sources;
+                pair.reverse();
+
+                assert!(first.sources != second.sources && pair != second.sources);
+            }
+        }
+    }
+
+    #[test]
+    fn swiss_byes_go_to_different_players() {
+        let mut tournament = tournament(Format::Swiss, 5);
+        play(&mut tournament, better_seed);
+
+        let byes: Vec<_> = tournament.matches.iter()
+            .filter(|val| val.sources[1] == Source::Bye)
+            .map(|val| val.sources[0])
+            .collect();
+
+        assert_eq!(byes.len(), 3);
+        assert!(byes.iter().enumerate().all(|(i, bye)| !byes[..i].contains(bye)));
+    }
+}
@@ -0,0 +1,254 @@
+// Ultimate tic-tac-toe: the 3×3 grid of 3×3 boards. The cell of every move
+// is the board where the opponent has to make the next move.
+//
+// Cells are numbered row by row on the whole 9×9 board, sub-boards are
+// numbered like cells of the classic board.
+use crate::board::{Board, GameCell, MoveError, Outcome, Player, Rules, LINES};
+
+// Cells in a row of the whole board
+pub const SIZE: usize = 9;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubBoardState {
+    Open,
+    Won(Player),
+    Full, // Without a winner
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UltimatePosition {
+    board: Board, // Marks of all 81 cells
+    sub_boards: [SubBoardState; 9],
+    moves: Vec<usize>,
+    outcome: Outcome, // `line` of the win is an index in the `LINES` of sub-boards
+}
+
+impl Default for UltimatePosition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Sub-board of the cell
+pub fn sub_board_of(cell: usize) -> usize {
+    cell / SIZE / 3 * 3 + cell % SIZE / 3
+}
+
+// Index of the cell inside of its sub-board, like cells of the classic board
+pub fn cell_in_sub_board(cell: usize) -> usize {
+    cell / SIZE % 3 * 3 + cell % SIZE % 3
+}
+
+// Cells of the sub-board in the order of `cell_in_sub_board`
+pub fn sub_board_cells(sub_board: usize) -> [usize; 9] {
+    let first = sub_board / 3 * 3 * SIZE + sub_board % 3 * 3;
+
+    std::array::from_fn(|i| first + i / 3 * SIZE + i % 3)
+}
+
+pub fn sub_board_center(sub_board: usize) -> usize {
+    sub_board_cells(sub_board)[4]
+}
+
+impl UltimatePosition {
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(Self::rules()),
+            sub_boards: [SubBoardState::Open; 9],
+            moves: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    // Size of the whole board, the win length is for sub-boards
+    pub fn rules() -> Rules {
+        Rules::new(SIZE, SIZE, 3).unwrap()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    pub fn turn(&self) -> Player {
+        match self.moves.len() % 2 {
+            0 => Player::First,
+            _ => Player::Second,
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    pub fn sub_board(&self, sub_board: usize) -> SubBoardState {
+        self.sub_boards[sub_board]
+    }
+
+    // Sub-boards where the next move can be made. It is the one chosen by
+    // the last move, or any open one if that sub-board is already decided.
+    pub fn active_sub_boards(&self) -> Vec<usize> {
+        if self.outcome != Outcome::Ongoing {
+            return Vec::new();
+        }
+
+        let target = self.moves.last().map(|&cell| cell_in_sub_board(cell));
+
+        match target {
+            Some(sub_board) if self.sub_boards[sub_board] == SubBoardState::Open => vec![sub_board],
+            _ => (0..9).filter(|&val| self.sub_boards[val] == SubBoardState::Open).collect(),
+        }
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_move(cell).is_ok()
+    }
+
+    pub fn legal_moves(&self) -> Vec<usize> {
+        self.active_sub_boards().into_iter()
+            .flat_map(sub_board_cells)
+            .filter(|&cell| self.board.is_empty(cell))
+            .collect()
+    }
+
+    pub fn apply_move(&mut self, cell: usize) -> Result<Outcome, MoveError> {
+        self.check_move(cell)?;
+
+        let player = self.turn();
+        let sub_board = sub_board_of(cell);
+
+        self.board.set(cell, player.cell());
+        self.moves.push(cell);
+
+        let cells = sub_board_cells(sub_board);
+        let has_line = LINES.iter()
+            .any(|line| line.iter().all(|&i| self.board.get(cells[i]) == player.cell()));
+
+        if has_line {
+            self.sub_boards[sub_board] = SubBoardState::Won(player);
+        }
+        else if cells.iter().all(|&val| !self.board.is_empty(val)) {
+            self.sub_boards[sub_board] = SubBoardState::Full;
+        }
+
+        let line = LINES.iter()
+            .position(|line| line.iter().all(|&i| self.sub_boards[i] == SubBoardState::Won(player)));
+
+        self.outcome = if let Some(line) = line {
+            Outcome::Win { player, line }
+        }
+        else if !self.sub_boards.contains(&SubBoardState::Open) {
+            Outcome::Draw
+        }
+        else {
+            Outcome::Ongoing
+        };
+
+        Ok(self.outcome)
+    }
+
+    // Takes back the last move, used by the search of the computer player
+    pub fn undo_move(&mut self) -> Option<usize> {
+        let cell = self.moves.pop()?;
+
+        // Moves are made only in open sub-boards of not finished games
+        self.board.set(cell, GameCell::None);
+        self.sub_boards[sub_board_of(cell)] = SubBoardState::Open;
+        self.outcome = Outcome::Ongoing;
+
+        Some(cell)
+    }
+
+    fn check_move(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= SIZE * SIZE {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        if !self.active_sub_boards().contains(&sub_board_of(cell)) {
+            return Err(MoveError::InactiveSubBoard);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Moves are pairs of a sub-board and a cell inside of it
+    fn play(moves: &[(usize, usize)]) -> UltimatePosition {
+        let mut position = UltimatePosition::new();
+
+        for &(sub_board, cell) in moves {
+            position.apply_move(sub_board_cells(sub_board)[cell]).unwrap();
+        }
+
+        position
+    }
+
+    #[test]
+    fn numbers_cells_by_sub_boards() {
+        assert_eq!(sub_board_cells(0), [0, 1, 2, 9, 10, 11, 18, 19, 20]);
+        assert_eq!(sub_board_center(4), 40);
+        assert_eq!(sub_board_of(80), 8);
+        assert_eq!(sub_board_of(50), 4);
+        assert_eq!(cell_in_sub_board(50), 8);
+    }
+
+    #[test]
+    fn sends_to_the_sub_board_of_the_cell() {
+        let mut position = play(&[(4, 2)]);
+
+        assert_eq!(position.active_sub_boards(), [2]);
+        assert_eq!(position.apply_move(sub_board_cells(4)[0]), Err(MoveError::InactiveSubBoard));
+        assert_eq!(position.legal_moves().len(), 9);
+
+        assert_eq!(position.apply_move(sub_board_cells(2)[4]), Ok(Outcome::Ongoing));
+        assert_eq!(position.active_sub_boards(), [4]);
+        assert_eq!(position.legal_moves().len(), 8);
+    }
+
+    #[test]
+    fn chooses_freely_when_the_sub_board_is_finished() {
+        // O takes the middle row of the top left sub-board, then X sends it there
+        let mut position = play(&[(0, 0), (0, 3), (3, 0), (0, 4), (4, 0), (0, 5)]);
+
+        assert_eq!(position.sub_board(0), SubBoardState::Won(Player::Second));
+        assert_eq!(position.apply_move(sub_board_cells(5)[0]), Ok(Outcome::Ongoing));
+        assert_eq!(position.active_sub_boards(), [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(position.apply_move(sub_board_cells(0)[1]), Err(MoveError::InactiveSubBoard));
+        assert!(position.is_legal(sub_board_cells(8)[8]));
+    }
+
+    #[test]
+    fn wins_by_a_line_of_sub_boards() {
+        // X takes bottom rows of the top sub-boards, O is sent to the bottom ones and sends X back
+        let mut position = play(&[
+            (0, 6), (6, 1), (1, 6), (6, 2), (2, 6), (6, 0),
+            (0, 7), (7, 1), (1, 7), (7, 2), (2, 7), (7, 0),
+            (0, 8), (8, 1), (1, 8), (8, 2),
+        ]);
+
+        assert_eq!(position.sub_board(0), SubBoardState::Won(Player::First));
+        assert_eq!(position.sub_board(6), SubBoardState::Won(Player::Second));
+        assert_eq!(position.apply_move(sub_board_cells(2)[8]), Ok(Outcome::Win { player: Player::First, line: 0 }));
+        assert!(position.legal_moves().is_empty());
+
+        // Taking the move back opens the sub-board again
+        position.undo_move();
+        assert_eq!(position.sub_board(2), SubBoardState::Open);
+        assert_eq!(position.outcome(), Outcome::Ongoing);
+    }
+}
@@ -0,0 +1,262 @@
+// Game modes and positions of any of them
+use crate::ai::{self, Difficulty};
+use crate::board::{Board, MoveError, Outcome, Player, Position, Rules};
+use crate::notakto::NotaktoPosition;
+use crate::numerical::NumericalPosition;
+use crate::quantum::QuantumPosition;
+use crate::qubic::QubicPosition;
+use crate::ultimate::UltimatePosition;
+use crate::wild::WildPosition;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    Classic, // Tic-tac-toe on any board up to the `MAX_BOARD_SIZE`
+    Gomoku, // Five in a row on the 15×15 board
+    Ultimate, // The 3×3 grid of classic boards
+    Qubic, // 3D tic-tac-toe on the 4×4×4 cube
+    Notakto, // Both players place X, three in a row loses
+    Wild, // Players choose X or O on every move
+    OrderChaos, // Order wants five same marks in a row on the 6×6 board, Chaos prevents it
+    Numerical, // Odd and even numbers, a line with the sum of 15 wins
+    Quantum, // Spooky marks in two cells, cycles of them are measured
+}
+
+impl Mode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Mode::Classic => "classic",
+            Mode::Gomoku => "gomoku",
+            Mode::Ultimate => "ultimate",
+            Mode::Qubic => "qubic",
+            Mode::Notakto => "notakto",
+            Mode::Wild => "wild",
+            Mode::OrderChaos => "order_chaos",
+            Mode::Numerical => "numerical",
+            Mode::Quantum => "quantum",
+        }
+    }
+
+    pub fn from_str(val: &str) -> Option<Self> {
+        match val {
+            "classic" => Some(Mode::Classic),
+            "gomoku" => Some(Mode::Gomoku),
+            "ultimate" => Some(Mode::Ultimate),
+            "qubic" => Some(Mode::Qubic),
+            "notakto" => Some(Mode::Notakto),
+            "wild" => Some(Mode::Wild),
+            "order_chaos" => Some(Mode::OrderChaos),
+            "numerical" => Some(Mode::Numerical),
+            "quantum" => Some(Mode::Quantum),
+            _ => None,
+        }
+    }
+}
+
+// What the move places on the board, chosen by the player in some modes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Piece {
+    Mark, // The mark of the player whose turn it is
+    Symbol(Player), // X or O in wild tic-tac-toe and Order & Chaos
+    Number(u8), // Numerical tic-tac-toe
+    Spooky(usize), // The other cell of the spooky mark in quantum tic-tac-toe
+    Collapse, // The measurement of the cycle in quantum tic-tac-toe, into the cell of the move
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum GamePosition {
+    Board(Position), // Classic and gomoku
+    Ultimate(UltimatePosition),
+    Qubic(QubicPosition),
+    Notakto(NotaktoPosition),
+    Wild(WildPosition), // Wild tic-tac-toe and Order & Chaos
+    Numerical(NumericalPosition),
+    Quantum(QuantumPosition),
+}
+
+impl GamePosition {
+    pub fn new(mode: Mode, rules: Rules) -> Self {
+        match mode {
+            Mode::Classic | Mode::Gomoku => GamePosition::Board(Position::new(rules)),
+            Mode::Ultimate => GamePosition::Ultimate(UltimatePosition::new()),
+            Mode::Qubic => GamePosition::Qubic(QubicPosition::new()),
+            Mode::Notakto => GamePosition::Notakto(NotaktoPosition::new(rules.width / 3)),
+            Mode::Wild => GamePosition::Wild(WildPosition::new(rules)),
+            Mode::OrderChaos => GamePosition::Wild(WildPosition::order_and_chaos()),
+            Mode::Numerical => GamePosition::Numerical(NumericalPosition::new()),
+            Mode::Quantum => GamePosition::Quantum(QuantumPosition::new()),
+        }
+    }
+
+    // Moves of a stored game with their pieces, in the order of `apply_move`. The game is replayed
+    // for telling measurements of quantum tic-tac-toe from spooky marks, which take two cells.
+    pub fn stored_moves(mode: Mode, rules: Rules, moves: &[usize], symbols: &[Player], numbers: &[u8]) -> Result<Vec<(usize, Piece)>, MoveError> {
+        let mut position = GamePosition::new(mode, rules);
+        let mut stored = Vec::new();
+        let mut i = 0;
+
+        while i < moves.len() {
+            let cell = moves[i];
+            let index = stored.len();
+
+            let piece = match &position {
+                GamePosition::Wild(_) => symbols.get(index).map_or(Piece::Mark, |&symbol| Piece::Symbol(symbol)),
+                GamePosition::Numerical(_) => numbers.get(index).map_or(Piece::Mark, |&number| Piece::Number(number)),
+                GamePosition::Quantum(val) if val.cycle().is_some() => Piece::Collapse,
+                GamePosition::Quantum(val) if val.needs_two_cells() => {
+                    i += 1;
+                    Piece::Spooky(*moves.get(i).ok_or(MoveError::SameCell)?)
+                }
+                _ => Piece::Mark,
+            };
+
+            position.apply_move(cell, piece)?;
+            stored.push((cell, piece));
+            i += 1;
+        }
+
+        Ok(stored)
+    }
+
+    // Size of the whole board, layers of the cube are stacked
+    pub fn rules(&self) -> Rules {
+        match self {
+            GamePosition::Board(val) => val.rules(),
+            GamePosition::Ultimate(_) => UltimatePosition::rules(),
+            GamePosition::Qubic(_) => QubicPosition::rules(),
+            GamePosition::Notakto(val) => NotaktoPosition::rules(val.boards()),
+            GamePosition::Wild(val) => val.rules(),
+            GamePosition::Numerical(_) => NumericalPosition::rules(),
+            GamePosition::Quantum(_) => QuantumPosition::rules(),
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        match self {
+            GamePosition::Board(val) => val.board(),
+            GamePosition::Ultimate(val) => val.board(),
+            GamePosition::Qubic(val) => val.board(),
+            GamePosition::Notakto(val) => val.board(),
+            GamePosition::Wild(val) => val.board(),
+            GamePosition::Numerical(val) => val.board(),
+            GamePosition::Quantum(val) => val.board(),
+        }
+    }
+
+    // Cells of moves and measurements in quantum tic-tac-toe, a spooky mark has two cells
+    pub fn moves(&self) -> &[usize] {
+        match self {
+            GamePosition::Board(val) => val.moves(),
+            GamePosition::Ultimate(val) => val.moves(),
+            GamePosition::Qubic(val) => val.moves(),
+            GamePosition::Notakto(val) => val.moves(),
+            GamePosition::Wild(val) => val.moves(),
+            GamePosition::Numerical(val) => val.moves(),
+            GamePosition::Quantum(val) => val.actions(),
+        }
+    }
+
+    // Marks of the moves in modes where players choose them, otherwise empty
+    pub fn symbols(&self) -> &[Player] {
+        match self {
+            GamePosition::Wild(val) => val.symbols(),
+            _ => &[],
+        }
+    }
+
+    // Numbers of the moves in numerical tic-tac-toe, otherwise empty
+    pub fn numbers(&self) -> &[u8] {
+        match self {
+            GamePosition::Numerical(val) => val.played(),
+            _ => &[],
+        }
+    }
+
+    // Number in the cell of numerical tic-tac-toe
+    pub fn number(&self, cell: usize) -> Option<u8> {
+        match self {
+            GamePosition::Numerical(val) => val.number(cell),
+            _ => None,
+        }
+    }
+
+    // Piece which is offered to the player whose turn it is
+    pub fn default_piece(&self) -> Piece {
+        match self {
+            GamePosition::Wild(_) => Piece::Symbol(Player::First),
+            GamePosition::Numerical(val) => val.remaining_numbers(val.turn()).first().map_or(Piece::Mark, |&val| Piece::Number(val)),
+            _ => Piece::Mark,
+        }
+    }
+
+    pub fn turn(&self) -> Player {
+        match self {
+            GamePosition::Board(val) => val.turn(),
+            GamePosition::Ultimate(val) => val.turn(),
+            GamePosition::Qubic(val) => val.turn(),
+            GamePosition::Notakto(val) => val.turn(),
+            GamePosition::Wild(val) => val.turn(),
+            GamePosition::Numerical(val) => val.turn(),
+            GamePosition::Quantum(val) => val.turn(),
+        }
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        match self {
+            GamePosition::Board(val) => val.is_legal(cell),
+            GamePosition::Ultimate(val) => val.is_legal(cell),
+            GamePosition::Qubic(val) => val.is_legal(cell),
+            GamePosition::Notakto(val) => val.is_legal(cell),
+            GamePosition::Wild(val) => val.is_legal(cell),
+            GamePosition::Numerical(val) => val.is_legal(cell),
+            GamePosition::Quantum(val) => val.is_legal(cell),
+        }
+    }
+
+    // The `piece` is used only in modes where players choose it
+    pub fn apply_move(&mut self, cell: usize, piece: Piece) -> Result<Outcome, MoveError> {
+        match (self, piece) {
+            (GamePosition::Board(val), _) => val.apply_move(cell),
+            (GamePosition::Ultimate(val), _) => val.apply_move(cell),
+            (GamePosition::Qubic(val), _) => val.apply_move(cell),
+            (GamePosition::Notakto(val), _) => val.apply_move(cell),
+            (GamePosition::Wild(val), Piece::Symbol(symbol)) => val.apply_move(cell, symbol),
+            (GamePosition::Wild(val), _) => val.apply_move(cell, val.turn()),
+            (GamePosition::Numerical(val), Piece::Number(number)) => val.apply_move(cell, number),
+            (GamePosition::Numerical(_), _) => Err(MoveError::UnavailableNumber),
+            (GamePosition::Quantum(val), Piece::Spooky(other)) => val.apply_move(cell, other),
+            (GamePosition::Quantum(val), Piece::Collapse) => val.collapse(cell),
+            (GamePosition::Quantum(val), _) => val.apply_move(cell, cell), // The last free cell
+        }
+    }
+
+    // Sub-boards of ultimate tic-tac-toe where the next move can be made
+    pub fn active_sub_boards(&self) -> Vec<usize> {
+        match self {
+            GamePosition::Ultimate(val) => val.active_sub_boards(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Move of the computer for the player whose turn it is
+    pub fn choose_move(&self, difficulty: Difficulty) -> (usize, Piece) {
+        match self {
+            GamePosition::Board(val) => (ai::choose_move(val, difficulty), Piece::Mark),
+            GamePosition::Ultimate(val) => (ai::choose_ultimate_move(val, difficulty), Piece::Mark),
+            GamePosition::Qubic(val) => (ai::choose_qubic_move(val, difficulty), Piece::Mark),
+            GamePosition::Notakto(val) => (ai::choose_notakto_move(val, difficulty), Piece::Mark),
+            GamePosition::Wild(val) => {
+                let (cell, symbol) = ai::choose_wild_move(val, difficulty);
+                (cell, Piece::Symbol(symbol))
+            }
+            GamePosition::Numerical(val) => {
+                let (cell, number) = ai::choose_numerical_move(val, difficulty);
+                (cell, Piece::Number(number))
+            }
+            GamePosition::Quantum(val) => match ai::choose_quantum_move(val, difficulty) {
+                [cell, _] if val.cycle().is_some() => (cell, Piece::Collapse),
+                [cell, other] if cell == other => (cell, Piece::Mark),
+                [cell, other] => (cell, Piece::Spooky(other)),
+            },
+        }
+    }
+}
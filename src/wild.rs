@@ -0,0 +1,186 @@
+// Variants where the player chooses X or O on every move. In wild tic-tac-toe
+// whoever completes a line of the same marks wins. In Order & Chaos the first
+// player (Order) wins by five same marks in a row on the 6×6 board, even if
+// the line is completed by the second player (Chaos), and Chaos wins when
+// the board is full without such a line.
+use crate::board::{Board, GameCell, MoveError, Outcome, Player, Rules};
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WildPosition {
+    board: Board,
+    order_and_chaos: bool,
+    moves: Vec<usize>,
+    symbols: Vec<Player>, // Marks of the moves, `Player::First` is X
+    outcome: Outcome,
+}
+
+impl WildPosition {
+    pub fn new(rules: Rules) -> Self {
+        Self {
+            board: Board::new(rules),
+            order_and_chaos: false,
+            moves: Vec::new(),
+            symbols: Vec::new(),
+            outcome: Outcome::Ongoing,
+        }
+    }
+
+    pub fn order_and_chaos() -> Self {
+        Self {
+            order_and_chaos: true,
+            ..Self::new(Self::order_and_chaos_rules())
+        }
+    }
+
+    pub fn order_and_chaos_rules() -> Rules {
+        Rules::new(6, 6, 5).unwrap()
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.board.rules()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn is_order_and_chaos(&self) -> bool {
+        self.order_and_chaos
+    }
+
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    pub fn symbols(&self) -> &[Player] {
+        &self.symbols
+    }
+
+    // Order is the first player in Order & Chaos
+    pub fn turn(&self) -> Player {
+        match self.moves.len() % 2 {
+            0 => Player::First,
+            _ => Player::Second,
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    pub fn is_legal(&self, cell: usize) -> bool {
+        self.check_move(cell).is_ok()
+    }
+
+    pub fn legal_moves(&self) -> Vec<usize> {
+        if self.outcome != Outcome::Ongoing {
+            return Vec::new();
+        }
+
+        (0..self.board.cells().len())
+            .filter(|&cell| self.board.is_empty(cell))
+            .collect()
+    }
+
+    pub fn apply_move(&mut self, cell: usize, symbol: Player) -> Result<Outcome, MoveError> {
+        self.check_move(cell)?;
+
+        let player = self.turn();
+
+        self.board.set(cell, symbol.cell());
+        self.moves.push(cell);
+        self.symbols.push(symbol);
+
+        self.outcome = if let Some((_, line)) = self.board.winning_line_through(cell) {
+            if self.order_and_chaos {
+                Outcome::Win { player: Player::First, line }
+            }
+            else {
+                Outcome::Win { player, line }
+            }
+        }
+        else if self.board.is_full() && self.order_and_chaos {
+            Outcome::Blocked { player: Player::Second }
+        }
+        else if self.board.is_full() {
+            Outcome::Draw
+        }
+        else {
+            Outcome::Ongoing
+        };
+
+        Ok(self.outcome)
+    }
+
+    // Takes back the last move, used by the search of the computer player
+    pub fn undo_move(&mut self) -> Option<(usize, Player)> {
+        let cell = self.moves.pop()?;
+        let symbol = self.symbols.pop()?;
+
+        self.board.set(cell, GameCell::None);
+        self.outcome = Outcome::Ongoing;
+
+        Some((cell, symbol))
+    }
+
+    fn check_move(&self, cell: usize) -> Result<(), MoveError> {
+        if cell >= self.board.cells().len() {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        if self.outcome != Outcome::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if !self.board.is_empty(cell) {
+            return Err(MoveError::Occupied);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn either_mark_wins_for_the_mover() {
+        for symbol in [Player::First, Player::Second] {
+            let mut position = WildPosition::new(Rules::default());
+
+            // The second player completes the top row of the same marks
+            for (cell, mark) in [(0, symbol), (4, symbol.other()), (1, symbol), (8, symbol.other())] {
+                assert_eq!(position.apply_move(cell, mark), Ok(Outcome::Ongoing));
+            }
+
+            assert_eq!(position.apply_move(2, symbol), Ok(Outcome::Win { player: Player::First, line: 0 }));
+            assert_eq!(position.board().get(2), symbol.cell());
+            assert_eq!(position.symbols(), [symbol, symbol.other(), symbol, symbol.other(), symbol]);
+        }
+
+        let mut position = WildPosition::new(Rules::default());
+
+        for cell in [0, 1, 4] {
+            position.apply_move(cell, Player::Second).unwrap();
+        }
+
+        assert_eq!(position.apply_move(8, Player::Second), Ok(Outcome::Win { player: Player::Second, line: 6 }));
+    }
+
+    #[test]
+    fn order_wins_lines_of_chaos() {
+        let mut position = WildPosition::order_and_chaos();
+
+        for cell in 0..4 {
+            position.apply_move(cell, Player::Second).unwrap();
+        }
+
+        assert_eq!(position.turn(), Player::First);
+        position.apply_move(10, Player::First).unwrap();
+
+        // Chaos completes five O in a row
+        let outcome = position.apply_move(4, Player::Second).unwrap();
+        assert_eq!(outcome.winner(), Some(Player::First));
+    }
+}